@@ -1,46 +1,337 @@
 use anyhow::{Context, Result};
-use beancount_staging::AutoCategorizeRule;
-use beancount_staging::reconcile::StagingSource;
+use beancount_staging::reconcile::{
+    AmountSign, CsvAmount, CsvColumn, CsvProfile, MatchingOptions, OfxProfile, StagingSource,
+};
+use beancount_staging::{AutoCategorizeRule, CommitMode, CommitTargetRule};
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigJournal {
     pub files: Vec<PathBuf>,
+    #[serde(default)]
+    pub commit_mode: ConfigCommitMode,
+    /// File with staging entries that should never be imported, not included from the journal
+    #[serde(default)]
+    pub ignored_file: Option<PathBuf>,
+}
+
+/// Where committed transactions are placed in the journal file.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigCommitMode {
+    /// Append at the end of the file
+    #[default]
+    Append,
+    /// Insert in date order
+    DateOrdered,
+}
+
+impl From<ConfigCommitMode> for CommitMode {
+    fn from(mode: ConfigCommitMode) -> Self {
+        match mode {
+            ConfigCommitMode::Append => CommitMode::Append,
+            ConfigCommitMode::DateOrdered => CommitMode::DateOrdered,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(try_from = "RawConfigStaging")]
-pub struct ConfigStaging(pub StagingSource);
+pub struct ConfigStaging {
+    /// Required if there are several staging sources
+    pub name: Option<String>,
+    /// Files, and the command's working directory, are relative to this directory
+    pub cwd: PathBuf,
+    pub source: StagingSource,
+    pub default_account: Option<String>,
+    /// Run the staging command again in this interval while serving the web UI
+    pub refresh_interval: Option<Duration>,
+}
 
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 struct RawConfigStaging {
+    #[serde(default)]
+    name: Option<String>,
+    /// Relative to the config file
+    #[serde(default)]
+    cwd: Option<PathBuf>,
+    /// Environment variables for the command
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// Target account suggested for entries of this source
+    #[serde(default)]
+    default_account: Option<String>,
     #[serde(default)]
     files: Vec<PathBuf>,
     #[serde(default)]
     command: Vec<String>,
+    #[serde(default)]
+    csv: Option<ConfigCsv>,
+    #[serde(default)]
+    ofx: Option<ConfigOfx>,
+    /// Directories the command imports from, its output is cached until they change
+    #[serde(default)]
+    inputs: Vec<PathBuf>,
+    /// Glob patterns of files which rerun the command when they change, e.g. `data/**/*.csv`
+    #[serde(default)]
+    watch: Vec<String>,
+    /// In seconds
+    #[serde(default)]
+    refresh_interval: Option<u64>,
 }
 
 impl TryFrom<RawConfigStaging> for ConfigStaging {
     type Error = String;
 
     fn try_from(raw: RawConfigStaging) -> Result<Self, Self::Error> {
-        match (raw.files.is_empty(), raw.command.is_empty()) {
-            (false, true) => Ok(ConfigStaging(StagingSource::Files(raw.files))),
-            (true, false) => Ok(ConfigStaging(StagingSource::Command {
+        let refresh_interval = raw.refresh_interval.map(Duration::from_secs);
+        if raw.command.is_empty()
+            && (!raw.inputs.is_empty()
+                || !raw.watch.is_empty()
+                || !raw.env.is_empty()
+                || refresh_interval.is_some())
+        {
+            return Err(
+                "staging 'inputs', 'watch', 'env' and 'refresh_interval' require a 'command'"
+                    .to_string(),
+            );
+        }
+        // Names end up in file names of cached command output
+        if let Some(name) = &raw.name
+            && (name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        {
+            return Err(format!(
+                "staging name {:?} may only contain letters, digits, '-' and '_'",
+                name
+            ));
+        }
+
+        let specified = [
+            !raw.files.is_empty(),
+            !raw.command.is_empty(),
+            raw.csv.is_some(),
+            raw.ofx.is_some(),
+        ];
+        match specified.iter().filter(|&&specified| specified).count() {
+            0 => {
+                return Err(
+                    "staging section must have either 'files', 'command', 'csv' or 'ofx' specified"
+                        .to_string(),
+                );
+            }
+            1 => {}
+            _ => {
+                return Err(
+                    "staging section can only have one of 'files', 'command', 'csv' and 'ofx' specified"
+                        .to_string(),
+                );
+            }
+        }
+        let source = if let Some(csv) = raw.csv {
+            StagingSource::Csv(csv.compile()?)
+        } else if let Some(ofx) = raw.ofx {
+            StagingSource::Ofx(ofx.into())
+        } else if !raw.command.is_empty() {
+            StagingSource::Command {
                 command: raw.command,
                 cwd: PathBuf::from("."),
-            })),
-            (true, true) => {
-                Err("staging section must have either 'files' or 'command' specified".to_string())
+                inputs: raw.inputs,
+                watch: raw.watch,
+                env: raw.env,
             }
-            (false, false) => {
-                Err("staging section cannot have both 'files' and 'command' specified".to_string())
+        } else {
+            StagingSource::Files(raw.files)
+        };
+        Ok(ConfigStaging {
+            name: raw.name,
+            cwd: raw.cwd.unwrap_or_else(|| PathBuf::from(".")),
+            source,
+            default_account: raw.default_account,
+            refresh_interval,
+        })
+    }
+}
+
+/// TOML schema for importing a bank's CSV export without an importer.
+///
+/// Columns are given by their name in the header or their index starting at 0. The amount
+/// is either in `amount_column`, or in `debit_column` and `credit_column`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigCsv {
+    /// Relative to the staging `cwd`
+    pub files: Vec<PathBuf>,
+    /// Account of the imported posting
+    pub account: String,
+    pub date_column: ConfigCsvColumn,
+    /// Using `%Y`, `%y`, `%m` and `%d`, defaults to `%Y-%m-%d`
+    #[serde(default)]
+    pub date_format: Option<String>,
+    #[serde(default)]
+    pub amount_column: Option<ConfigCsvColumn>,
+    #[serde(default)]
+    pub debit_column: Option<ConfigCsvColumn>,
+    #[serde(default)]
+    pub credit_column: Option<ConfigCsvColumn>,
+    #[serde(default)]
+    pub amount_sign: ConfigAmountSign,
+    #[serde(default)]
+    pub payee_column: Option<ConfigCsvColumn>,
+    /// Joined with spaces
+    #[serde(default)]
+    pub narration_columns: Vec<ConfigCsvColumn>,
+    /// Defaults to `EUR`
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub currency_column: Option<ConfigCsvColumn>,
+    #[serde(default)]
+    pub delimiter: Option<char>,
+    #[serde(default)]
+    pub decimal_separator: Option<char>,
+    /// Like `utf-8` (the default) or `windows-1252`
+    #[serde(default)]
+    pub encoding: Option<String>,
+    /// Lines before the header
+    #[serde(default)]
+    pub skip_rows: usize,
+    #[serde(default)]
+    pub has_header: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigCsvColumn {
+    Index(usize),
+    Name(String),
+}
+
+impl From<ConfigCsvColumn> for CsvColumn {
+    fn from(column: ConfigCsvColumn) -> Self {
+        match column {
+            ConfigCsvColumn::Index(index) => CsvColumn::Index(index),
+            ConfigCsvColumn::Name(name) => CsvColumn::Name(name),
+        }
+    }
+}
+
+/// What a positive amount in the CSV means for the imported account.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigAmountSign {
+    /// Positive amounts are deposits
+    #[default]
+    Normal,
+    /// Positive amounts are withdrawals, like in some credit card exports
+    Inverted,
+}
+
+impl From<ConfigAmountSign> for AmountSign {
+    fn from(sign: ConfigAmountSign) -> Self {
+        match sign {
+            ConfigAmountSign::Normal => AmountSign::Normal,
+            ConfigAmountSign::Inverted => AmountSign::Inverted,
+        }
+    }
+}
+
+impl ConfigCsv {
+    fn compile(self) -> Result<CsvProfile, String> {
+        let amount = match (self.amount_column, self.debit_column, self.credit_column) {
+            (Some(amount), None, None) => CsvAmount::Signed(amount.into()),
+            (None, Some(debit), Some(credit)) => CsvAmount::DebitCredit {
+                debit: debit.into(),
+                credit: credit.into(),
+            },
+            _ => {
+                return Err(
+                    "staging csv needs either 'amount_column' or 'debit_column' and 'credit_column'"
+                        .to_string(),
+                );
             }
+        };
+
+        let defaults = CsvProfile::default();
+        Ok(CsvProfile {
+            files: self.files,
+            account: self.account,
+            date: self.date_column.into(),
+            date_format: self.date_format.unwrap_or(defaults.date_format),
+            amount,
+            amount_sign: self.amount_sign.into(),
+            payee: self.payee_column.map(Into::into),
+            narration: self.narration_columns.into_iter().map(Into::into).collect(),
+            currency: self.currency.unwrap_or(defaults.currency),
+            currency_column: self.currency_column.map(Into::into),
+            delimiter: self.delimiter.unwrap_or(defaults.delimiter),
+            decimal_separator: self.decimal_separator.unwrap_or(defaults.decimal_separator),
+            encoding: self.encoding.unwrap_or(defaults.encoding),
+            skip_rows: self.skip_rows,
+            has_header: self.has_header.unwrap_or(defaults.has_header),
+        })
+    }
+}
+
+/// TOML schema for importing OFX/QFX statement downloads.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOfx {
+    /// Relative to the staging `cwd`
+    pub files: Vec<PathBuf>,
+    /// Account for statements not listed in `accounts`
+    #[serde(default)]
+    pub account: Option<String>,
+    /// Accounts by the `ACCTID` of the statement
+    #[serde(default)]
+    pub accounts: BTreeMap<String, String>,
+}
+
+impl From<ConfigOfx> for OfxProfile {
+    fn from(ofx: ConfigOfx) -> Self {
+        OfxProfile {
+            files: ofx.files,
+            account: ofx.account,
+            accounts: ofx.accounts,
+        }
+    }
+}
+
+/// Accept a single `[staging]` table as well as several `[[staging]]` tables.
+fn one_or_many_staging<'de, D>(deserializer: D) -> Result<Vec<ConfigStaging>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+
+    struct Visitor;
+
+    impl<'de> serde::de::Visitor<'de> for Visitor {
+        type Value = Vec<ConfigStaging>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a [staging] table or [[staging]] tables")
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            Ok(vec![ConfigStaging::deserialize(
+                MapAccessDeserializer::new(map),
+            )?])
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            Vec::deserialize(SeqAccessDeserializer::new(seq))
         }
     }
+
+    deserializer.deserialize_any(Visitor)
 }
 
 /// TOML schema for an auto-categorization rule.
@@ -86,13 +377,71 @@ impl ConfigAutoCategorizeRule {
     }
 }
 
+/// TOML schema for a commit target rule.
+///
+/// `match_source_account` is a glob where `*` matches any sequence of characters.
+/// `file` is relative to the config file and may contain `{year}` and `{month}`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigCommitTargetRule {
+    pub match_source_account: String,
+    pub file: PathBuf,
+}
+
+impl ConfigCommitTargetRule {
+    pub fn compile(self, base_dir: &Path) -> Result<CommitTargetRule> {
+        CommitTargetRule::new(&self.match_source_account, base_dir.join(self.file))
+            .context("Invalid commit_target.match_source_account")
+    }
+}
+
+/// TOML schema for tolerant matching of staging entries.
+///
+/// Disabled unless at least one of the fields is set.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigMatching {
+    /// Allow the journal date to differ from the staging date by this many days
+    #[serde(default)]
+    pub date_window_days: u32,
+    /// Minimum similarity (between 0 and 1) of payee and narration
+    #[serde(default)]
+    pub min_similarity: Option<f64>,
+    /// Merge opposite one-legged staging entries of two accounts up to this many days apart
+    /// into one transfer
+    #[serde(default)]
+    pub transfer_window_days: Option<u32>,
+}
+
+impl ConfigMatching {
+    pub fn compile(self) -> Result<MatchingOptions> {
+        if let Some(min_similarity) = self.min_similarity {
+            anyhow::ensure!(
+                (0.0..=1.0).contains(&min_similarity),
+                "matching.min_similarity must be between 0 and 1, got {}",
+                min_similarity
+            );
+        }
+        Ok(MatchingOptions {
+            date_window_days: self.date_window_days,
+            min_similarity: self.min_similarity,
+            transfer_window_days: self.transfer_window_days,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub journal: ConfigJournal,
-    pub staging: ConfigStaging,
+    #[serde(deserialize_with = "one_or_many_staging")]
+    pub staging: Vec<ConfigStaging>,
     #[serde(default)]
     pub auto_categorize: Vec<ConfigAutoCategorizeRule>,
+    #[serde(default)]
+    pub commit_target: Vec<ConfigCommitTargetRule>,
+    #[serde(default)]
+    pub matching: ConfigMatching,
 }
 
 impl Config {
@@ -126,10 +475,29 @@ impl Config {
 
         let config: Config = toml::from_str(&contents)
             .with_context(|| format!("Failed to parse config file: {}", config_path.display()))?;
+        config
+            .check_staging_names()
+            .with_context(|| format!("Invalid config file: {}", config_path.display()))?;
 
         Ok((base_dir, config))
     }
 
+    /// Several staging sources need unique names to tell them apart.
+    fn check_staging_names(&self) -> Result<()> {
+        if self.staging.len() < 2 {
+            return Ok(());
+        }
+        let mut names = std::collections::HashSet::new();
+        for staging in &self.staging {
+            let name = staging
+                .name
+                .as_deref()
+                .context("Every [[staging]] source needs a 'name' if there are several")?;
+            anyhow::ensure!(names.insert(name), "Duplicate staging name: {}", name);
+        }
+        Ok(())
+    }
+
     pub fn find_and_load() -> Result<Option<(PathBuf, Self)>> {
         if let Some(config_path) = Self::find_config_in_dir(Path::new(".")) {
             return Self::load_from_file(&config_path).map(Some);
@@ -0,0 +1,59 @@
+//! `beancount-staging ignore`: never import matching staging entries again.
+//!
+//! The entries are appended to the `ignored_file` configured in the `[journal]` section,
+//! tagged `#ignored`. That file is not part of the journal, but reconciliation treats its
+//! entries as matched, so they no longer show up in `diff` and `serve`.
+
+use anstyle::{AnsiColor, Color, Style};
+use anyhow::{Context, Result};
+use beancount_parser::DirectiveContent;
+use beancount_staging::reconcile::{ReconcileConfig, ReconcileItemKind};
+
+pub fn run_ignore(
+    config: ReconcileConfig,
+    pattern: &str,
+    date: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    let pattern = regex::Regex::new(pattern)
+        .with_context(|| format!("Invalid ignore pattern: {:?}", pattern))?;
+    if config.ignored_file.is_none() {
+        anyhow::bail!("No ignored_file configured in the [journal] section");
+    }
+
+    let state = config.read()?;
+    let results = state.reconcile()?;
+    let to_ignore: Vec<_> = results
+        .iter()
+        .filter_map(|item| match item.item {
+            ReconcileItemKind::OnlyInStaging(directive) => Some(directive),
+            _ => None,
+        })
+        .filter(|directive| date.is_none_or(|date| directive.date.to_string() == date))
+        .filter(|directive| match &directive.content {
+            DirectiveContent::Transaction(txn) => {
+                let payee = txn.payee.as_deref().unwrap_or("");
+                let narration = txn.narration.as_deref().unwrap_or("");
+                pattern.is_match(payee) || pattern.is_match(narration)
+            }
+            _ => false,
+        })
+        .collect();
+
+    if to_ignore.is_empty() {
+        println!("No staging entry matches");
+        return Ok(());
+    }
+
+    if !dry_run {
+        config.ignore(&to_ignore)?;
+    }
+
+    let style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Yellow)));
+    let title = if dry_run { "Would ignore" } else { "Ignored" };
+    for directive in &to_ignore {
+        println!("{style}━━━ {title} ━━━{style:#}");
+        println!("{}", directive);
+    }
+    Ok(())
+}
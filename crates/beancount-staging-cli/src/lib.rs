@@ -1,16 +1,20 @@
 mod config;
+mod ignore;
 mod lint;
 #[allow(dead_code)]
 mod review;
 mod show;
+mod undo;
 
 pub use beancount_staging;
 
 use std::path::PathBuf;
 
 use anyhow::Result;
-use beancount_staging::AutoCategorizeRule;
-use beancount_staging::reconcile::StagingSource;
+use beancount_staging::reconcile::{
+    CsvProfile, OfxProfile, ReconcileConfig, StagingSource, StagingSourceConfig,
+};
+use beancount_staging::{AutoCategorizeRule, CommitMode, CommitTargetRule};
 use clap::{Args as ClapArgs, CommandFactory as _, Parser, Subcommand, error::ErrorKind};
 
 #[derive(Parser)]
@@ -63,9 +67,28 @@ enum Commands {
         /// Include transactions that only exist in journal (not in staging)
         #[arg(long)]
         include_only_journal: bool,
+
+        /// Only show entries of this [[staging]] source
+        #[arg(long)]
+        source: Option<String>,
     },
     /// Check [[auto_categorize]] rules against the journal history
     Lint,
+    /// Remove the most recently committed staging transaction from the journal
+    Undo,
+    /// Never import matching staging entries again, by writing them into [journal] ignored_file
+    Ignore {
+        /// Regex matched against payee and narration (substring match)
+        pattern: String,
+
+        /// Only ignore entries on this date (YYYY-MM-DD)
+        #[arg(long)]
+        date: Option<String>,
+
+        /// Only show which entries would be ignored
+        #[arg(long)]
+        dry_run: bool,
+    },
     // /// Interactively review and stage transactions in the terminal
     // Cli,
 }
@@ -98,20 +121,81 @@ pub async fn run(args: impl IntoIterator<Item = String>) -> Result<()> {
         })
         .unwrap_or_default();
 
-    // Extract staging source from config (either files or command)
-    let mut staging_source = config.as_mut().map(|(base_dir, c)| match &c.staging.0 {
-        StagingSource::Files(files) => StagingSource::Files(
-            files
+    // Extract staging sources from config (each either files or command)
+    let mut staging_sources = config
+        .as_mut()
+        .map(|(base_dir, c)| {
+            c.staging
                 .iter()
-                .map(|path| base_dir.join(path))
-                .collect::<Vec<_>>(),
-        ),
-        StagingSource::Command { command, cwd: _ } => StagingSource::Command {
-            command: command.clone(),
-            cwd: base_dir.clone(),
-        },
+                .map(|staging| {
+                    let cwd = base_dir.join(&staging.cwd);
+                    let source = match &staging.source {
+                        StagingSource::Files(files) => StagingSource::Files(
+                            files.iter().map(|path| cwd.join(path)).collect::<Vec<_>>(),
+                        ),
+                        StagingSource::Command {
+                            command,
+                            cwd: _,
+                            inputs,
+                            watch,
+                            env,
+                        } => StagingSource::Command {
+                            command: command.clone(),
+                            cwd: cwd.clone(),
+                            inputs: inputs.clone(),
+                            watch: watch.clone(),
+                            env: env.clone(),
+                        },
+                        StagingSource::Csv(profile) => StagingSource::Csv(CsvProfile {
+                            files: profile.files.iter().map(|path| cwd.join(path)).collect(),
+                            ..profile.clone()
+                        }),
+                        StagingSource::Ofx(profile) => StagingSource::Ofx(OfxProfile {
+                            files: profile.files.iter().map(|path| cwd.join(path)).collect(),
+                            ..profile.clone()
+                        }),
+                    };
+                    StagingSourceConfig {
+                        name: staging.name.clone(),
+                        source,
+                        default_account: staging.default_account.clone(),
+                        refresh_interval: staging.refresh_interval,
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let commit_mode: CommitMode = config
+        .as_ref()
+        .map(|(_, c)| c.journal.commit_mode.into())
+        .unwrap_or_default();
+
+    let ignored_file = config.as_ref().and_then(|(base_dir, c)| {
+        c.journal
+            .ignored_file
+            .as_ref()
+            .map(|path| base_dir.join(path))
     });
 
+    // Compile commit target rules from config, relative to the config directory
+    let commit_targets: Vec<CommitTargetRule> = config
+        .as_mut()
+        .map(|(base_dir, c)| {
+            std::mem::take(&mut c.commit_target)
+                .into_iter()
+                .map(|r| r.compile(base_dir))
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let matching = config
+        .as_mut()
+        .map(|(_, c)| std::mem::take(&mut c.matching).compile())
+        .transpose()?
+        .unwrap_or_default();
+
     // Compile auto-categorization rules from config
     let auto_rules: Vec<AutoCategorizeRule> = config
         .map(|(_, c)| {
@@ -128,7 +212,7 @@ pub async fn run(args: impl IntoIterator<Item = String>) -> Result<()> {
         journal_paths = args.files.journal_file;
     }
     if !args.files.staging_file.is_empty() {
-        staging_source = Some(StagingSource::Files(args.files.staging_file));
+        staging_sources = vec![StagingSource::Files(args.files.staging_file).into()];
     }
 
     // Journal is always required.
@@ -150,25 +234,31 @@ pub async fn run(args: impl IntoIterator<Item = String>) -> Result<()> {
         return lint::run_lint(journal_paths, &auto_rules);
     }
 
-    if staging_source.is_none() {
+    if staging_sources.is_empty() {
         cmd.error(
             ErrorKind::MissingRequiredArgument,
             "Staging file path or command required:\n    Pass via --staging-file <STAGING_FILE> or beancount-staging.toml",
         )
         .exit();
     }
-    let staging_source = staging_source.unwrap();
+
+    let mut reconcile_config = ReconcileConfig::with_sources(journal_paths, staging_sources);
+    reconcile_config.commit_targets = commit_targets;
+    reconcile_config.commit_mode = commit_mode;
+    reconcile_config.matching = matching;
+    reconcile_config.ignored_file = ignored_file;
 
     match command {
         Commands::Diff {
             debug,
             include_only_journal,
+            source,
         } => show::show_diff(
-            journal_paths,
-            staging_source,
+            reconcile_config,
             &auto_rules,
             debug,
             include_only_journal,
+            source.as_deref(),
         ),
         Commands::Serve { port, socket } => {
             let listener = if let Some(socket_path) = socket {
@@ -178,8 +268,14 @@ pub async fn run(args: impl IntoIterator<Item = String>) -> Result<()> {
                     port.unwrap_or(beancount_staging_web::DEFAULT_PORT),
                 )
             };
-            beancount_staging_web::run(journal_paths, staging_source, auto_rules, listener).await
+            beancount_staging_web::run(reconcile_config, auto_rules, listener).await
         }
+        Commands::Undo => undo::run_undo(reconcile_config),
+        Commands::Ignore {
+            pattern,
+            date,
+            dry_run,
+        } => ignore::run_ignore(reconcile_config, &pattern, date.as_deref(), dry_run),
         Commands::Lint => unreachable!("handled above"),
         /*Commands::Cli => {
             review::review_interactive(journal_paths, staging_source)
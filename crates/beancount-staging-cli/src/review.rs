@@ -150,9 +150,10 @@ fn run_review_loop(
                             let directive = staging_items[current_index];
                             match beancount_staging::commit_transaction(
                                 directive,
-                                Some(expense_account.as_str()),
-                                None, // payee unchanged
-                                None, // narration unchanged
+                                &beancount_staging::CommitOverrides {
+                                    expense_account: Some(expense_account.as_str()),
+                                    ..Default::default()
+                                },
                                 beancount_staging::SourceMetaTarget::Transaction,
                                 journal_path,
                             ) {
@@ -1,21 +1,25 @@
-use std::path::PathBuf;
-
 use anstyle::{AnsiColor, Color, Style};
 use anyhow::Result;
 use beancount_parser::DirectiveContent;
-use beancount_staging::AutoCategorizeRule;
 use beancount_staging::reconcile::{
-    MismatchReason, ReconcileConfig, ReconcileItemKind, StagingSource,
+    MismatchReason, ReconcileConfig, ReconcileItemKind, staging_origin,
 };
+use beancount_staging::{AutoCategorizeRule, Directive};
 
 pub fn show_diff(
-    journal: Vec<PathBuf>,
-    staging_source: StagingSource,
+    config: ReconcileConfig,
     auto_rules: &[AutoCategorizeRule],
     debug: bool,
     include_only_journal: bool,
+    source: Option<&str>,
 ) -> Result<()> {
-    let config = ReconcileConfig::new(journal, staging_source);
+    if let Some(source) = source {
+        anyhow::ensure!(
+            config.staging_source_names().contains(&source),
+            "Unknown staging source: {}",
+            source
+        );
+    }
     let state = config.read()?;
     let results = state.reconcile()?;
 
@@ -23,10 +27,19 @@ pub fn show_diff(
     let staging_style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Green)));
     let debug_style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::BrightBlack)));
 
+    let fuzzy_style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Cyan)));
+
     let mut journal_count = 0;
     let mut staging_count = 0;
+    let mut fuzzy_count = 0;
+    let mut transfer_count = 0;
 
     for item in &results {
+        if let Some(source) = source
+            && !from_source(&item.item, source)
+        {
+            continue;
+        }
         match &item.item {
             ReconcileItemKind::OnlyInJournal(directive) => {
                 if let DirectiveContent::Open(_)
@@ -51,13 +64,18 @@ pub fn show_diff(
                 println!("{staging_style}━━━ Only in Staging (needs review) ━━━{staging_style:#}");
                 println!("{}", directive);
 
-                if debug && !item.mismatch_reasons.is_empty() {
+                let mismatch_reasons = if debug {
+                    item.mismatch_reasons()
+                } else {
+                    Vec::new()
+                };
+                if !mismatch_reasons.is_empty() {
                     println!();
                     println!(
                         "{debug_style}Debug: Checked against {} journal transaction(s) on same date:{debug_style:#}",
-                        item.mismatch_reasons.len()
+                        mismatch_reasons.len()
                     );
-                    for (idx, journal_item, reason) in &item.mismatch_reasons {
+                    for (idx, journal_item, reason) in &mismatch_reasons {
                         if let MismatchReason::DifferentDirectiveType = reason {
                             continue;
                         }
@@ -87,11 +105,57 @@ pub fn show_diff(
                 println!();
                 staging_count += 1;
             }
+            ReconcileItemKind::FuzzyMatch {
+                journal,
+                staging,
+                similarity,
+            } => {
+                println!(
+                    "{fuzzy_style}━━━ Fuzzy match ({:.0}% similar, please confirm) ━━━{fuzzy_style:#}",
+                    similarity * 100.0
+                );
+                println!("{debug_style}; journal{debug_style:#}");
+                println!("{}", journal);
+                println!("{debug_style}; staging{debug_style:#}");
+                println!("{}", staging);
+                println!();
+                fuzzy_count += 1;
+            }
+            ReconcileItemKind::Transfer { outgoing, incoming } => {
+                println!(
+                    "{staging_style}━━━ Transfer (needs review, commit as one) ━━━{staging_style:#}"
+                );
+                println!("{debug_style}; outgoing{debug_style:#}");
+                println!("{}", outgoing);
+                println!("{debug_style}; incoming{debug_style:#}");
+                println!("{}", incoming);
+                println!();
+                transfer_count += 1;
+            }
+            // Matched, only shown to explain why several entries disappeared from review
+            ReconcileItemKind::CombinedMatch { journal, staging } => {
+                if debug {
+                    println!(
+                        "{debug_style}━━━ Combined match ({} journal, {} staging) ━━━{debug_style:#}",
+                        journal.len(),
+                        staging.len()
+                    );
+                    for directive in journal {
+                        println!("{debug_style}; journal{debug_style:#}");
+                        println!("{}", directive);
+                    }
+                    for directive in staging {
+                        println!("{debug_style}; staging{debug_style:#}");
+                        println!("{}", directive);
+                    }
+                    println!();
+                }
+            }
         }
     }
 
     // Summary
-    if journal_count == 0 && staging_count == 0 {
+    if journal_count == 0 && staging_count == 0 && fuzzy_count == 0 && transfer_count == 0 {
         println!("✓ All transactions match!");
     } else {
         println!("━━━ Summary ━━━");
@@ -107,7 +171,33 @@ pub fn show_diff(
                 s = if journal_count == 1 { "" } else { "s" }
             );
         }
+        if transfer_count > 0 {
+            println!(
+                "  {staging_style}{transfer_count}{staging_style:#} transfer{s} staging",
+                s = if transfer_count == 1 { "" } else { "s" }
+            );
+        }
+        if fuzzy_count > 0 {
+            println!(
+                "  {fuzzy_style}{fuzzy_count}{fuzzy_style:#} fuzzy match{es} to confirm",
+                es = if fuzzy_count == 1 { "" } else { "es" }
+            );
+        }
     }
 
     Ok(())
 }
+
+/// Whether any staging entry of `item` came from the staging source `source`.
+fn from_source(item: &ReconcileItemKind<'_>, source: &str) -> bool {
+    let staging: Vec<&Directive> = match item {
+        ReconcileItemKind::OnlyInJournal(_) => Vec::new(),
+        ReconcileItemKind::OnlyInStaging(directive) => vec![directive],
+        ReconcileItemKind::FuzzyMatch { staging, .. } => vec![staging],
+        ReconcileItemKind::Transfer { outgoing, incoming } => vec![outgoing, incoming],
+        ReconcileItemKind::CombinedMatch { staging, .. } => staging.clone(),
+    };
+    staging
+        .into_iter()
+        .any(|directive| staging_origin(directive) == Some(source))
+}
@@ -0,0 +1,48 @@
+//! `beancount-staging undo`: revert the most recent commit of a staging transaction.
+//!
+//! Commits are appended to their target journal file, so the last entry in that file
+//! which matches a staging directive is the one that gets removed. With
+//! `[[commit_target]]` rules there can be several journal files; the most recently
+//! modified one is assumed to hold the latest commit. After that the staging directive
+//! is unreviewed again and shows up in `diff` and `serve`.
+//!
+//! With `commit_mode = "date-ordered"` the latest commit can't be told apart, so undo
+//! refuses to guess.
+
+use std::path::PathBuf;
+
+use anstyle::{AnsiColor, Color, Style};
+use anyhow::Result;
+use beancount_staging::reconcile::ReconcileConfig;
+
+pub fn run_undo(config: ReconcileConfig) -> Result<()> {
+    let state = config.read()?;
+
+    // Commit targets are included from the main journal, so they are all journal files.
+    // A date override may have moved a commit into any of them.
+    let mut targets: Vec<PathBuf> = state
+        .journal_sourceset
+        .iter()
+        .filter(|path| path.exists())
+        .cloned()
+        .collect();
+    targets.sort();
+    targets.sort_by_key(|path| std::cmp::Reverse(path.metadata().and_then(|m| m.modified()).ok()));
+
+    for journal_path in targets {
+        if let Some(removed) =
+            beancount_staging::uncommit_last(&state.staging, &journal_path, config.commit_mode)?
+        {
+            let style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Yellow)));
+            println!(
+                "{style}━━━ Removed from {} ━━━{style:#}",
+                journal_path.display()
+            );
+            println!("{}", removed);
+            return Ok(());
+        }
+    }
+
+    println!("No committed staging transaction found");
+    Ok(())
+}
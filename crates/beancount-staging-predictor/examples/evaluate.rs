@@ -7,7 +7,7 @@ use beancount_staging_predictor::{
     training::extract_training_examples,
 };
 use clap::Parser;
-use shared::evaluation::{evaluate, train_test_split};
+use shared::evaluation::{evaluate, train_test_split, without_amount_and_date};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -92,6 +92,17 @@ fn main() -> Result<()> {
 
             println!("\n{}", metrics.report(predictor.name()));
 
+            println!("Training again without amount and date features...");
+            let text_only =
+                DecisionTreePredictor::<Alpha>::train(&without_amount_and_date(&train_examples));
+            let text_only_metrics = evaluate(&text_only, &without_amount_and_date(&test_examples));
+            println!(
+                "  Accuracy: {:.1}% with, {:.1}% without amount and date features ({:+.1}%)",
+                metrics.accuracy * 100.0,
+                text_only_metrics.accuracy * 100.0,
+                (metrics.accuracy - text_only_metrics.accuracy) * 100.0,
+            );
+
             if metrics.accuracy < 0.5 {
                 anyhow::bail!("Accuracy below 50% - predictor needs improvement");
             }
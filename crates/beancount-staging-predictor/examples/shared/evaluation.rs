@@ -17,6 +17,19 @@ pub fn train_test_split(
     (train, test)
 }
 
+/// The examples without amount, currency and date, to compare against the text features only.
+pub fn without_amount_and_date(examples: &[TrainingExample]) -> Vec<TrainingExample> {
+    examples
+        .iter()
+        .map(|example| TrainingExample {
+            amount: None,
+            currency: None,
+            date: None,
+            ..example.clone()
+        })
+        .collect()
+}
+
 pub fn evaluate(
     predictor: &impl Predictor,
     test_examples: &[TrainingExample],
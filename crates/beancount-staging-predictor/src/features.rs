@@ -1,12 +1,15 @@
 /// Feature extraction for machine learning predictors
 use crate::{
-    TrainingExample,
+    Decimal, TrainingExample,
     preprocessing::{Alpha, Preprocessor},
 };
+use beancount_parser::Date;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct FeatureExtractor<P: Preprocessor = Alpha> {
     /// Vocabulary: maps feature name to index
     vocabulary: HashMap<String, usize>,
@@ -79,6 +82,22 @@ impl<P: Preprocessor + Default> FeatureExtractor<P> {
             features.push(format!("desc:{} {}", window[0], window[1]));
         }
 
+        // Amount features, rent and subscriptions land in the same bucket every month
+        if let Some(amount) = example.amount {
+            let sign = if amount.is_sign_negative() { "-" } else { "+" };
+            features.push(format!("sign:{}", sign));
+            features.push(format!("amount:{}", amount_bucket(amount)));
+        }
+        if let Some(currency) = &example.currency {
+            features.push(format!("currency:{}", currency));
+        }
+
+        // Calendar features, e.g. salary at the end of the month
+        if let Some((weekday, day)) = example.date.as_ref().and_then(weekday_and_day) {
+            features.push(format!("weekday:{}", WEEKDAYS[weekday]));
+            features.push(format!("month_day:{}", day));
+        }
+
         features
     }
 
@@ -106,6 +125,38 @@ impl<P: Preprocessor + Default> FeatureExtractor<P> {
     pub fn feature_count(&self) -> usize {
         self.feature_count
     }
+
+    /// Feature names in the order of the feature vector
+    pub fn feature_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.vocabulary.iter().collect();
+        names.sort_by_key(|&(_, &idx)| idx);
+        names.into_iter().map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+/// Upper bounds of the amount buckets, roughly logarithmic
+const AMOUNT_BUCKETS: [u32; 12] = [5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000, 10000, 50000];
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Bucket of the absolute amount, e.g. `<50` for 25.00 or -49.99.
+fn amount_bucket(amount: Decimal) -> String {
+    let amount = amount.abs();
+    match AMOUNT_BUCKETS
+        .iter()
+        .find(|&&bound| amount < Decimal::from(bound))
+    {
+        Some(bound) => format!("<{}", bound),
+        None => format!(">={}", AMOUNT_BUCKETS[AMOUNT_BUCKETS.len() - 1]),
+    }
+}
+
+/// Weekday (0 is Monday) and day of month of `date`.
+fn weekday_and_day(date: &Date) -> Option<(usize, i64)> {
+    let day = date.to_string().get(8..10)?.parse().ok()?;
+    // 1970-01-01 was a Thursday
+    let weekday = (beancount_staging::day_number(date) + 3).rem_euclid(7) as usize;
+    Some((weekday, day))
 }
 
 #[cfg(test)]
@@ -123,6 +174,10 @@ mod tests {
             source_account: source.parse().unwrap(),
             payee: payee.map(|s| s.to_string()),
             narration: narration.to_string(),
+            amount: None,
+            currency: None,
+            date: None,
+            weight: 1.0,
             target_account: target.parse().unwrap(),
         }
     }
@@ -238,4 +293,43 @@ mod tests {
         // Should be all zeros (no matching features)
         assert_eq!(vec.iter().filter(|&&v| v > 0.0).count(), 0);
     }
+
+    #[test]
+    fn test_amount_and_calendar_features() {
+        let source = r#"
+2026-01-30 * "Landlord" "Rent January"
+    Assets:Checking  -950.00 EUR
+    Expenses:Rent  950.00 EUR
+"#;
+        let directives = beancount_parser::parse::<Decimal>(source)
+            .unwrap()
+            .directives;
+        let examples = crate::training::extract_training_examples(&directives);
+
+        let features = FeatureExtractor::<Smart>::extract_feature_names(&examples[0], &Smart);
+        let features: Vec<_> = features
+            .iter()
+            .filter(|name| {
+                ["sign:", "amount:", "currency:", "weekday:", "month_day:"]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+            })
+            .collect();
+        insta::assert_debug_snapshot!(features, @r#"
+        [
+            "sign:-",
+            "amount:<1000",
+            "currency:EUR",
+            "weekday:fri",
+            "month_day:30",
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_amount_bucket() {
+        assert_eq!(amount_bucket(Decimal::new(-4999, 2)), "<50");
+        assert_eq!(amount_bucket(Decimal::from(50)), "<100");
+        assert_eq!(amount_bucket(Decimal::from(120_000)), ">=50000");
+    }
 }
@@ -1,21 +1,32 @@
 pub mod features;
+pub mod model;
 pub mod predictor;
 pub mod preprocessing;
+pub mod splits;
 pub mod training;
 
-pub use beancount_staging::{Decimal, Directive, Result, Transaction};
+pub use beancount_staging::{Decimal, Directive, Posting, Result, Transaction};
 pub use predictor::{
     DecisionTreePredictor, MultinomialNBPredictor, PayeeFrequencyPredictor, Predictor,
-    RandomForestPredictor,
+    RandomForestPredictor, RankedPrediction,
 };
 
-use beancount_parser::Account;
+use beancount_parser::{Account, Date};
 
 #[derive(Debug, Clone)]
 pub struct TrainingExample {
     pub source_account: Account,
     pub payee: Option<String>,
     pub narration: String,
+    /// Amount of the source posting
+    pub amount: Option<Decimal>,
+    pub currency: Option<String>,
+    pub date: Option<Date>,
+    /// Share of the target account in the amount of a split transaction, 1 otherwise.
+    ///
+    /// `PayeeFrequencyPredictor` sums it up; the smartcore classifiers have no sample
+    /// weights and only learn the largest leg of a split.
+    pub weight: f64,
     pub target_account: Account,
 }
 
@@ -24,6 +35,10 @@ pub struct PredictionInput {
     pub source_account: Account,
     pub payee: Option<String>,
     pub narration: String,
+    /// Amount of the source posting
+    pub amount: Option<Decimal>,
+    pub currency: Option<String>,
+    pub date: Option<Date>,
 }
 
 impl From<&TrainingExample> for PredictionInput {
@@ -32,6 +47,9 @@ impl From<&TrainingExample> for PredictionInput {
             source_account: example.source_account.clone(),
             payee: example.payee.clone(),
             narration: example.narration.clone(),
+            amount: example.amount,
+            currency: example.currency.clone(),
+            date: example.date,
         }
     }
 }
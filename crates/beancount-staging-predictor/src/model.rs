@@ -0,0 +1,134 @@
+//! Trained predictors saved to disk, so they don't have to be retrained on every start.
+use crate::TrainingExample;
+use crate::features::FeatureExtractor;
+use crate::preprocessing::Preprocessor;
+use anyhow::{Context, Result};
+use beancount_staging::StableHasher;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Version of the saved model format, bump it when changing the predictors. Changed features
+/// are caught by the feature names in the key.
+const MODEL_VERSION: u32 = 1;
+
+/// A predictor together with the key of the examples it was trained on.
+#[derive(Serialize, Deserialize)]
+struct SavedModel<T> {
+    model_key: u64,
+    predictor: T,
+}
+
+/// Key identifying the training data and the features extracted from it, changes whenever
+/// an example is added, removed or edited, or the features change.
+pub fn model_key<P: Preprocessor + Default>(examples: &[TrainingExample]) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write_u64(MODEL_VERSION.into());
+    let feature_names = FeatureExtractor::<P>::fit(examples).feature_names();
+    hasher.write_u64(feature_names.len() as u64);
+    for name in feature_names {
+        hasher.write_str(name);
+    }
+    hasher.write_u64(examples.len() as u64);
+    for example in examples {
+        hasher.write_str(example.source_account.as_str());
+        hasher.write_option_str(example.payee.as_deref());
+        hasher.write_str(&example.narration);
+        hasher.write_option_str(example.amount.map(|amount| amount.to_string()).as_deref());
+        hasher.write_option_str(example.currency.as_deref());
+        hasher.write_option_str(example.date.map(|date| date.to_string()).as_deref());
+        hasher.write_u64(example.weight.to_bits());
+        hasher.write_str(example.target_account.as_str());
+    }
+    hasher.finish()
+}
+
+/// The predictor saved at `path`, if it was trained on examples with `model_key`.
+pub fn load_model<T: DeserializeOwned>(path: &Path, model_key: u64) -> Result<Option<T>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read file: {}", path.display()));
+        }
+    };
+    let saved: SavedModel<T> = bincode::deserialize(&bytes)
+        .with_context(|| format!("Failed to read model: {}", path.display()))?;
+
+    Ok((saved.model_key == model_key).then_some(saved.predictor))
+}
+
+/// Save `predictor`, trained on examples with `model_key`, replacing the model at `path`.
+pub fn save_model<T: Serialize>(path: &Path, model_key: u64, predictor: &T) -> Result<()> {
+    let bytes = bincode::serialize(&SavedModel {
+        model_key,
+        predictor,
+    })
+    .context("Failed to serialize model")?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    }
+    // A crash or a concurrent load never sees a truncated model
+    beancount_staging::write_atomically(path, &bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocessing::Alpha;
+    use crate::{PayeeFrequencyPredictor, PredictionInput, Predictor};
+
+    fn example(payee: &str, target: &str) -> TrainingExample {
+        TrainingExample {
+            source_account: "Assets:Checking".parse().unwrap(),
+            payee: Some(payee.into()),
+            narration: "Purchase".into(),
+            amount: None,
+            currency: None,
+            date: None,
+            weight: 1.0,
+            target_account: target.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_model_reused_until_examples_change() {
+        let dir = std::env::temp_dir().join(format!(
+            "beancount-staging-predictor-model-{}",
+            std::process::id()
+        ));
+        let path = dir.join("model.bin");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let examples = vec![example("REWE", "Expenses:Groceries")];
+        let key = model_key::<Alpha>(&examples);
+        assert!(
+            load_model::<PayeeFrequencyPredictor>(&path, key)
+                .unwrap()
+                .is_none(),
+            "Should have no model before saving one"
+        );
+
+        save_model(&path, key, &PayeeFrequencyPredictor::train(&examples)).unwrap();
+        let loaded: PayeeFrequencyPredictor = load_model(&path, key).unwrap().unwrap();
+        assert_eq!(
+            loaded
+                .predict(&PredictionInput::from(&examples[0]))
+                .map(|a| a.to_string()),
+            Some("Expenses:Groceries".into())
+        );
+
+        let changed = vec![example("REWE", "Expenses:Household")];
+        assert_ne!(model_key::<Alpha>(&changed), key);
+        assert!(
+            load_model::<PayeeFrequencyPredictor>(&path, model_key::<Alpha>(&changed))
+                .unwrap()
+                .is_none(),
+            "Should not reuse a model trained on other examples"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,73 +1,64 @@
-use super::Predictor;
+use super::{Predictor, RankedPrediction};
 use crate::{PredictionInput, TrainingExample};
 use beancount_parser::Account;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct PayeeFrequencyPredictor {
-    payee_accounts: HashMap<String, HashMap<String, usize>>,
-    source_fallback: HashMap<String, String>,
+    /// Summed weights of the target accounts by payee
+    payee_accounts: HashMap<String, HashMap<String, f64>>,
+    /// Summed weights of the target accounts by source account
+    source_accounts: HashMap<String, HashMap<String, f64>>,
 }
 
 impl Predictor for PayeeFrequencyPredictor {
     fn train(examples: &[TrainingExample]) -> Self {
-        let mut payee_accounts: HashMap<String, HashMap<String, usize>> = HashMap::new();
-        let mut source_accounts: HashMap<String, HashMap<String, usize>> = HashMap::new();
-
+        let mut predictor = Self::default();
         for example in examples {
-            let target = example.target_account.to_string();
-            let source = example.source_account.to_string();
-
-            if let Some(payee) = &example.payee {
-                payee_accounts
-                    .entry(payee.clone())
-                    .or_default()
-                    .entry(target.clone())
-                    .and_modify(|c| *c += 1)
-                    .or_insert(1);
-            }
-
-            source_accounts
-                .entry(source)
-                .or_default()
-                .entry(target)
-                .and_modify(|c| *c += 1)
-                .or_insert(1);
+            predictor.learn(example);
         }
+        predictor
+    }
 
-        let source_fallback = source_accounts
+    fn predict(&self, input: &PredictionInput) -> Option<Account> {
+        self.predict_ranked(input, 1)
             .into_iter()
-            .filter_map(|(source, targets)| {
-                let most_common = targets
-                    .into_iter()
-                    .max_by_key(|(_, count)| *count)
-                    .map(|(account, _)| account)?;
-                Some((source, most_common))
-            })
-            .collect();
-
-        Self {
-            payee_accounts,
-            source_fallback,
-        }
+            .next()
+            .map(|prediction| prediction.account)
     }
 
-    fn predict(&self, input: &PredictionInput) -> Option<Account> {
-        if let Some(payee) = &input.payee
-            && let Some(accounts) = self.payee_accounts.get(payee)
-        {
-            let most_common = accounts
-                .iter()
-                .max_by_key(|(_, count)| *count)
-                .map(|(account, _)| account)?;
-
-            return most_common.parse().ok();
+    fn predict_ranked(&self, input: &PredictionInput, limit: usize) -> Vec<RankedPrediction> {
+        // Fall back to the targets of the source account for unknown payees
+        let counts = input
+            .payee
+            .as_ref()
+            .and_then(|payee| self.payee_accounts.get(payee))
+            .or_else(|| self.source_accounts.get(&input.source_account.to_string()));
+        let Some(counts) = counts else {
+            return Vec::new();
+        };
+
+        let total: f64 = counts.values().sum();
+        if total <= 0.0 {
+            return Vec::new();
         }
+        let mut ranked: Vec<(&String, f64)> = counts
+            .iter()
+            .map(|(account, count)| (account, *count))
+            .collect();
+        ranked.sort_by(|(a, a_count), (b, b_count)| b_count.total_cmp(a_count).then(a.cmp(b)));
 
-        let source = input.source_account.to_string();
-        self.source_fallback
-            .get(&source)
-            .and_then(|acc| acc.parse().ok())
+        ranked
+            .into_iter()
+            .filter_map(|(account, count)| {
+                Some(RankedPrediction {
+                    account: account.parse().ok()?,
+                    probability: count / total,
+                })
+            })
+            .take(limit)
+            .collect()
     }
 
     fn name(&self) -> &'static str {
@@ -76,10 +67,38 @@ impl Predictor for PayeeFrequencyPredictor {
 }
 
 impl PayeeFrequencyPredictor {
+    /// Learn from one more example, same as training on all examples again.
+    pub fn learn(&mut self, example: &TrainingExample) {
+        let target = example.target_account.to_string();
+        let source = example.source_account.to_string();
+
+        if let Some(payee) = &example.payee {
+            *self
+                .payee_accounts
+                .entry(payee.clone())
+                .or_default()
+                .entry(target.clone())
+                .or_default() += example.weight;
+        }
+
+        *self
+            .source_accounts
+            .entry(source)
+            .or_default()
+            .entry(target)
+            .or_default() += example.weight;
+    }
+
+    /// Whether any example had `payee`, i.e. predictions for it don't fall back to the
+    /// source account.
+    pub fn knows_payee(&self, payee: &str) -> bool {
+        self.payee_accounts.contains_key(payee)
+    }
+
     pub fn stats(&self) -> PredictorStats {
         PredictorStats {
             unique_payees: self.payee_accounts.len(),
-            unique_sources: self.source_fallback.len(),
+            unique_sources: self.source_accounts.len(),
         }
     }
 }
@@ -101,18 +120,30 @@ mod tests {
                 source_account: "Assets:Checking".parse().unwrap(),
                 payee: Some("REWE".into()),
                 narration: "Groceries".into(),
+                amount: None,
+                currency: None,
+                date: None,
+                weight: 1.0,
                 target_account: "Expenses:Groceries".parse().unwrap(),
             },
             TrainingExample {
                 source_account: "Assets:Checking".parse().unwrap(),
                 payee: Some("REWE".into()),
                 narration: "More groceries".into(),
+                amount: None,
+                currency: None,
+                date: None,
+                weight: 1.0,
                 target_account: "Expenses:Groceries".parse().unwrap(),
             },
             TrainingExample {
                 source_account: "Assets:Checking".parse().unwrap(),
                 payee: Some("REWE".into()),
                 narration: "Household items".into(),
+                amount: None,
+                currency: None,
+                date: None,
+                weight: 1.0,
                 target_account: "Expenses:Household".parse().unwrap(),
             },
         ];
@@ -123,6 +154,9 @@ mod tests {
             source_account: "Assets:Checking".parse().unwrap(),
             payee: Some("REWE".into()),
             narration: "New purchase".into(),
+            amount: None,
+            currency: None,
+            date: None,
         };
 
         let prediction = predictor.predict(&input);
@@ -140,12 +174,20 @@ mod tests {
                 source_account: "Liabilities:CreditCard".parse().unwrap(),
                 payee: Some("Various1".into()),
                 narration: "Purchase".into(),
+                amount: None,
+                currency: None,
+                date: None,
+                weight: 1.0,
                 target_account: "Expenses:Shopping".parse().unwrap(),
             },
             TrainingExample {
                 source_account: "Liabilities:CreditCard".parse().unwrap(),
                 payee: Some("Various2".into()),
                 narration: "Purchase".into(),
+                amount: None,
+                currency: None,
+                date: None,
+                weight: 1.0,
                 target_account: "Expenses:Shopping".parse().unwrap(),
             },
         ];
@@ -156,6 +198,9 @@ mod tests {
             source_account: "Liabilities:CreditCard".parse().unwrap(),
             payee: Some("UnknownPayee".into()),
             narration: "New purchase".into(),
+            amount: None,
+            currency: None,
+            date: None,
         };
 
         let prediction = predictor.predict(&input);
@@ -172,6 +217,10 @@ mod tests {
             source_account: "Assets:Checking".parse().unwrap(),
             payee: Some("KnownPayee".into()),
             narration: "Purchase".into(),
+            amount: None,
+            currency: None,
+            date: None,
+            weight: 1.0,
             target_account: "Expenses:Test".parse().unwrap(),
         }];
 
@@ -181,6 +230,9 @@ mod tests {
             source_account: "Liabilities:Unknown".parse().unwrap(),
             payee: Some("UnknownPayee".into()),
             narration: "New purchase".into(),
+            amount: None,
+            currency: None,
+            date: None,
         };
 
         let prediction = predictor.predict(&input);
@@ -189,4 +241,79 @@ mod tests {
             "Should return None when both payee and source are unknown"
         );
     }
+
+    #[test]
+    fn test_ranked_payee_frequencies() {
+        let example = |payee: &str, target: &str| TrainingExample {
+            source_account: "Assets:Checking".parse().unwrap(),
+            payee: Some(payee.into()),
+            narration: "Purchase".into(),
+            amount: None,
+            currency: None,
+            date: None,
+            weight: 1.0,
+            target_account: target.parse().unwrap(),
+        };
+        let examples = vec![
+            example("REWE", "Expenses:Groceries"),
+            example("REWE", "Expenses:Groceries"),
+            example("REWE", "Expenses:Household"),
+            example("REWE", "Expenses:Drinks"),
+        ];
+
+        let predictor = PayeeFrequencyPredictor::train(&examples);
+
+        let input = PredictionInput {
+            source_account: "Assets:Checking".parse().unwrap(),
+            payee: Some("REWE".into()),
+            narration: "New purchase".into(),
+            amount: None,
+            currency: None,
+            date: None,
+        };
+
+        let ranked: Vec<_> = predictor
+            .predict_ranked(&input, 5)
+            .into_iter()
+            .map(|p| (p.account.to_string(), p.probability))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                ("Expenses:Groceries".to_string(), 0.5),
+                ("Expenses:Drinks".to_string(), 0.25),
+                ("Expenses:Household".to_string(), 0.25),
+            ],
+            "Should rank by share of the payee's transactions, ties by name"
+        );
+        assert_eq!(predictor.predict_ranked(&input, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_learn_updates_predictions() {
+        let example = |narration: &str, target: &str| TrainingExample {
+            source_account: "Assets:Checking".parse().unwrap(),
+            payee: Some("REWE".into()),
+            narration: narration.into(),
+            amount: None,
+            currency: None,
+            date: None,
+            weight: 1.0,
+            target_account: target.parse().unwrap(),
+        };
+        let mut predictor =
+            PayeeFrequencyPredictor::train(&[example("Groceries", "Expenses:Groceries")]);
+        assert!(predictor.knows_payee("REWE"));
+        assert!(!predictor.knows_payee("ALDI"));
+
+        predictor.learn(&example("Cleaning supplies", "Expenses:Household"));
+        predictor.learn(&example("Detergent", "Expenses:Household"));
+
+        let input = PredictionInput::from(&example("New purchase", "Expenses:Unknown"));
+        assert_eq!(
+            predictor.predict(&input).map(|a| a.to_string()),
+            Some("Expenses:Household".into()),
+            "Should predict from the learned examples without retraining"
+        );
+    }
 }
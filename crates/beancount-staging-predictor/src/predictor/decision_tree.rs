@@ -1,10 +1,12 @@
-use super::Predictor;
+use super::{Predictor, RankedPrediction, dominant_legs, rank_labels};
 use crate::{
     PredictionInput, TrainingExample,
     features::FeatureExtractor,
     preprocessing::{Alpha, Preprocessor},
 };
 use beancount_parser::Account;
+use serde::{Deserialize, Serialize};
+use smartcore::linalg::basic::arrays::Array;
 use smartcore::linalg::basic::matrix::DenseMatrix;
 use smartcore::tree::decision_tree_classifier::{
     DecisionTreeClassifier, DecisionTreeClassifierParameters,
@@ -14,6 +16,8 @@ use std::marker::PhantomData;
 
 /// N-gram ML predictor using decision tree classifier
 /// Generic over preprocessing strategy
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct DecisionTreePredictor<P: Preprocessor = Alpha> {
     classifier: DecisionTreeClassifier<f64, i32, DenseMatrix<f64>, Vec<i32>>,
     feature_extractor: FeatureExtractor<P>,
@@ -24,6 +28,28 @@ pub struct DecisionTreePredictor<P: Preprocessor = Alpha> {
 }
 
 impl<P: Preprocessor + Default> DecisionTreePredictor<P> {
+    /// Feature matrix with the single row for `input`, `None` if no feature is known.
+    fn features(&self, input: &PredictionInput) -> Option<DenseMatrix<f64>> {
+        let dummy_example = TrainingExample {
+            source_account: input.source_account.clone(),
+            payee: input.payee.clone(),
+            narration: input.narration.clone(),
+            amount: input.amount,
+            currency: input.currency.clone(),
+            date: input.date,
+            weight: 1.0,
+            target_account: "Expenses:Unknown".parse().unwrap(),
+        };
+
+        let features = self.feature_extractor.transform(&dummy_example);
+
+        if features.iter().all(|&f| f == 0.0) {
+            return None;
+        }
+
+        Some(DenseMatrix::from_2d_vec(&vec![features]).expect("Failed to create prediction matrix"))
+    }
+
     pub fn stats(&self) -> MLPredictorStats {
         MLPredictorStats {
             n_features: self.feature_extractor.feature_count(),
@@ -34,6 +60,8 @@ impl<P: Preprocessor + Default> DecisionTreePredictor<P> {
 
 impl<P: Preprocessor + Default> Predictor for DecisionTreePredictor<P> {
     fn train(examples: &[TrainingExample]) -> Self {
+        let examples = &dominant_legs(examples);
+
         // Build feature extractor
         let feature_extractor = FeatureExtractor::<P>::fit(examples);
 
@@ -88,21 +116,7 @@ impl<P: Preprocessor + Default> Predictor for DecisionTreePredictor<P> {
     }
 
     fn predict(&self, input: &PredictionInput) -> Option<Account> {
-        let dummy_example = TrainingExample {
-            source_account: input.source_account.clone(),
-            payee: input.payee.clone(),
-            narration: input.narration.clone(),
-            target_account: "Expenses:Unknown".parse().unwrap(),
-        };
-
-        let features = self.feature_extractor.transform(&dummy_example);
-
-        if features.iter().all(|&f| f == 0.0) {
-            return None;
-        }
-
-        let x =
-            DenseMatrix::from_2d_vec(&vec![features]).expect("Failed to create prediction matrix");
+        let x = self.features(input)?;
 
         let predictions = self.classifier.predict(&x).ok()?;
         let label = predictions[0];
@@ -111,6 +125,23 @@ impl<P: Preprocessor + Default> Predictor for DecisionTreePredictor<P> {
         account_str.parse().ok()
     }
 
+    fn predict_ranked(&self, input: &PredictionInput, limit: usize) -> Vec<RankedPrediction> {
+        let Some(x) = self.features(input) else {
+            return Vec::new();
+        };
+        let Ok(probabilities) = self.classifier.predict_proba(&x) else {
+            return Vec::new();
+        };
+
+        // One column per label, in label order
+        let (_, n_labels) = probabilities.shape();
+        rank_labels(
+            (0..n_labels).map(|label| *probabilities.get((0, label))),
+            &self.label_to_account,
+            limit,
+        )
+    }
+
     fn name(&self) -> &'static str {
         std::any::type_name::<P>()
             .split("::")
@@ -1,13 +1,18 @@
-use super::{Predictor, decision_tree::MLPredictorStats};
+use super::{
+    Predictor, RankedPrediction, decision_tree::MLPredictorStats, dominant_legs, rank_labels,
+};
 use crate::{PredictionInput, TrainingExample, features::FeatureExtractor};
 use beancount_parser::Account;
+use serde::{Deserialize, Serialize};
 use smartcore::ensemble::random_forest_classifier::{
     RandomForestClassifier, RandomForestClassifierParameters,
 };
+use smartcore::linalg::basic::arrays::Array;
 use smartcore::linalg::basic::matrix::DenseMatrix;
 use std::collections::HashMap;
 
 /// N-gram ML predictor using random forest classifier (smart preprocessing)
+#[derive(Serialize, Deserialize)]
 pub struct RandomForestPredictor {
     classifier: RandomForestClassifier<f64, i32, DenseMatrix<f64>, Vec<i32>>,
     feature_extractor: FeatureExtractor,
@@ -18,6 +23,8 @@ pub struct RandomForestPredictor {
 
 impl Predictor for RandomForestPredictor {
     fn train(examples: &[TrainingExample]) -> Self {
+        let examples = &dominant_legs(examples);
+
         // Build feature extractor with smart preprocessing
         let feature_extractor = FeatureExtractor::fit(examples);
 
@@ -71,10 +78,48 @@ impl Predictor for RandomForestPredictor {
     }
 
     fn predict(&self, input: &PredictionInput) -> Option<Account> {
+        let x = self.features(input)?;
+
+        let predictions = self.classifier.predict(&x).ok()?;
+        let label = predictions[0];
+
+        let account_str = self.label_to_account.get(label as usize)?;
+        account_str.parse().ok()
+    }
+
+    fn predict_ranked(&self, input: &PredictionInput, limit: usize) -> Vec<RankedPrediction> {
+        let Some(x) = self.features(input) else {
+            return Vec::new();
+        };
+        // Averaged over the trees of the forest
+        let Ok(probabilities) = self.classifier.predict_proba(&x) else {
+            return Vec::new();
+        };
+
+        let (_, n_labels) = probabilities.shape();
+        rank_labels(
+            (0..n_labels).map(|label| *probabilities.get((0, label))),
+            &self.label_to_account,
+            limit,
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "RandomForest"
+    }
+}
+
+impl RandomForestPredictor {
+    /// Feature matrix with the single row for `input`, `None` if no feature is known.
+    fn features(&self, input: &PredictionInput) -> Option<DenseMatrix<f64>> {
         let dummy_example = TrainingExample {
             source_account: input.source_account.clone(),
             payee: input.payee.clone(),
             narration: input.narration.clone(),
+            amount: input.amount,
+            currency: input.currency.clone(),
+            date: input.date,
+            weight: 1.0,
             target_account: "Expenses:Unknown".parse().unwrap(),
         };
 
@@ -84,22 +129,9 @@ impl Predictor for RandomForestPredictor {
             return None;
         }
 
-        let x =
-            DenseMatrix::from_2d_vec(&vec![features]).expect("Failed to create prediction matrix");
-
-        let predictions = self.classifier.predict(&x).ok()?;
-        let label = predictions[0];
-
-        let account_str = self.label_to_account.get(label as usize)?;
-        account_str.parse().ok()
-    }
-
-    fn name(&self) -> &'static str {
-        "RandomForest"
+        Some(DenseMatrix::from_2d_vec(&vec![features]).expect("Failed to create prediction matrix"))
     }
-}
 
-impl RandomForestPredictor {
     pub fn stats(&self) -> MLPredictorStats {
         MLPredictorStats {
             n_features: self.feature_extractor.feature_count(),
@@ -1,5 +1,6 @@
 use crate::{PredictionInput, TrainingExample};
 use beancount_parser::Account;
+use std::collections::HashMap;
 
 mod baseline;
 mod decision_tree;
@@ -11,6 +12,14 @@ pub use decision_tree::{DecisionTreePredictor, MLPredictorStats};
 pub use ensemble::RandomForestPredictor;
 pub use naive_bayes::MultinomialNBPredictor;
 
+/// A candidate target account with the predictor's confidence in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedPrediction {
+    pub account: Account,
+    /// Between 0 and 1, summing to at most 1 over all candidates
+    pub probability: f64,
+}
+
 pub trait Predictor {
     fn train(examples: &[TrainingExample]) -> Self
     where
@@ -18,5 +27,160 @@ pub trait Predictor {
 
     fn predict(&self, input: &PredictionInput) -> Option<Account>;
 
+    /// Up to `limit` candidate accounts, most likely first.
+    ///
+    /// Empty when the predictor has nothing to go on, i.e. when `predict` returns `None`.
+    fn predict_ranked(&self, input: &PredictionInput, limit: usize) -> Vec<RankedPrediction>;
+
     fn name(&self) -> &'static str;
 }
+
+/// Rank the accounts of a classifier by the probability of their label.
+///
+/// Labels with zero probability are dropped; ties keep the label order.
+fn rank_labels(
+    probabilities: impl IntoIterator<Item = f64>,
+    label_to_account: &[String],
+    limit: usize,
+) -> Vec<RankedPrediction> {
+    let mut ranked: Vec<(usize, f64)> = probabilities
+        .into_iter()
+        .enumerate()
+        .filter(|(_, probability)| *probability > 0.0)
+        .collect();
+    ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    ranked
+        .into_iter()
+        .filter_map(|(label, probability)| {
+            let account = label_to_account.get(label)?.parse().ok()?;
+            Some(RankedPrediction {
+                account,
+                probability,
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+/// Source posting and description of the transaction an example was taken from.
+type TransactionKey = (
+    String,
+    Option<String>,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+fn transaction_key(example: &TrainingExample) -> TransactionKey {
+    (
+        example.source_account.to_string(),
+        example.payee.clone(),
+        example.narration.clone(),
+        example.amount.map(|amount| amount.to_string()),
+        example.currency.clone(),
+        example.date.map(|date| date.to_string()),
+    )
+}
+
+/// The examples to train a classifier on: every unsplit example, but only the largest leg
+/// of a split transaction, the first by account name among equally large ones.
+///
+/// The smartcore classifiers have no sample weights, so each leg would count as a full
+/// example of the transaction and contradict the others. Splits are suggested by
+/// `SplitTemplates` instead.
+fn dominant_legs(examples: &[TrainingExample]) -> Vec<TrainingExample> {
+    let mut largest: HashMap<TransactionKey, &TrainingExample> = HashMap::new();
+    for example in examples.iter().filter(|example| example.weight < 1.0) {
+        largest
+            .entry(transaction_key(example))
+            .and_modify(|largest| {
+                if example.weight > largest.weight
+                    || (example.weight >= largest.weight
+                        && example.target_account.as_str() < largest.target_account.as_str())
+                {
+                    *largest = example;
+                }
+            })
+            .or_insert(example);
+    }
+
+    examples
+        .iter()
+        .filter(|example| {
+            example.weight >= 1.0
+                || largest
+                    .get(&transaction_key(example))
+                    .is_some_and(|largest| largest.target_account == example.target_account)
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dominant_legs() {
+        let example = |narration: &str, weight: f64, target: &str| TrainingExample {
+            source_account: "Assets:Checking".parse().unwrap(),
+            payee: Some("Store".to_string()),
+            narration: narration.to_string(),
+            amount: None,
+            currency: None,
+            date: None,
+            weight,
+            target_account: target.parse().unwrap(),
+        };
+        let examples = [
+            example("Groceries", 1.0, "Expenses:Groceries"),
+            example("Groceries", 1.0, "Expenses:Household"),
+            example("Weekly", 0.75, "Expenses:Groceries"),
+            example("Weekly", 0.25, "Expenses:Household"),
+            example("Shared", 0.5, "Expenses:Household"),
+            example("Shared", 0.5, "Expenses:Groceries"),
+        ];
+
+        let kept: Vec<_> = dominant_legs(&examples)
+            .iter()
+            .map(|ex| (ex.narration.clone(), ex.target_account.to_string()))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("Groceries", "Expenses:Groceries"),
+                ("Groceries", "Expenses:Household"),
+                ("Weekly", "Expenses:Groceries"),
+                ("Shared", "Expenses:Groceries"),
+            ]
+            .map(|(narration, account)| (narration.to_string(), account.to_string())),
+            "Should keep unsplit examples and one largest leg of each split"
+        );
+    }
+
+    #[test]
+    fn test_rank_labels() {
+        let accounts = vec![
+            "Expenses:Groceries".to_string(),
+            "Expenses:Household".to_string(),
+            "Expenses:Rent".to_string(),
+            "Expenses:Travel".to_string(),
+        ];
+
+        let ranked = rank_labels([0.2, 0.0, 0.6, 0.2], &accounts, 2);
+        let ranked: Vec<_> = ranked
+            .iter()
+            .map(|p| (p.account.to_string(), p.probability))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                ("Expenses:Rent".to_string(), 0.6),
+                ("Expenses:Groceries".to_string(), 0.2),
+            ],
+            "Should sort by probability, keep label order on ties and stop at the limit"
+        );
+    }
+}
@@ -1,11 +1,13 @@
-use super::Predictor;
+use super::{Predictor, RankedPrediction, dominant_legs, rank_labels};
 use crate::{PredictionInput, TrainingExample, features::FeatureExtractor};
 use beancount_parser::Account;
+use serde::{Deserialize, Serialize};
 use smartcore::linalg::basic::matrix::DenseMatrix;
 use smartcore::naive_bayes::multinomial::MultinomialNB;
 use std::collections::HashMap;
 
 /// N-gram ML predictor using Multinomial Naive Bayes (smart preprocessing)
+#[derive(Serialize, Deserialize)]
 pub struct MultinomialNBPredictor {
     classifier: MultinomialNB<u32, u32, DenseMatrix<u32>, Vec<u32>>,
     feature_extractor: FeatureExtractor,
@@ -16,6 +18,8 @@ pub struct MultinomialNBPredictor {
 
 impl Predictor for MultinomialNBPredictor {
     fn train(examples: &[TrainingExample]) -> Self {
+        let examples = &dominant_legs(examples);
+
         // Build feature extractor from training data (smart preprocessing)
         let feature_extractor = FeatureExtractor::fit(examples);
 
@@ -69,16 +73,7 @@ impl Predictor for MultinomialNBPredictor {
     }
 
     fn predict(&self, input: &PredictionInput) -> Option<Account> {
-        let dummy_example = TrainingExample {
-            source_account: input.source_account.clone(),
-            payee: input.payee.clone(),
-            narration: input.narration.clone(),
-            target_account: "Expenses:Unknown".parse().unwrap(),
-        };
-
-        let features = self.feature_extractor.transform(&dummy_example);
-        let features_u32: Vec<u32> = features.into_iter().map(|v| v as u32).collect();
-        let x = DenseMatrix::from_2d_vec(&vec![features_u32]).ok()?;
+        let x = DenseMatrix::from_2d_vec(&vec![self.features(input)]).ok()?;
 
         let predictions = self.classifier.predict(&x).ok()?;
         let label = predictions[0];
@@ -86,7 +81,63 @@ impl Predictor for MultinomialNBPredictor {
         account_str.parse().ok()
     }
 
+    fn predict_ranked(&self, input: &PredictionInput, limit: usize) -> Vec<RankedPrediction> {
+        let features = self.features(input);
+
+        // Joint log-likelihood of the features and each class, as in `predict`
+        let class_count = self.classifier.class_count();
+        let n_samples: usize = class_count.iter().sum();
+        let log_likelihoods: Vec<f64> = class_count
+            .iter()
+            .zip(self.classifier.feature_log_prob())
+            .map(|(&count, feature_log_prob)| {
+                let prior = (count as f64 / n_samples as f64).ln();
+                features
+                    .iter()
+                    .zip(feature_log_prob)
+                    .map(|(&x, log_prob)| x as f64 * log_prob)
+                    .sum::<f64>()
+                    + prior
+            })
+            .collect();
+
+        // Normalize with softmax, shifted by the maximum to avoid underflow
+        let max = log_likelihoods
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let exp: Vec<f64> = log_likelihoods.iter().map(|l| (l - max).exp()).collect();
+        let total: f64 = exp.iter().sum();
+
+        let mut probabilities = vec![0.0; self.label_to_account.len()];
+        for (&class, p) in self.classifier.classes().iter().zip(exp) {
+            if let Some(probability) = probabilities.get_mut(class as usize) {
+                *probability = p / total;
+            }
+        }
+        rank_labels(probabilities, &self.label_to_account, limit)
+    }
+
     fn name(&self) -> &'static str {
         "MultinomialNB"
     }
 }
+
+impl MultinomialNBPredictor {
+    /// Feature counts for `input`.
+    fn features(&self, input: &PredictionInput) -> Vec<u32> {
+        let dummy_example = TrainingExample {
+            source_account: input.source_account.clone(),
+            payee: input.payee.clone(),
+            narration: input.narration.clone(),
+            amount: input.amount,
+            currency: input.currency.clone(),
+            date: input.date,
+            weight: 1.0,
+            target_account: "Expenses:Unknown".parse().unwrap(),
+        };
+
+        let features = self.feature_extractor.transform(&dummy_example);
+        features.into_iter().map(|v| v as u32).collect()
+    }
+}
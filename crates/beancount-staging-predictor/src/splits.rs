@@ -0,0 +1,180 @@
+//! Split suggestions for payees whose transactions are always split the same way, e.g. a
+//! phone bill split between the contract and the device installment.
+use crate::training::posting_amounts;
+use crate::{Decimal, Directive, Posting};
+use beancount_parser::{Account, DirectiveContent};
+use std::collections::HashMap;
+
+/// Transactions of a payee needed before its split is suggested.
+const MIN_SPLIT_TRANSACTIONS: usize = 2;
+
+/// A leg of a suggested split.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitLeg {
+    pub account: Account,
+    pub amount: Decimal,
+}
+
+/// How the transactions of a payee were split so far.
+#[derive(Debug, Clone)]
+struct PayeeSplit {
+    /// Accounts of the legs, sorted
+    accounts: Vec<String>,
+    /// Summed share of each leg in the amount of the legs
+    shares: Vec<Decimal>,
+    transactions: usize,
+}
+
+/// Split templates by payee, learned from the journal.
+#[derive(Debug, Default)]
+pub struct SplitTemplates {
+    /// `None` for payees whose transactions were split in different ways
+    payees: HashMap<String, Option<PayeeSplit>>,
+}
+
+impl SplitTemplates {
+    pub fn learn(directives: &[Directive]) -> Self {
+        let mut templates = Self::default();
+
+        for directive in directives {
+            let DirectiveContent::Transaction(txn) = &directive.content else {
+                continue;
+            };
+            let Some(payee) = &txn.payee else {
+                continue;
+            };
+
+            let split = transaction_split(&txn.postings);
+            let Some(known) = templates.payees.get_mut(payee) else {
+                templates.payees.insert(payee.clone(), split);
+                continue;
+            };
+            match (known.as_mut(), split) {
+                (Some(known), Some(split)) if known.accounts == split.accounts => {
+                    for (sum, share) in known.shares.iter_mut().zip(split.shares) {
+                        *sum += share;
+                    }
+                    known.transactions += 1;
+                }
+                _ => *known = None,
+            }
+        }
+
+        templates
+    }
+
+    /// The legs for a transaction of `payee` adding up to `amount`, if all transactions of
+    /// the payee were split across the same accounts.
+    ///
+    /// Each leg gets its average share, rounded like `amount`. The last leg takes the
+    /// rounding difference, so the legs always add up.
+    pub fn suggest(&self, payee: &str, amount: Decimal) -> Option<Vec<SplitLeg>> {
+        let split = self.payees.get(payee)?.as_ref()?;
+        if split.accounts.len() < 2 || split.transactions < MIN_SPLIT_TRANSACTIONS {
+            return None;
+        }
+
+        let transactions = Decimal::from(split.transactions);
+        let mut remaining = amount;
+        let mut legs = Vec::new();
+        for (i, (account, share)) in split.accounts.iter().zip(&split.shares).enumerate() {
+            let leg_amount = if i + 1 == split.accounts.len() {
+                remaining
+            } else {
+                (amount * share / transactions).round_dp(amount.scale())
+            };
+            remaining -= leg_amount;
+            legs.push(SplitLeg {
+                account: account.parse().ok()?,
+                amount: leg_amount,
+            });
+        }
+        Some(legs)
+    }
+}
+
+/// The legs of a transaction with their share, `None` if the amounts aren't known.
+fn transaction_split(postings: &[Posting]) -> Option<PayeeSplit> {
+    let amounts = posting_amounts(postings)?;
+    let total: Decimal = amounts[1..].iter().sum();
+    if total.is_zero() {
+        return None;
+    }
+
+    let mut legs: Vec<(String, Decimal)> = postings[1..]
+        .iter()
+        .zip(&amounts[1..])
+        .map(|(leg, amount)| (leg.account.to_string(), amount / total))
+        .collect();
+    legs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Some(PayeeSplit {
+        accounts: legs.iter().map(|(account, _)| account.clone()).collect(),
+        shares: legs.into_iter().map(|(_, share)| share).collect(),
+        transactions: 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn learn(source: &str) -> SplitTemplates {
+        let directives = beancount_parser::parse::<Decimal>(source)
+            .unwrap()
+            .directives;
+        SplitTemplates::learn(&directives)
+    }
+
+    fn render(legs: Option<Vec<SplitLeg>>) -> Option<Vec<String>> {
+        legs.map(|legs| {
+            legs.iter()
+                .map(|leg| format!("{} {}", leg.account, leg.amount))
+                .collect()
+        })
+    }
+
+    const PHONE_BILLS: &str = r#"
+2024-01-05 * "Telco" "Phone bill"
+    Assets:Checking  -40.00 EUR
+    Expenses:Phone:Device  10.00 EUR
+    Expenses:Phone:Contract
+
+2024-02-05 * "Telco" "Phone bill"
+    Assets:Checking  -42.00 EUR
+    Expenses:Phone:Contract  31.50 EUR
+    Expenses:Phone:Device  10.50 EUR
+"#;
+
+    #[test]
+    fn test_suggests_split_by_average_share() {
+        let templates = learn(PHONE_BILLS);
+
+        assert_eq!(
+            render(templates.suggest("Telco", Decimal::new(1001, 2))),
+            Some(vec![
+                "Expenses:Phone:Contract 7.51".to_string(),
+                "Expenses:Phone:Device 2.50".to_string(),
+            ]),
+            "Should split by share, the last leg taking the rounding difference"
+        );
+        assert_eq!(render(templates.suggest("Other", Decimal::from(10))), None);
+    }
+
+    #[test]
+    fn test_no_split_when_split_differently() {
+        let templates = learn(&format!(
+            "{PHONE_BILLS}
+2024-03-05 * \"Telco\" \"Phone bill\"
+    Assets:Checking  -30.00 EUR
+    Expenses:Phone:Contract
+"
+        ));
+
+        assert_eq!(
+            render(templates.suggest("Telco", Decimal::from(40))),
+            None,
+            "Should only suggest splits for payees which are always split the same way"
+        );
+    }
+}
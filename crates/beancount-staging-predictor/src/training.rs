@@ -1,6 +1,10 @@
-use crate::{Directive, TrainingExample};
+use crate::{Decimal, Directive, Posting, TrainingExample};
 use beancount_parser::DirectiveContent;
 
+/// One example per leg of each journal transaction, i.e. per posting after the first one,
+/// which is the source.
+///
+/// Legs of split transactions are weighted by their share of the amount.
 pub fn extract_training_examples(directives: &[Directive]) -> Vec<TrainingExample> {
     let mut examples = Vec::new();
 
@@ -9,28 +13,93 @@ pub fn extract_training_examples(directives: &[Directive]) -> Vec<TrainingExampl
             continue;
         };
 
-        if txn.postings.len() != 2 {
+        let Some((source, legs)) = txn.postings.split_first() else {
             continue;
-        }
-
-        let source = &txn.postings[0];
-        let target = &txn.postings[1];
-
-        if is_placeholder_account(&source.account) || is_placeholder_account(&target.account) {
+        };
+        if legs.is_empty()
+            || txn
+                .postings
+                .iter()
+                .any(|posting| is_placeholder_account(&posting.account))
+        {
             continue;
         }
 
-        examples.push(TrainingExample {
-            source_account: source.account.clone(),
-            payee: txn.payee.clone(),
-            narration: txn.narration.clone().unwrap_or_default(),
-            target_account: target.account.clone(),
-        });
+        let weights = leg_weights(&txn.postings);
+        for (leg, weight) in legs.iter().zip(weights) {
+            examples.push(TrainingExample {
+                source_account: source.account.clone(),
+                payee: txn.payee.clone(),
+                narration: txn.narration.clone().unwrap_or_default(),
+                amount: source.amount.as_ref().map(|amount| amount.value),
+                currency: source
+                    .amount
+                    .as_ref()
+                    .map(|amount| amount.currency.to_string()),
+                date: Some(directive.date),
+                weight,
+                target_account: leg.account.clone(),
+            });
+        }
     }
 
     examples
 }
 
+/// Amount of every posting, with the one posting left out inferred so that they balance.
+///
+/// `None` for transactions in several currencies or with costs or prices, whose postings
+/// can't be compared by amount.
+pub(crate) fn posting_amounts(postings: &[Posting]) -> Option<Vec<Decimal>> {
+    let mut currencies = postings
+        .iter()
+        .filter_map(|posting| posting.amount.as_ref())
+        .map(|amount| amount.currency.as_str());
+    let currency = currencies.next()?;
+    if currencies.any(|other| other != currency)
+        || postings
+            .iter()
+            .any(|posting| posting.cost.is_some() || posting.price.is_some())
+        || postings
+            .iter()
+            .filter(|posting| posting.amount.is_none())
+            .count()
+            > 1
+    {
+        return None;
+    }
+
+    let sum: Decimal = postings
+        .iter()
+        .filter_map(|posting| posting.amount.as_ref())
+        .map(|amount| amount.value)
+        .sum();
+    Some(
+        postings
+            .iter()
+            .map(|posting| posting.amount.as_ref().map_or(-sum, |amount| amount.value))
+            .collect(),
+    )
+}
+
+/// Share of each leg (all postings but the first) in the amount of the legs, equal shares
+/// if the amounts aren't known.
+fn leg_weights(postings: &[Posting]) -> Vec<f64> {
+    let legs = postings.len() - 1;
+    let amounts: Vec<Decimal> = posting_amounts(postings)
+        .map(|amounts| amounts[1..].iter().map(|amount| amount.abs()).collect())
+        .unwrap_or_default();
+    let total: Decimal = amounts.iter().sum();
+    if amounts.is_empty() || total.is_zero() {
+        return vec![1.0 / legs as f64; legs];
+    }
+
+    amounts
+        .iter()
+        .map(|amount| f64::try_from(*amount / total).unwrap_or(0.0))
+        .collect()
+}
+
 fn is_placeholder_account(account: &beancount_parser::Account) -> bool {
     let account_str = account.to_string().to_lowercase();
     account_str.contains("fixme") || account_str.contains("todo") || account_str.is_empty()
@@ -55,6 +124,16 @@ mod tests {
         assert_eq!(examples[0].target_account.to_string(), "Expenses:Groceries");
         assert_eq!(examples[0].payee.as_deref(), Some("SuperMart"));
         assert_eq!(examples[0].narration, "Weekly groceries");
+        assert_eq!(examples[0].weight, 1.0);
+        assert_eq!(
+            examples[0].amount.map(|a| a.to_string()).as_deref(),
+            Some("-156.78")
+        );
+        assert_eq!(examples[0].currency.as_deref(), Some("EUR"));
+        assert_eq!(
+            examples[0].date.map(|d| d.to_string()).as_deref(),
+            Some("2024-01-05")
+        );
     }
 
     #[test]
@@ -75,20 +154,34 @@ mod tests {
     }
 
     #[test]
-    fn test_skip_multi_posting_transactions() {
+    fn test_multi_posting_transactions_weighted_by_amount() {
         let source = r#"
 2024-01-05 * "Split transaction" "Multiple postings"
     Assets:Checking  -100.00 EUR
     Expenses:Food  60.00 EUR
-    Expenses:Transport  40.00 EUR
+    Expenses:Transport  35.00 EUR
+    Expenses:Fees
 "#;
         let directives = parse_test_source(source);
         let examples = extract_training_examples(&directives);
 
+        let legs: Vec<_> = examples
+            .iter()
+            .map(|ex| (ex.target_account.to_string(), ex.weight))
+            .collect();
         assert_eq!(
-            examples.len(),
-            0,
-            "Should skip transactions with more than 2 postings"
+            legs,
+            vec![
+                ("Expenses:Food".to_string(), 0.6),
+                ("Expenses:Transport".to_string(), 0.35),
+                ("Expenses:Fees".to_string(), 0.05),
+            ],
+            "Should create one example per leg, the fees inferred from the balance"
+        );
+        assert!(
+            examples
+                .iter()
+                .all(|ex| ex.source_account.to_string() == "Assets:Checking")
         );
     }
 
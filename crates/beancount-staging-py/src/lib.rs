@@ -6,7 +6,7 @@ use pyo3::{exceptions::PyValueError, prelude::*};
 #[pymodule]
 mod beancount_staging {
     use crate::run_async;
-    use beancount_staging_cli::beancount_staging::reconcile::StagingSource;
+    use beancount_staging_cli::beancount_staging::reconcile::{ReconcileConfig, StagingSource};
     use beancount_staging_web::ListenerType;
     use pyo3::prelude::*;
     use std::path::PathBuf;
@@ -33,8 +33,7 @@ mod beancount_staging {
         run_async(
             py,
             beancount_staging_web::run(
-                journal_files,
-                StagingSource::Files(staging_files),
+                ReconcileConfig::new(journal_files, StagingSource::Files(staging_files)),
                 Vec::new(),
                 ListenerType::Tcp(port),
             ),
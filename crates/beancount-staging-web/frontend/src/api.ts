@@ -4,22 +4,48 @@ export interface InitResponse {
   items: Directive[];
   current_index: number;
   available_accounts: string[];
+  /** Names of the staging sources, empty unless there are named sources */
+  sources: string[];
+}
+
+export interface Prediction {
+  account: string;
+  /** Between 0 and 1 */
+  probability: number;
 }
 
 export interface TransactionResponse {
   transaction: Directive;
   predicted_account?: string;
+  /** Up to 5 candidate accounts, most likely first */
+  predictions: Prediction[];
+  /** Suggested split if the payee is always split the same way, empty otherwise */
+  split_template: CommitSplit[];
+}
+
+export interface CommitSplit {
+  account: string;
+  amount: string;
 }
 
 export interface TransactionPatch {
   account?: string;
   payee?: string;
   narration?: string;
+  date?: string;
+  splits?: CommitSplit[];
+  add_tags?: string[];
+  remove_tags?: string[];
+  add_links?: string[];
+  remove_links?: string[];
+  metadata?: Record<string, string>;
 }
 
 export interface CommitResponse {
   ok: boolean;
   remaining_count: number;
+  /** Key to undo a commit with; it stays valid when staging is reloaded */
+  undo_id?: string;
 }
 
 export class ApiClient {
@@ -54,4 +80,41 @@ export class ApiClient {
 
     return await resp.json();
   }
+
+  async ignoreTransaction(id: string): Promise<CommitResponse> {
+    const resp = await fetch(`/api/transaction/${id}/ignore`, { method: "POST" });
+
+    if (!resp.ok) {
+      const errorData = await resp.json().catch(() => null);
+      const errorMsg = errorData?.error ?? resp.statusText;
+      throw new Error(errorMsg);
+    }
+
+    return await resp.json();
+  }
+
+  /** Run the staging command again, even if its inputs didn't change. */
+  async refreshStaging(): Promise<CommitResponse> {
+    const resp = await fetch("/api/staging/refresh", { method: "POST" });
+
+    if (!resp.ok) {
+      const errorData = await resp.json().catch(() => null);
+      const errorMsg = errorData?.error ?? resp.statusText;
+      throw new Error(errorMsg);
+    }
+
+    return await resp.json();
+  }
+
+  async uncommitTransaction(id: string): Promise<CommitResponse> {
+    const resp = await fetch(`/api/transaction/${id}/uncommit`, { method: "POST" });
+
+    if (!resp.ok) {
+      const errorData = await resp.json().catch(() => null);
+      const errorMsg = errorData?.error ?? resp.statusText;
+      throw new Error(errorMsg);
+    }
+
+    return await resp.json();
+  }
 }
@@ -1,17 +1,25 @@
 import { ApiClient, type TransactionPatch } from "./api";
 import { DirectiveRenderer, type EditState } from "./directive-renderer";
 import { filterAccounts } from "./account-filter";
+import { filterBySource } from "./source-filter";
 import type { Directive } from "./model/beancount";
 
 class StagingApp {
   private api = new ApiClient();
+  /** All staging items, `directives` are those of the selected source */
+  private allDirectives: Directive[] = [];
   private directives: Directive[] = [];
   private currentIndex = 0;
   private editStates: Map<string, EditState> = new Map();
+  private committedIds: string[] = [];
 
   private transactionEl: HTMLElement;
   private counterEl: HTMLElement;
   private commitBtn: HTMLButtonElement;
+  private undoBtn: HTMLButtonElement;
+  private ignoreBtn: HTMLButtonElement;
+  private refreshBtn: HTMLButtonElement;
+  private sourceFilterEl: HTMLSelectElement;
   private messageEl: HTMLElement;
   private prevBtn: HTMLButtonElement;
   private nextBtn: HTMLButtonElement;
@@ -22,6 +30,10 @@ class StagingApp {
     this.transactionEl = document.getElementById("transaction")!;
     this.counterEl = document.getElementById("counter")!;
     this.commitBtn = document.getElementById("commit") as HTMLButtonElement;
+    this.undoBtn = document.getElementById("undo") as HTMLButtonElement;
+    this.ignoreBtn = document.getElementById("ignore") as HTMLButtonElement;
+    this.refreshBtn = document.getElementById("refresh") as HTMLButtonElement;
+    this.sourceFilterEl = document.getElementById("source-filter") as HTMLSelectElement;
     this.messageEl = document.getElementById("message")!;
     this.prevBtn = document.getElementById("prev") as HTMLButtonElement;
     this.nextBtn = document.getElementById("next") as HTMLButtonElement;
@@ -45,6 +57,10 @@ class StagingApp {
     this.prevBtn.onclick = () => this.prev();
     this.nextBtn.onclick = () => this.next();
     this.commitBtn.onclick = () => this.commit();
+    this.undoBtn.onclick = () => this.undo();
+    this.ignoreBtn.onclick = () => this.ignore();
+    this.refreshBtn.onclick = () => this.refresh();
+    this.sourceFilterEl.onchange = () => this.filterSource();
 
     // Set up keyboard shortcuts
     document.addEventListener("keydown", (e) => this.handleKeyboardShortcuts(e));
@@ -58,6 +74,8 @@ class StagingApp {
       prev: ["ArrowLeft", "h"],
       next: ["ArrowRight", "l"],
       commit: "Enter",
+      undo: "u",
+      ignore: "x",
     };
 
     if (KEYBINDS.prev.includes(e.key)) {
@@ -68,6 +86,14 @@ class StagingApp {
       if (!this.commitBtn.disabled) {
         void this.commit();
       }
+    } else if (e.key === KEYBINDS.undo) {
+      if (!this.undoBtn.disabled) {
+        void this.undo();
+      }
+    } else if (e.key === KEYBINDS.ignore) {
+      if (!this.ignoreBtn.disabled) {
+        void this.ignore();
+      }
     }
   }
 
@@ -89,30 +115,57 @@ class StagingApp {
     try {
       const data = await this.api.init();
 
-      this.directives = data.items;
+      this.allDirectives = data.items;
+      this.directives = filterBySource(data.items, this.sourceFilterEl.value);
       this.renderer.setAvailableAccounts(data.available_accounts);
+      this.setSources(data.sources);
 
-      if (this.directives.length === 0) {
-        this.showSuccess("No transactions to review!");
-        this.transactionEl.textContent = "All done!";
-        this.counterEl.textContent = "0/0";
-        this.commitBtn.disabled = true;
-        this.prevBtn.disabled = true;
-        this.nextBtn.disabled = true;
-        return;
-      }
-
-      // Adjust current index if it's now out of bounds
-      if (this.currentIndex >= this.directives.length) {
-        this.currentIndex = this.directives.length - 1;
-      }
-
-      await this.loadTransaction();
+      await this.showDirectives();
     } catch (err) {
       this.showError(`Failed to reload data: ${String(err)}`);
     }
   }
 
+  /** Fill the source selection, which is only shown for several named sources. */
+  private setSources(sources: string[]) {
+    const selected = this.sourceFilterEl.value;
+    this.sourceFilterEl.replaceChildren(new Option("All sources", ""));
+    for (const source of sources) {
+      this.sourceFilterEl.add(new Option(source, source));
+    }
+    this.sourceFilterEl.value = sources.includes(selected) ? selected : "";
+    this.sourceFilterEl.hidden = sources.length === 0;
+  }
+
+  /** Only review the directives of the selected source. */
+  async filterSource() {
+    this.directives = filterBySource(this.allDirectives, this.sourceFilterEl.value);
+    this.currentIndex = 0;
+    await this.showDirectives();
+  }
+
+  private async showDirectives() {
+    if (this.directives.length === 0) {
+      this.showSuccess("No transactions to review!");
+      this.transactionEl.textContent = "All done!";
+      this.counterEl.textContent = "0/0";
+      this.commitBtn.disabled = true;
+      this.ignoreBtn.disabled = true;
+      this.prevBtn.disabled = true;
+      this.nextBtn.disabled = true;
+      return;
+    }
+    this.prevBtn.disabled = false;
+    this.nextBtn.disabled = false;
+
+    // Adjust current index if it's now out of bounds
+    if (this.currentIndex >= this.directives.length) {
+      this.currentIndex = this.directives.length - 1;
+    }
+
+    await this.loadTransaction();
+  }
+
   async loadTransaction() {
     try {
       const currentDirective = this.directives[this.currentIndex];
@@ -132,29 +185,39 @@ class StagingApp {
           this.editStates.set(currentDirective.id, {});
         } else {
           // Check if transaction already has an expense posting (posting without amount)
-          let defaultAccount = data.predicted_account ?? "Expenses:";
-          if (data.transaction.type === "transaction") {
-            const expensePosting = data.transaction.postings.find((p) => !p.amount);
-            if (expensePosting) {
-              defaultAccount = expensePosting.account;
-            }
+          const expensePosting =
+            data.transaction.type === "transaction"
+              ? data.transaction.postings.find((p) => !p.amount)
+              : undefined;
+          const defaultAccount = expensePosting?.account ?? data.predicted_account ?? "Expenses:";
+
+          if (!expensePosting && data.split_template.length > 0) {
+            // The payee is always split the same way, start from that split
+            this.editStates.set(currentDirective.id, {
+              splits: data.split_template.map((split) => ({ ...split })),
+            });
+          } else {
+            this.editStates.set(currentDirective.id, {
+              account: defaultAccount,
+            });
           }
-
-          this.editStates.set(currentDirective.id, {
-            account: defaultAccount,
-          });
         }
       }
 
       const editState = this.editStates.get(currentDirective.id);
 
       // Render directive based on type
+      const position = `${this.currentIndex + 1}/${this.directives.length}`;
+      const source = data.transaction.source ? ` (${data.transaction.source})` : "";
       if (data.transaction.type === "transaction") {
         this.renderer.render(data.transaction, editState);
-        this.counterEl.textContent = `Transaction ${this.currentIndex + 1}/${this.directives.length}`;
+        this.counterEl.textContent = `Transaction ${position}${source}`;
       } else if (data.transaction.type === "balance") {
         this.renderer.renderBalance(data.transaction);
-        this.counterEl.textContent = `Balance ${this.currentIndex + 1}/${this.directives.length}`;
+        this.counterEl.textContent = `Balance ${position}${source}`;
+      } else {
+        this.renderer.renderOther(data.transaction);
+        this.counterEl.textContent = `Directive ${position}${source}`;
       }
 
       this.clearMessage();
@@ -175,8 +238,10 @@ class StagingApp {
 
     const editState = this.editStates.get(currentDirective.id);
 
-    // For unbalanced transactions, require an account
-    if (!isBalanced && (!editState?.account || !editState.account.trim())) {
+    const splits = (editState?.splits ?? []).filter((split) => split.account.trim() !== "");
+
+    // For unbalanced transactions, require an account or splits
+    if (!isBalanced && (!editState?.account || !editState.account.trim()) && splits.length === 0) {
       this.showError("Please enter an expense account");
       return;
     }
@@ -191,35 +256,132 @@ class StagingApp {
       if (editState?.narration) {
         patch.narration = editState.narration;
       }
+      if (
+        editState?.date &&
+        currentDirective.type === "transaction" &&
+        editState.date !== currentDirective.date
+      ) {
+        patch.date = editState.date;
+      }
       if (!isBalanced && editState?.account) {
         patch.account = editState.account;
       }
+      if (splits.length > 0) {
+        patch.splits = splits;
+      }
+      for (const field of ["add_tags", "remove_tags", "add_links", "remove_links"] as const) {
+        const names = editState?.[field];
+        if (names && names.length > 0) {
+          patch[field] = names;
+        }
+      }
 
       const data = await this.api.commitTransaction(currentDirective.id, patch);
+      this.committedIds.push(data.undo_id ?? currentDirective.id);
+      this.undoBtn.disabled = false;
 
       if (data.remaining_count === 0) {
-        this.showSuccess("All transactions committed!");
-        this.transactionEl.textContent = "All done!";
-        this.counterEl.textContent = "0/0";
-        this.commitBtn.disabled = true;
-        this.prevBtn.disabled = true;
-        this.nextBtn.disabled = true;
-        this.directives = [];
+        this.showAllDone("All transactions committed!");
         return;
       }
 
-      // Remove committed transaction's edit state and directive
-      this.editStates.delete(currentDirective.id);
-      this.directives.splice(this.currentIndex, 1);
+      await this.removeCurrent(currentDirective.id);
+    } catch (err) {
+      this.showError(`Failed to commit transaction: ${String(err)}`);
+    }
+  }
 
-      // Adjust index if needed
-      if (this.currentIndex >= this.directives.length) {
-        this.currentIndex = this.directives.length - 1;
+  /** Never show the current directive again, it is written to the ignored file. */
+  async ignore() {
+    const currentDirective = this.directives[this.currentIndex];
+    if (!currentDirective) {
+      return;
+    }
+
+    try {
+      const data = await this.api.ignoreTransaction(currentDirective.id);
+      if (data.remaining_count === 0) {
+        this.showAllDone("All transactions reviewed!");
+        return;
       }
 
-      await this.loadTransaction();
+      await this.removeCurrent(currentDirective.id);
     } catch (err) {
-      this.showError(`Failed to commit transaction: ${String(err)}`);
+      this.showError(`Failed to ignore transaction: ${String(err)}`);
+    }
+  }
+
+  /** Run the importers again, the new items arrive through `reloadData`. */
+  async refresh() {
+    this.refreshBtn.disabled = true;
+    this.refreshBtn.textContent = "Refreshing...";
+    try {
+      const data = await this.api.refreshStaging();
+      await this.reloadData();
+      this.showSuccess(`Import refreshed, ${data.remaining_count} to review`);
+    } catch (err) {
+      this.showError(`Failed to refresh import: ${String(err)}`);
+    } finally {
+      this.refreshBtn.disabled = false;
+      this.refreshBtn.textContent = "Refresh import";
+    }
+  }
+
+  private showAllDone(message: string) {
+    this.showSuccess(message);
+    this.transactionEl.textContent = "All done!";
+    this.counterEl.textContent = "0/0";
+    this.commitBtn.disabled = true;
+    this.ignoreBtn.disabled = true;
+    this.prevBtn.disabled = true;
+    this.nextBtn.disabled = true;
+    this.allDirectives = [];
+    this.directives = [];
+  }
+
+  /** Drop a reviewed directive and its edit state, and show the next one. */
+  private async removeCurrent(id: string) {
+    this.editStates.delete(id);
+    this.directives.splice(this.currentIndex, 1);
+    this.allDirectives = this.allDirectives.filter((d) => d.id !== id);
+    if (this.directives.length === 0) {
+      // Other sources may still have items
+      await this.showDirectives();
+      return;
+    }
+
+    // Adjust index if needed
+    if (this.currentIndex >= this.directives.length) {
+      this.currentIndex = this.directives.length - 1;
+    }
+
+    await this.loadTransaction();
+  }
+
+  async undo() {
+    const id = this.committedIds.pop();
+    this.undoBtn.disabled = this.committedIds.length === 0;
+    if (!id) {
+      return;
+    }
+
+    try {
+      await this.api.uncommitTransaction(id);
+      await this.reloadData();
+
+      // Jump back to the restored transaction
+      // The restored transaction gets a counter suffix if duplicates are still in staging
+      const restoredIndex = this.directives.findIndex(
+        (d) => d.id === id || d.id.startsWith(`${id}-`),
+      );
+      if (restoredIndex !== -1) {
+        this.currentIndex = restoredIndex;
+        this.prevBtn.disabled = false;
+        this.nextBtn.disabled = false;
+        await this.loadTransaction();
+      }
+    } catch (err) {
+      this.showError(`Failed to undo commit: ${String(err)}`);
     }
   }
 
@@ -242,6 +404,7 @@ class StagingApp {
 
   private updateCommitButton() {
     const currentDirective = this.directives[this.currentIndex];
+    this.ignoreBtn.disabled = !currentDirective;
     if (!currentDirective) {
       this.commitBtn.disabled = true;
       return;
@@ -256,7 +419,8 @@ class StagingApp {
 
     const editState = this.editStates.get(currentDirective.id);
     const hasAccount = editState?.account && editState.account.trim() !== "";
-    this.commitBtn.disabled = !hasAccount;
+    const hasSplits = (editState?.splits ?? []).some((split) => split.account.trim() !== "");
+    this.commitBtn.disabled = !hasAccount && !hasSplits;
   }
 
   private showError(message: string) {
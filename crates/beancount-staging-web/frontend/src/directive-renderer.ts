@@ -1,21 +1,42 @@
-import type { Transaction, Balance } from "./model/beancount";
-import type { TransactionPatch } from "./api";
+import type { Transaction, Balance, Other } from "./model/beancount";
+import type { CommitSplit, TransactionPatch } from "./api";
 import { Autocomplete, type FilterFunction } from "./autocomplete";
 
 export type EditState = TransactionPatch;
 
+export type EditField =
+  | "payee"
+  | "narration"
+  | "date"
+  | "account"
+  | "splits"
+  | "add_tags"
+  | "remove_tags"
+  | "add_links"
+  | "remove_links";
+
 const EDITABLE_SHORTCUTS = {
   payee: "p",
   narration: "n",
   account: "a",
+  date: "d",
+  tags: "t",
 };
 
+const ADD_SPLIT_SHORTCUT = "s";
+
+/** Apply added and removed names to the original list, keeping the original order. */
+function applyEdits(original: string[], added: string[] = [], removed: string[] = []): string[] {
+  return [...original.filter((name) => !removed.includes(name)), ...added];
+}
+
 export class DirectiveRenderer {
   private autocomplete: Autocomplete;
+  private current: { txn: Transaction; editState?: EditState } | null = null;
 
   constructor(
     private container: HTMLElement,
-    private onInput: (field: "payee" | "narration" | "account", value: string) => void,
+    private onInput: (field: EditField, value: string | string[] | CommitSplit[]) => void,
     availableAccounts: string[],
     filterFn: FilterFunction,
   ) {
@@ -31,10 +52,17 @@ export class DirectiveRenderer {
   }
 
   render(txn: Transaction, editState?: EditState): void {
+    this.current = { txn, editState };
     this.container.innerHTML = "";
 
     // First line: date flag payee narration
-    this.container.appendChild(this.createColored(txn.date, "date"));
+    const dateField = this.createTextField(
+      editState?.date ?? txn.date,
+      EDITABLE_SHORTCUTS.date,
+      "date",
+    );
+    dateField.classList.add("date");
+    this.container.appendChild(dateField);
     this.container.appendChild(document.createTextNode(" " + txn.flag));
 
     if (txn.payee !== null) {
@@ -57,17 +85,18 @@ export class DirectiveRenderer {
 
     this.container.appendChild(document.createTextNode("\n"));
 
-    // Tags and links
-    if (txn.tags.length > 0) {
-      this.container.appendChild(
-        document.createTextNode("    " + txn.tags.map((t) => "#" + t).join(" ") + "\n"),
-      );
-    }
-    if (txn.links.length > 0) {
-      this.container.appendChild(
-        document.createTextNode("    " + txn.links.map((l) => "^" + l).join(" ") + "\n"),
-      );
-    }
+    // Tags and links, editable as one line of `#tag ^link` words
+    const tags = applyEdits(txn.tags, editState?.add_tags, editState?.remove_tags);
+    const links = applyEdits(txn.links, editState?.add_links, editState?.remove_links);
+    this.container.appendChild(document.createTextNode("    "));
+    this.container.appendChild(
+      this.createTextField(
+        [...tags.map((t) => "#" + t), ...links.map((l) => "^" + l)].join(" "),
+        EDITABLE_SHORTCUTS.tags,
+        (value) => this.updateTagsAndLinks(txn, value),
+      ),
+    );
+    this.container.appendChild(document.createTextNode("\n"));
 
     // Use backend-computed balance status
     const isBalanced = txn.is_balanced;
@@ -76,15 +105,45 @@ export class DirectiveRenderer {
     const expensePosting = txn.postings.find((p) => !p.amount);
     let hasEditableLine = false;
 
+    // Splits are shown in the currency of the primary posting
+    const splitCurrency = txn.postings.find((p) => p.amount)?.amount?.currency ?? "";
+    const splits = editState?.splits ?? [];
+    const renderSplits = () => {
+      splits.forEach((split, index) => {
+        this.container.appendChild(document.createTextNode("    "));
+        this.container.appendChild(
+          this.createAccountField(split.account, undefined, (value) =>
+            this.updateSplit(splits, index, { account: value }),
+          ),
+        );
+        this.container.appendChild(document.createTextNode("  "));
+        const amountField = this.createTextField(split.amount, undefined, (value) =>
+          this.updateSplit(splits, index, { amount: value }),
+        );
+        amountField.classList.add("amount");
+        amountField.setAttribute("data-split-amount", String(index));
+        this.container.appendChild(amountField);
+        this.container.appendChild(document.createTextNode(" "));
+        this.container.appendChild(this.createColored(splitCurrency, "currency"));
+        this.container.appendChild(document.createTextNode("\n"));
+      });
+    };
+
     // Postings
     for (const posting of txn.postings) {
+      // Splits go before the amountless balancing posting
+      if (posting === expensePosting) {
+        renderSplits();
+      }
       this.container.appendChild(document.createTextNode("    "));
 
       // If this is the expense posting without amount, make it editable
       if (posting === expensePosting) {
         const accountText = editState?.account ?? posting.account;
         this.container.appendChild(
-          this.createAccountField(accountText, EDITABLE_SHORTCUTS.account),
+          this.createAccountField(accountText, EDITABLE_SHORTCUTS.account, (value) =>
+            this.onInput("account", value),
+          ),
         );
         hasEditableLine = true;
       } else {
@@ -109,25 +168,66 @@ export class DirectiveRenderer {
     // Only add editable expense account line if:
     // 1. Transaction is not already balanced
     // 2. There wasn't already an editable posting
+    if (!hasEditableLine) {
+      renderSplits();
+    }
     if (!isBalanced && !hasEditableLine) {
       this.container.appendChild(document.createTextNode("    "));
       const accountText = editState?.account ?? "";
-      this.container.appendChild(this.createAccountField(accountText, EDITABLE_SHORTCUTS.account));
+      this.container.appendChild(
+        this.createAccountField(accountText, EDITABLE_SHORTCUTS.account, (value) =>
+          this.onInput("account", value),
+        ),
+      );
       this.container.appendChild(document.createTextNode("\n"));
     }
   }
 
+  /** Add an empty split line to the current transaction and focus its account. */
+  addSplit(): void {
+    if (!this.current) {
+      return;
+    }
+    const { txn, editState } = this.current;
+    const splits = [...(editState?.splits ?? []), { account: "", amount: "" }];
+    this.onInput("splits", splits);
+    this.render(txn, { ...editState, splits });
+
+    const accountFields = this.container.querySelectorAll<HTMLElement>("[data-split-account]");
+    accountFields[accountFields.length - 1]?.focus();
+  }
+
+  private updateTagsAndLinks(txn: Transaction, value: string) {
+    const words = value.split(/\s+/).filter((word) => word !== "");
+    const tags = words
+      .filter((word) => !word.startsWith("^"))
+      .map((word) => word.replace(/^#/, ""));
+    const links = words.filter((word) => word.startsWith("^")).map((word) => word.slice(1));
+
+    this.onInput("add_tags", tags.filter((tag) => !txn.tags.includes(tag)));
+    this.onInput("remove_tags", txn.tags.filter((tag) => !tags.includes(tag)));
+    this.onInput("add_links", links.filter((link) => !txn.links.includes(link)));
+    this.onInput("remove_links", txn.links.filter((link) => !links.includes(link)));
+  }
+
+  private updateSplit(splits: CommitSplit[], index: number, change: Partial<CommitSplit>) {
+    splits[index] = { ...splits[index], ...change };
+    this.onInput("splits", [...splits]);
+  }
+
   private createTextField(
     text: string,
-    key: string,
-    fieldName: "payee" | "narration",
+    key: string | undefined,
+    fieldName: "payee" | "narration" | "date" | ((value: string) => void),
   ): HTMLSpanElement {
     const span = document.createElement("span");
     span.contentEditable = "plaintext-only";
     span.spellcheck = false;
     span.textContent = text;
     span.className = "editable";
-    span.setAttribute("data-key", key);
+    if (key) {
+      span.setAttribute("data-key", key);
+    }
 
     span.addEventListener("focus", () => this.selectAll(span));
     span.addEventListener("keydown", (e) => {
@@ -142,19 +242,31 @@ export class DirectiveRenderer {
     });
     span.addEventListener("input", () => {
       const value = span.textContent?.trim() || "";
-      this.onInput(fieldName, value);
+      if (typeof fieldName === "function") {
+        fieldName(value);
+      } else {
+        this.onInput(fieldName, value);
+      }
     });
 
     return span;
   }
 
-  private createAccountField(text: string, key: string): HTMLSpanElement {
+  private createAccountField(
+    text: string,
+    key: string | undefined,
+    onChange: (value: string) => void,
+  ): HTMLSpanElement {
     const span = document.createElement("span");
     span.contentEditable = "plaintext-only";
     span.spellcheck = false;
     span.textContent = text;
     span.className = "editable";
-    span.setAttribute("data-key", key);
+    if (key) {
+      span.setAttribute("data-key", key);
+    } else {
+      span.setAttribute("data-split-account", "");
+    }
 
     span.addEventListener("focus", () => {
       this.selectAll(span);
@@ -163,7 +275,7 @@ export class DirectiveRenderer {
 
     span.addEventListener("input", () => {
       const value = span.textContent?.trim() || "";
-      this.onInput("account", value);
+      onChange(value);
       this.autocomplete.show(span);
     });
 
@@ -227,6 +339,7 @@ export class DirectiveRenderer {
   }
 
   renderBalance(bal: Balance): void {
+    this.current = null;
     this.container.innerHTML = "";
 
     // Format: date balance account amount [tolerance]
@@ -246,7 +359,25 @@ export class DirectiveRenderer {
     this.container.appendChild(document.createTextNode("\n"));
   }
 
+  renderOther(other: Other): void {
+    this.current = null;
+    this.container.innerHTML = "";
+
+    // The text starts with the date, which is highlighted like for other directives
+    const rest = other.text.startsWith(other.date)
+      ? other.text.slice(other.date.length)
+      : " " + other.text;
+    this.container.appendChild(this.createColored(other.date, "date"));
+    this.container.appendChild(document.createTextNode(rest + "\n"));
+  }
+
   private handleFocusShortcuts(e: KeyboardEvent) {
+    if (e.key === ADD_SPLIT_SHORTCUT && this.current) {
+      e.preventDefault();
+      this.addSplit();
+      return;
+    }
+
     // Focus editable fields by their hint key
     if (Object.values(EDITABLE_SHORTCUTS).includes(e.key)) {
       const editable = this.container.querySelector(`[data-key="${e.key}"]`);
@@ -1,6 +1,8 @@
+/** `source` names the [[staging]] source the directive came from, if it has a name. */
 export type Directive =
-  | ({ id: string; type: "transaction" } & Transaction)
-  | ({ id: string; type: "balance" } & Balance);
+  | ({ id: string; source: string | null; type: "transaction" } & Transaction)
+  | ({ id: string; source: string | null; type: "balance" } & Balance)
+  | ({ id: string; source: string | null; type: "other" } & Other);
 
 export interface Transaction {
   date: string;
@@ -20,6 +22,12 @@ export interface Balance {
   tolerance: string | null;
 }
 
+/** Any other directive (note, document, event, ...), rendered as journal text. */
+export interface Other {
+  date: string;
+  text: string;
+}
+
 export interface Posting {
   account: string;
   amount: Amount | null;
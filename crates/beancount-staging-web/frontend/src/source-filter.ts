@@ -0,0 +1,7 @@
+import type { Directive } from "./model/beancount";
+
+/** The directives of one staging source, or all of them if `source` is empty. */
+export function filterBySource(directives: Directive[], source: string): Directive[] {
+  if (!source) return directives;
+  return directives.filter((directive) => directive.source === source);
+}
@@ -54,6 +54,12 @@ describe("ApiClient", () => {
             postings: [],
           },
         },
+        predicted_account: "Expenses:Groceries",
+        predictions: [
+          { account: "Expenses:Groceries", probability: 0.75 },
+          { account: "Expenses:Household", probability: 0.25 },
+        ],
+        split_template: [],
       };
 
       global.fetch = vi.fn().mockResolvedValue({
@@ -123,4 +129,101 @@ describe("ApiClient", () => {
       await expect(client.commitTransaction("txn-1", "Invalid")).rejects.toThrow("Bad Request");
     });
   });
+
+  describe("ignoreTransaction", () => {
+    it("should ignore transaction successfully", async () => {
+      const mockResponse = {
+        ok: true,
+        remaining_count: 4,
+      };
+
+      global.fetch = vi.fn().mockResolvedValue({
+        ok: true,
+        json: async () => mockResponse,
+      });
+
+      const result = await client.ignoreTransaction("txn-1");
+
+      expect(global.fetch).toHaveBeenCalledWith("/api/transaction/txn-1/ignore", {
+        method: "POST",
+      });
+      expect(result).toEqual(mockResponse);
+    });
+
+    it("should throw error on failed request with error message", async () => {
+      global.fetch = vi.fn().mockResolvedValue({
+        ok: false,
+        statusText: "Internal Server Error",
+        json: async () => ({ error: "Failed to ignore: No ignored_file configured" }),
+      });
+
+      await expect(client.ignoreTransaction("txn-1")).rejects.toThrow(
+        "Failed to ignore: No ignored_file configured",
+      );
+    });
+  });
+
+  describe("refreshStaging", () => {
+    it("should refresh staging successfully", async () => {
+      const mockResponse = {
+        ok: true,
+        remaining_count: 7,
+      };
+
+      global.fetch = vi.fn().mockResolvedValue({
+        ok: true,
+        json: async () => mockResponse,
+      });
+
+      const result = await client.refreshStaging();
+
+      expect(global.fetch).toHaveBeenCalledWith("/api/staging/refresh", {
+        method: "POST",
+      });
+      expect(result).toEqual(mockResponse);
+    });
+
+    it("should throw error on failed request with error message", async () => {
+      global.fetch = vi.fn().mockResolvedValue({
+        ok: false,
+        statusText: "Bad Request",
+        json: async () => ({ error: "Failed to refresh staging: Staging command failed" }),
+      });
+
+      await expect(client.refreshStaging()).rejects.toThrow(
+        "Failed to refresh staging: Staging command failed",
+      );
+    });
+  });
+
+  describe("uncommitTransaction", () => {
+    it("should uncommit transaction successfully", async () => {
+      const mockResponse = {
+        ok: true,
+        remaining_count: 6,
+      };
+
+      global.fetch = vi.fn().mockResolvedValue({
+        ok: true,
+        json: async () => mockResponse,
+      });
+
+      const result = await client.uncommitTransaction("txn-1");
+
+      expect(global.fetch).toHaveBeenCalledWith("/api/transaction/txn-1/uncommit", {
+        method: "POST",
+      });
+      expect(result).toEqual(mockResponse);
+    });
+
+    it("should throw error on failed request with error message", async () => {
+      global.fetch = vi.fn().mockResolvedValue({
+        ok: false,
+        statusText: "Bad Request",
+        json: async () => ({ error: "Failed to uncommit" }),
+      });
+
+      await expect(client.uncommitTransaction("txn-1")).rejects.toThrow("Failed to uncommit");
+    });
+  });
 });
@@ -1,10 +1,11 @@
 import { describe, it, expect, beforeEach, vi, type Mock } from "vitest";
-import { DirectiveRenderer } from "../src/directive-renderer";
+import { DirectiveRenderer, type EditField } from "../src/directive-renderer";
+import type { CommitSplit } from "../src/api";
 import type { Transaction } from "../src/model/beancount";
 
 describe("DirectiveRenderer", () => {
   let container: HTMLElement;
-  let onInputMock: Mock<(field: "payee" | "narration" | "account", value: string) => void>;
+  let onInputMock: Mock<(field: EditField, value: string | string[] | CommitSplit[]) => void>;
   let filterMock: Mock<(query: string, items: string[]) => string[]>;
   let renderer: DirectiveRenderer;
 
@@ -436,6 +437,42 @@ describe("DirectiveRenderer", () => {
       expect(onInputMock).toHaveBeenCalledWith("narration", "New Narration");
     });
 
+    it("should call onInput when date is edited", () => {
+      const txn = createTransaction();
+      renderer.render(txn);
+
+      const dateField = container.querySelector('[data-key="d"]') as HTMLElement;
+      expect(dateField.textContent).toBe("2024-01-15");
+      dateField.textContent = "2024-01-12";
+      dateField.dispatchEvent(new Event("input"));
+
+      expect(onInputMock).toHaveBeenCalledWith("date", "2024-01-12");
+    });
+
+    it("should report added and removed tags and links", () => {
+      const txn = createTransaction({ tags: ["import"], links: ["booking-1"] });
+      renderer.render(txn);
+
+      const tagsField = container.querySelector('[data-key="t"]') as HTMLElement;
+      expect(tagsField.textContent).toBe("#import ^booking-1");
+
+      tagsField.textContent = "#trip-2026 ^booking-1 ^invoice";
+      tagsField.dispatchEvent(new Event("input"));
+
+      expect(onInputMock).toHaveBeenCalledWith("add_tags", ["trip-2026"]);
+      expect(onInputMock).toHaveBeenCalledWith("remove_tags", ["import"]);
+      expect(onInputMock).toHaveBeenCalledWith("add_links", ["invoice"]);
+      expect(onInputMock).toHaveBeenCalledWith("remove_links", []);
+    });
+
+    it("should render tags and links from edit state", () => {
+      const txn = createTransaction({ tags: ["import"] });
+      renderer.render(txn, { add_tags: ["trip-2026"], remove_tags: ["import"] });
+
+      const tagsField = container.querySelector('[data-key="t"]') as HTMLElement;
+      expect(tagsField.textContent).toBe("#trip-2026");
+    });
+
     it("should call onInput when account is edited", () => {
       const txn = createTransaction({
         postings: [
@@ -646,4 +683,74 @@ describe("DirectiveRenderer", () => {
       expect(focusSpy).toHaveBeenCalled();
     });
   });
+
+  describe("renderOther", () => {
+    it("should render other directives as text", () => {
+      renderer.renderOther({
+        date: "2024-01-15",
+        text: '2024-01-15 note Assets:Bank "Card replaced"',
+      });
+
+      expect(container.textContent).toBe('2024-01-15 note Assets:Bank "Card replaced"\n');
+      expect(container.querySelector(".date")?.textContent).toBe("2024-01-15");
+    });
+  });
+
+  describe("splits", () => {
+    it("should render splits before the balancing posting", () => {
+      const txn = createTransaction();
+      renderer.render(txn, {
+        account: "Expenses:Food",
+        splits: [{ account: "Expenses:Household", amount: "12.50" }],
+      });
+
+      const text = container.textContent ?? "";
+      expect(text).toContain("Expenses:Household  12.50 USD");
+      expect(text.indexOf("Expenses:Household")).toBeLessThan(text.indexOf("Expenses:Food"));
+    });
+
+    it("should add an empty split on 's' key", () => {
+      const txn = createTransaction();
+      renderer.render(txn, { account: "Expenses:Food" });
+
+      const event = new KeyboardEvent("keydown", { key: "s", bubbles: true });
+      document.dispatchEvent(event);
+
+      expect(onInputMock).toHaveBeenCalledWith("splits", [{ account: "", amount: "" }]);
+      expect(container.querySelectorAll("[data-split-account]").length).toBe(1);
+    });
+
+    it("should call onInput with all splits when a split is edited", () => {
+      const txn = createTransaction();
+      renderer.render(txn, {
+        splits: [
+          { account: "Expenses:Food", amount: "30.00" },
+          { account: "Expenses:Household", amount: "" },
+        ],
+      });
+
+      const amountField = container.querySelector('[data-split-amount="1"]') as HTMLElement;
+      amountField.textContent = "20.00";
+      amountField.dispatchEvent(new Event("input"));
+
+      expect(onInputMock).toHaveBeenCalledWith("splits", [
+        { account: "Expenses:Food", amount: "30.00" },
+        { account: "Expenses:Household", amount: "20.00" },
+      ]);
+    });
+
+    it("should not add splits to balance directives", () => {
+      renderer.renderBalance({
+        date: "2024-01-15",
+        account: "Assets:Bank",
+        amount: { value: "100.00", currency: "USD" },
+        tolerance: null,
+      });
+
+      const event = new KeyboardEvent("keydown", { key: "s", bubbles: true });
+      document.dispatchEvent(event);
+
+      expect(onInputMock).not.toHaveBeenCalled();
+    });
+  });
 });
@@ -0,0 +1,22 @@
+import { describe, it, expect } from "vitest";
+import { filterBySource } from "../src/source-filter";
+import type { Directive } from "../src/model/beancount";
+
+describe("filterBySource", () => {
+  const directive = (id: string, source: string | null): Directive => ({
+    id,
+    source,
+    type: "other",
+    date: "2025-01-01",
+    text: "",
+  });
+  const directives = [directive("a", "bank"), directive("b", "paypal"), directive("c", null)];
+
+  it("should return all directives without a selected source", () => {
+    expect(filterBySource(directives, "")).toEqual(directives);
+  });
+
+  it("should only return directives of the selected source", () => {
+    expect(filterBySource(directives, "paypal").map((d) => d.id)).toEqual(["b"]);
+  });
+});
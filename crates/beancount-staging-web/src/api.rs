@@ -9,12 +9,17 @@ use axum::{
 };
 use futures::stream::Stream;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::convert::Infallible;
 use tokio_stream::StreamExt;
 use tokio_stream::wrappers::BroadcastStream;
 
 use crate::state::AppState;
 use beancount_staging::Directive;
+use beancount_staging::reconcile::staging_origin;
+
+/// Number of candidate accounts sent with a transaction
+const MAX_PREDICTIONS: usize = 5;
 
 fn serialize_directive(id: &str, directive: &Directive) -> SerializedDirective {
     use beancount_parser::DirectiveContent;
@@ -62,14 +67,16 @@ fn serialize_directive(id: &str, directive: &Directive) -> SerializedDirective {
             },
             tolerance: bal.tolerance.as_ref().map(|t| t.to_string()),
         }),
-        other => todo!(
-            "Directive type not yet supported for serialization: {:?}",
-            other
-        ),
+        // Notes, documents, events, ... can only be accepted as they are
+        _ => SerializedDirectiveContent::Other(SerializedOther {
+            date: directive.date.to_string(),
+            text: directive.to_string(),
+        }),
     };
 
     SerializedDirective {
         id: id.to_string(),
+        source: staging_origin(directive).map(ToString::to_string),
         content,
     }
 }
@@ -90,11 +97,15 @@ pub struct InitResponse {
     pub items: Vec<SerializedDirective>,
     pub current_index: usize,
     pub available_accounts: Vec<String>,
+    /// Names of the staging sources, for filtering
+    pub sources: Vec<String>,
 }
 
 #[derive(Serialize)]
 pub struct SerializedDirective {
     pub id: String,
+    /// Name of the staging source the directive came from
+    pub source: Option<String>,
     #[serde(flatten)]
     pub content: SerializedDirectiveContent,
 }
@@ -104,6 +115,7 @@ pub struct SerializedDirective {
 pub enum SerializedDirectiveContent {
     Transaction(SerializedTransaction),
     Balance(SerializedBalance),
+    Other(SerializedOther),
 }
 
 #[derive(Serialize)]
@@ -126,6 +138,13 @@ pub struct SerializedBalance {
     pub tolerance: Option<String>,
 }
 
+#[derive(Serialize)]
+pub struct SerializedOther {
+    pub date: String,
+    /// The directive as it would be written to the journal
+    pub text: String,
+}
+
 #[derive(Serialize)]
 pub struct SerializedPosting {
     pub account: String,
@@ -144,6 +163,16 @@ pub struct SerializedAmount {
 pub struct TransactionResponse {
     pub transaction: SerializedDirective,
     pub predicted_account: Option<String>,
+    /// Candidate accounts, most likely first; the first one is `predicted_account`
+    pub predictions: Vec<SerializedPrediction>,
+    /// Suggested split if the payee is always split the same way, empty otherwise
+    pub split_template: Vec<CommitSplitRequest>,
+}
+
+#[derive(Serialize)]
+pub struct SerializedPrediction {
+    pub account: String,
+    pub probability: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -151,12 +180,34 @@ pub struct CommitRequest {
     pub account: Option<String>,
     pub payee: Option<String>,
     pub narration: Option<String>,
+    pub date: Option<String>,
+    #[serde(default)]
+    pub splits: Vec<CommitSplitRequest>,
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+    #[serde(default)]
+    pub add_links: Vec<String>,
+    #[serde(default)]
+    pub remove_links: Vec<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitSplitRequest {
+    pub account: String,
+    pub amount: String,
 }
 
 #[derive(Serialize)]
 pub struct CommitResponse {
     pub ok: bool,
     pub remaining_count: usize,
+    /// Key to undo a commit with; it stays valid when staging is reloaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undo_id: Option<String>,
 }
 
 pub async fn init_handler(State(state): State<AppState>) -> Result<Json<InitResponse>, StatusCode> {
@@ -175,6 +226,12 @@ pub async fn init_handler(State(state): State<AppState>) -> Result<Json<InitResp
         items,
         current_index: 0,
         available_accounts: inner.available_accounts.iter().cloned().collect(),
+        sources: inner
+            .reconcile_config
+            .staging_source_names()
+            .into_iter()
+            .map(ToString::to_string)
+            .collect(),
     }))
 }
 
@@ -185,11 +242,29 @@ pub async fn get_transaction(
     let inner = state.lock().unwrap();
 
     let directive = inner.staging_items.get(&id).ok_or(StatusCode::NOT_FOUND)?;
-    let predicted_account = inner.predict(directive);
+    let predictions: Vec<SerializedPrediction> = inner
+        .predict(directive, MAX_PREDICTIONS)
+        .into_iter()
+        .map(|prediction| SerializedPrediction {
+            account: prediction.account.to_string(),
+            probability: prediction.probability,
+        })
+        .collect();
+
+    let split_template = inner
+        .suggest_split(directive)
+        .into_iter()
+        .map(|leg| CommitSplitRequest {
+            account: leg.account.to_string(),
+            amount: leg.amount.to_string(),
+        })
+        .collect();
 
     Ok(Json(TransactionResponse {
         transaction: serialize_directive(&id, directive),
-        predicted_account: predicted_account.map(|account| account.to_string()),
+        predicted_account: predictions.first().map(|p| p.account.clone()),
+        predictions,
+        split_template,
     }))
 }
 
@@ -213,8 +288,8 @@ pub async fn commit_transaction(
             false
         };
 
-    // For unbalanced transactions, require an account
-    if !is_balanced && payload.account.is_none() {
+    // For unbalanced transactions, require an account or splits
+    if !is_balanced && payload.account.is_none() && payload.splits.is_empty() {
         return Err((
             StatusCode::UNPROCESSABLE_ENTITY,
             Json(ErrorResponse {
@@ -224,34 +299,168 @@ pub async fn commit_transaction(
             .into_response());
     }
 
+    let splits = payload
+        .splits
+        .iter()
+        .map(|split| {
+            let amount = split.amount.trim().parse().map_err(|_| {
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(ErrorResponse {
+                        error: format!("Invalid split amount: '{}'", split.amount),
+                    }),
+                )
+                    .into_response()
+            })?;
+            Ok(beancount_staging::CommitSplit {
+                account: &split.account,
+                amount,
+            })
+        })
+        .collect::<Result<Vec<_>, Response>>()?;
+
+    let add_tags: Vec<&str> = payload.add_tags.iter().map(String::as_str).collect();
+    let remove_tags: Vec<&str> = payload.remove_tags.iter().map(String::as_str).collect();
+    let add_links: Vec<&str> = payload.add_links.iter().map(String::as_str).collect();
+    let remove_links: Vec<&str> = payload.remove_links.iter().map(String::as_str).collect();
+    let metadata: Vec<(&str, &str)> = payload
+        .metadata
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+
     // Use library function to commit transaction
-    let journal_path = &inner.reconcile_config.journal_paths[0];
-    beancount_staging::commit_transaction(
-        directive,
-        payload.account.as_deref(),
-        payload.payee.as_deref(),
-        payload.narration.as_deref(),
-        beancount_staging::SourceMetaTarget::Transaction,
-        journal_path,
-    )
-    .map_err(|e| {
-        tracing::error!("Failed to commit transaction {}: {}", id, e);
+    let overrides = beancount_staging::CommitOverrides {
+        expense_account: payload.account.as_deref(),
+        payee: payload.payee.as_deref(),
+        narration: payload.narration.as_deref(),
+        date: payload.date.as_deref(),
+        splits: &splits,
+        add_tags: &add_tags,
+        remove_tags: &remove_tags,
+        add_links: &add_links,
+        remove_links: &remove_links,
+        metadata: &metadata,
+    };
+    let journal_path = inner
+        .reconcile_config
+        .commit_target(directive, &overrides)
+        .and_then(|journal_path| {
+            inner.reconcile_config.commit_transactions(
+                &[(directive, overrides)],
+                beancount_staging::SourceMetaTarget::Transaction,
+            )?;
+            Ok(journal_path)
+        })
+        .map_err(|e| {
+            tracing::error!("Failed to commit transaction {}: {}", id, e);
+            ErrorResponse {
+                error: format!("Failed to commit: {}", e),
+            }
+            .into_response()
+        })?;
+
+    tracing::info!("Committed transaction {} with patch: {:?}", id, payload);
+
+    // Move from staging items to committed items, so the commit can be undone
+    let undo_id = inner.record_commit(&id, &overrides, journal_path);
+    state.request_retrain();
+
+    let remaining_count = inner.staging_items.len();
+
+    Ok(Json(CommitResponse {
+        ok: true,
+        remaining_count,
+        undo_id,
+    }))
+}
+
+pub async fn ignore_transaction(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<CommitResponse>, Response> {
+    let mut inner = state.lock().unwrap();
+
+    let directive = inner
+        .staging_items
+        .get(&id)
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+
+    inner.reconcile_config.ignore(&[directive]).map_err(|e| {
+        tracing::error!("Failed to ignore transaction {}: {}", id, e);
         ErrorResponse {
-            error: format!("Failed to commit: {}", e),
+            error: format!("Failed to ignore: {}", e),
         }
         .into_response()
     })?;
 
-    tracing::info!("Committed transaction {} with patch: {:?}", id, payload);
+    tracing::info!("Ignored transaction {}", id);
 
-    // Remove from staging items
     inner.staging_items.remove(&id);
+    let remaining_count = inner.staging_items.len();
+
+    Ok(Json(CommitResponse {
+        ok: true,
+        remaining_count,
+        undo_id: None,
+    }))
+}
+
+pub async fn uncommit_transaction(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<CommitResponse>, Response> {
+    let mut inner = state.lock().unwrap();
+
+    let item = inner
+        .last_commit(&id)
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+
+    beancount_staging::uncommit_transaction(&item.directive, &item.journal_path).map_err(|e| {
+        tracing::error!("Failed to uncommit transaction {}: {}", id, e);
+        ErrorResponse {
+            error: format!("Failed to uncommit: {}", e),
+        }
+        .into_response()
+    })?;
+
+    tracing::info!("Uncommitted transaction {}", id);
+
+    // Put back into staging items
+    inner.restore_commit(&id);
+    state.request_retrain();
 
     let remaining_count = inner.staging_items.len();
 
     Ok(Json(CommitResponse {
         ok: true,
         remaining_count,
+        undo_id: None,
+    }))
+}
+
+pub async fn refresh_staging(
+    State(state): State<AppState>,
+) -> Result<Json<CommitResponse>, Response> {
+    // The importers may take a while, don't block the runtime meanwhile
+    let remaining_count = tokio::task::spawn_blocking(move || state.refresh_staging())
+        .await
+        .map_err(|e| {
+            tracing::error!("Staging refresh panicked: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?
+        .map_err(|e| {
+            tracing::error!("Failed to refresh staging: {}", e);
+            ErrorResponse {
+                error: format!("Failed to refresh staging: {}", e),
+            }
+            .into_response()
+        })?;
+
+    Ok(Json(CommitResponse {
+        ok: true,
+        remaining_count,
+        undo_id: None,
     }))
 }
 
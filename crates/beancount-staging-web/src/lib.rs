@@ -1,4 +1,5 @@
 mod api;
+mod retrain;
 mod state;
 mod static_files;
 mod watcher;
@@ -8,8 +9,8 @@ use axum::{
     Router,
     routing::{get, post},
 };
-use beancount_staging::AutoCategorizeRule;
-use beancount_staging::reconcile::StagingSource;
+use beancount_staging::reconcile::ReconcileConfig;
+use beancount_staging::{AutoCategorizeRule, Glob};
 use std::{
     net::{Ipv4Addr, SocketAddrV4},
     path::PathBuf,
@@ -18,7 +19,7 @@ use tokio::{net::TcpListener, task::spawn_blocking};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
 
-use state::{AppState, FileChangeEvent};
+use state::AppState;
 use watcher::FileWatcher;
 
 // also change the clap default
@@ -31,12 +32,11 @@ pub enum ListenerType {
 }
 
 pub async fn run(
-    journal: Vec<PathBuf>,
-    staging_source: StagingSource,
+    reconcile_config: ReconcileConfig,
     auto_rules: Vec<AutoCategorizeRule>,
     listener_type: ListenerType,
 ) -> anyhow::Result<()> {
-    let app = router(journal, staging_source, auto_rules)?;
+    let app = router(reconcile_config, auto_rules)?;
 
     match listener_type {
         ListenerType::Tcp(port) => {
@@ -67,8 +67,7 @@ pub async fn run(
 }
 
 pub fn router(
-    journal: Vec<PathBuf>,
-    staging_source: StagingSource,
+    reconcile_config: ReconcileConfig,
     auto_rules: Vec<AutoCategorizeRule>,
 ) -> Result<Router> {
     // Initialize tracing if not already initialized
@@ -82,54 +81,74 @@ pub fn router(
 
     // Initialize application state first
     let (file_change_tx, _rx) = tokio::sync::broadcast::channel(100);
-    let mut state = AppState::new(journal, staging_source, auto_rules, file_change_tx.clone())?;
+    let state = AppState::new(reconcile_config, auto_rules, file_change_tx.clone())?;
 
-    spawn_blocking({
-        let state = state.clone();
-        move || {
-            if let Err(e) = state.inner.lock().unwrap().retrain() {
-                tracing::error!("Error trying to retrain: {e}");
-            }
-        }
-    });
+    tokio::spawn(retrain::run(state.clone()));
 
-    // Set up file watcher and store it in AppState to keep it alive
-    let watcher = {
+    // Set up file watcher. Files included after startup, e.g. new commit targets, may be in
+    // directories which aren't watched yet, they are sent to the task owning the watcher.
+    let (watch_tx, mut watch_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = {
         let state_ = state.lock().unwrap();
-        let relevant_files = {
-            state_
-                .reconcile_state
-                .journal_sourceset
-                .iter()
-                .chain(state_.reconcile_state.staging_sourceset.iter())
-                .map(AsRef::as_ref)
-        };
+        let relevant_dirs = state_.watched_dirs();
+        // Inputs of the staging command
+        let watched_dirs = state_.staging_watch.iter().map(Glob::root);
         let state_for_watcher = state.clone();
-        FileWatcher::new(relevant_files, move || {
-            if let Err(e) = state_for_watcher.reload() {
-                tracing::error!("Failed to reload state: {}", e);
-            } else {
-                tracing::info!("State reloaded successfully");
-            }
-
-            // notify clients via SSE
-            let subscriber_count = state_for_watcher.file_change_tx.receiver_count();
-            match state_for_watcher.file_change_tx.send(FileChangeEvent) {
-                Ok(_) => {
-                    tracing::info!(
-                        "Sent file change event to {} SSE clients",
-                        subscriber_count - 1
-                    );
+        FileWatcher::new(
+            relevant_dirs.iter().map(AsRef::as_ref),
+            watched_dirs,
+            move |changed| {
+                match state_for_watcher.reload(changed) {
+                    Ok(true) => tracing::info!("State reloaded successfully"),
+                    // Other files in a watched directory
+                    Ok(false) => return,
+                    Err(e) => tracing::error!("Failed to reload state: {}", e),
                 }
-                Err(e) => {
-                    tracing::error!("Failed to send SSE event: {}", e);
+                let _ = watch_tx.send(state_for_watcher.lock().unwrap().watched_dirs());
+
+                // notify clients via SSE
+                state_for_watcher.notify_clients();
+            },
+        )?
+    };
+    // The task keeps the watcher alive, its sender lives as long as the watcher
+    tokio::spawn(async move {
+        while let Some(dirs) = watch_rx.recv().await {
+            for dir in dirs {
+                if let Err(e) = watcher.watch(&dir) {
+                    tracing::warn!("Failed to watch {}: {}", dir.display(), e);
                 }
             }
-        })?
-    };
+        }
+    });
 
-    // Store watcher in AppState to prevent it from being dropped
-    state.set_watcher(watcher);
+    // Each staging command with a refresh interval is run again on its own schedule
+    let refresh_intervals: Vec<_> = state
+        .lock()
+        .unwrap()
+        .reconcile_config
+        .staging_sources
+        .iter()
+        .enumerate()
+        .filter_map(|(index, source)| Some((index, source.refresh_interval?)))
+        .collect();
+    for (index, refresh_interval) in refresh_intervals {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(refresh_interval);
+            // The first tick completes immediately, but staging was just read
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let state = state.clone();
+                match spawn_blocking(move || state.refresh_staging_source(index)).await {
+                    Ok(Ok(())) => tracing::info!("Refreshed staging"),
+                    Ok(Err(e)) => tracing::error!("Failed to refresh staging: {}", e),
+                    Err(e) => tracing::error!("Staging refresh panicked: {}", e),
+                }
+            }
+        });
+    }
 
     // Build router with API routes first, then fallback to embedded static files
     let app = Router::new()
@@ -139,6 +158,15 @@ pub fn router(
             "/api/transaction/{index}/commit",
             post(api::commit_transaction),
         )
+        .route(
+            "/api/transaction/{index}/uncommit",
+            post(api::uncommit_transaction),
+        )
+        .route(
+            "/api/transaction/{index}/ignore",
+            post(api::ignore_transaction),
+        )
+        .route("/api/staging/refresh", post(api::refresh_staging))
         .route("/api/file-changes", get(api::file_changes_stream))
         .with_state(state)
         .layer(TraceLayer::new_for_http())
@@ -0,0 +1,57 @@
+//! Retraining the predictor in the background, so categorizations made while reviewing
+//! improve later suggestions.
+use beancount_staging_predictor::splits::SplitTemplates;
+use beancount_staging_predictor::training::extract_training_examples;
+use std::time::Duration;
+use tokio::task::spawn_blocking;
+
+use crate::state::{AppState, model_path, train_predictor};
+
+/// Commits less than this apart are learned in a single retraining.
+const RETRAIN_DEBOUNCE: Duration = Duration::from_secs(10);
+
+/// Train the predictor for the journal, then again whenever commits have settled.
+pub async fn run(state: AppState) {
+    loop {
+        retrain(&state).await;
+
+        state.retrain_requested.notified().await;
+        while tokio::time::timeout(RETRAIN_DEBOUNCE, state.retrain_requested.notified())
+            .await
+            .is_ok()
+        {}
+    }
+}
+
+/// Train on the current journal without holding the lock, then swap in the new predictor.
+async fn retrain(state: &AppState) {
+    // Only the snapshot is taken under the lock, a pass over the journal would block
+    // every request meanwhile
+    let (journal, model_path, learned_count) = {
+        let inner = state.lock().unwrap();
+        (
+            inner.reconcile_state.journal.clone(),
+            model_path(&inner.reconcile_config),
+            inner.learned_count(),
+        )
+    };
+
+    let trained = spawn_blocking(move || {
+        let examples = extract_training_examples(&journal);
+        let split_templates = SplitTemplates::learn(&journal);
+        (
+            train_predictor(&examples, model_path.as_deref()),
+            split_templates,
+        )
+    })
+    .await;
+    match trained {
+        Ok((predictor, split_templates)) => {
+            state
+                .lock()
+                .unwrap()
+                .swap_predictor(predictor, split_templates, learned_count);
+        }
+        Err(e) => tracing::error!("Error trying to retrain: {e}"),
+    }
+}
@@ -1,19 +1,21 @@
 use beancount_parser::Account;
 use beancount_staging::reconcile::{
-    ReconcileConfig, ReconcileItemKind, ReconcileState, StagingSource,
+    CACHE_DIR, ReconcileConfig, ReconcileItemKind, ReconcileState, SourceSet, StagingRefresh,
 };
-use beancount_staging::{AutoCategorizeRule, Directive, DirectiveContent};
+use beancount_staging::{AutoCategorizeRule, CommitOverrides, Directive, DirectiveContent, Glob};
 use beancount_staging_predictor::preprocessing::Alpha;
-use beancount_staging_predictor::{DecisionTreePredictor, PredictionInput, Predictor};
+use beancount_staging_predictor::splits::{SplitLeg, SplitTemplates};
+use beancount_staging_predictor::{
+    DecisionTreePredictor, PayeeFrequencyPredictor, PredictionInput, Predictor, RankedPrediction,
+    TrainingExample,
+};
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
-use tokio::sync::broadcast;
-
-use crate::watcher::FileWatcher;
+use tokio::sync::{Notify, broadcast};
 
 /// Generates unique IDs for directives, handling collisions by adding counter suffixes
 struct UniqueIdGenerator {
@@ -69,11 +71,17 @@ impl UniqueIdGenerator {
     }
 }
 
-fn train_predictor(reconcile_state: &ReconcileState) -> Option<DecisionTreePredictor<Alpha>> {
-    use beancount_staging_predictor::training::extract_training_examples;
+/// Where the trained predictor is saved, in the cache directory next to the journal.
+pub(crate) fn model_path(reconcile_config: &ReconcileConfig) -> Option<PathBuf> {
+    let journal = reconcile_config.journal_paths.first()?;
+    Some(journal.parent()?.join(CACHE_DIR).join("model.bin"))
+}
 
-    // Extract training examples from journal directives
-    let examples = extract_training_examples(&reconcile_state.journal);
+pub(crate) fn train_predictor(
+    examples: &[TrainingExample],
+    model_path: Option<&Path>,
+) -> Option<DecisionTreePredictor<Alpha>> {
+    use beancount_staging_predictor::model::{load_model, model_key, save_model};
 
     // Require minimum training data
     const MIN_TRAINING_EXAMPLES: usize = 10;
@@ -86,19 +94,103 @@ fn train_predictor(reconcile_state: &ReconcileState) -> Option<DecisionTreePredi
         return None;
     }
 
+    // Reuse the saved model unless the journal history changed
+    let key = model_key::<Alpha>(examples);
+    if let Some(path) = model_path {
+        match load_model(path, key) {
+            Ok(Some(predictor)) => {
+                tracing::info!("Loaded predictor from {}", path.display());
+                return Some(predictor);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to load predictor, retraining: {e:#}"),
+        }
+    }
+
     let start = Instant::now();
 
     // Train the predictor
-    let predictor = DecisionTreePredictor::<Alpha>::train(&examples);
+    let predictor = DecisionTreePredictor::<Alpha>::train(examples);
     tracing::info!(
         "Training predictor with {} examples took {:?}",
         examples.len(),
         start.elapsed()
     );
 
+    if let Some(path) = model_path
+        && let Err(e) = save_model(path, key, &predictor)
+    {
+        tracing::warn!("Failed to save predictor: {e:#}");
+    }
+
     Some(predictor)
 }
 
+/// The training example for committing `directive` with `overrides`, if it is categorized
+/// into a single account.
+fn commit_example(
+    directive: &Directive,
+    overrides: &CommitOverrides<'_>,
+) -> Option<TrainingExample> {
+    let DirectiveContent::Transaction(txn) = &directive.content else {
+        return None;
+    };
+    // Same as `extract_training_examples` once the balancing posting is added
+    if txn.postings.len() != 1 || !overrides.splits.is_empty() {
+        return None;
+    }
+
+    let source = &txn.postings[0];
+    Some(TrainingExample {
+        source_account: source.account.clone(),
+        payee: overrides
+            .payee
+            .map(ToString::to_string)
+            .or_else(|| txn.payee.clone()),
+        narration: overrides
+            .narration
+            .map(ToString::to_string)
+            .or_else(|| txn.narration.clone())
+            .unwrap_or_default(),
+        amount: source.amount.as_ref().map(|amount| amount.value),
+        currency: source
+            .amount
+            .as_ref()
+            .map(|amount| amount.currency.to_string()),
+        // Committed under this date, like a retrain from the journal sees it
+        date: Some(match overrides.date {
+            Some(date) => beancount_staging::parse_date(date).ok()?,
+            None => directive.date,
+        }),
+        weight: 1.0,
+        target_account: overrides.expense_account?.parse().ok()?,
+    })
+}
+
+/// A commit of this session which can be undone.
+pub struct CommittedItem {
+    /// The staging directive as it was before committing
+    pub directive: Directive,
+    /// The journal file it was committed to
+    pub journal_path: PathBuf,
+}
+
+/// A commit of this session the predictor learned from.
+struct LearnedCommit {
+    /// Undo key of the committed directive
+    id: String,
+    /// Number of commits learned before this one
+    seq: usize,
+    example: TrainingExample,
+}
+
+/// Whether `path` is one of the files in `sourceset`.
+fn contains_path(sourceset: &SourceSet, path: &Path) -> bool {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    let path = canonical(path);
+    sourceset.iter().any(|file| canonical(file) == path)
+}
+
 #[derive(Clone, Debug)]
 pub struct FileChangeEvent;
 
@@ -106,20 +198,32 @@ pub struct FileChangeEvent;
 pub struct AppState {
     pub inner: Arc<Mutex<AppStateInner>>,
     pub file_change_tx: broadcast::Sender<FileChangeEvent>,
-    /// FileWatcher must be kept alive for the duration of the application.
-    /// It's stored here to prevent it from being dropped.
-    _watcher: Option<Arc<FileWatcher>>,
+    /// Wakes up the background retraining, see [`crate::retrain`].
+    pub retrain_requested: Arc<Notify>,
 }
 
 pub struct AppStateInner {
     pub reconcile_config: ReconcileConfig,
     pub reconcile_state: ReconcileState,
     pub auto_rules: Vec<AutoCategorizeRule>,
+    /// Files which rerun the staging command when they change.
+    pub staging_watch: Vec<Glob>,
 
     // derived data
     pub staging_items: BTreeMap<String, Directive>,
+    /// Staging directives committed through the UI in this session, so that a commit can
+    /// be undone. Keyed by their staging id without collision counter, since the counters
+    /// of duplicates change when staging is reloaded; the last directive is undone first.
+    pub committed_items: BTreeMap<String, Vec<CommittedItem>>,
     pub available_accounts: BTreeSet<String>,
     pub predictor: Option<DecisionTreePredictor<Alpha>>,
+    /// Learns from the commits of this session right away, until `predictor` is retrained
+    /// with them.
+    pub session_predictor: PayeeFrequencyPredictor,
+    /// Splits for payees whose transactions are always split the same way
+    pub split_templates: SplitTemplates,
+    learned_commits: Vec<LearnedCommit>,
+    learned_count: usize,
 }
 
 /// Why an `OnlyInStaging` directive may be auto-committed.
@@ -145,30 +249,88 @@ impl AutoCommitDecision<'_> {
 
 impl AppStateInner {
     fn new(
-        journal_paths: Vec<PathBuf>,
-        staging_source: StagingSource,
+        reconcile_config: ReconcileConfig,
         auto_rules: Vec<AutoCategorizeRule>,
+        staging_watch: Vec<Glob>,
     ) -> Self {
-        let reconcile_config = ReconcileConfig::new(journal_paths, staging_source);
-
         AppStateInner {
             reconcile_config,
             reconcile_state: ReconcileState::default(),
             auto_rules,
+            staging_watch,
             staging_items: BTreeMap::new(),
+            committed_items: BTreeMap::new(),
             available_accounts: BTreeSet::default(),
             predictor: None,
+            session_predictor: PayeeFrequencyPredictor::default(),
+            split_templates: SplitTemplates::default(),
+            learned_commits: Vec::new(),
+            learned_count: 0,
         }
     }
 
-    fn reload(&mut self) -> anyhow::Result<()> {
-        self.reconcile_state = self.reconcile_config.read()?;
+    /// Read journal and staging again after the files in `changed` were modified, or
+    /// everything if `changed` is empty.
+    ///
+    /// Returns whether anything was read, changes to unrelated files are ignored.
+    fn reload(&mut self, changed: &[PathBuf]) -> anyhow::Result<bool> {
+        let state = &self.reconcile_state;
+        let refresh = if changed.is_empty() {
+            StagingRefresh::IfChanged
+        } else if changed
+            .iter()
+            .any(|path| self.staging_watch.iter().any(|glob| glob.matches(path)))
+        {
+            // The command may not know its inputs changed
+            StagingRefresh::Force
+        } else if changed
+            .iter()
+            .any(|path| contains_path(&state.staging_sourceset, path))
+        {
+            StagingRefresh::IfChanged
+        } else if changed
+            .iter()
+            .any(|path| contains_path(&state.journal_sourceset, path))
+        {
+            // Our own commits only touch the journal, no need to run the importers again
+            StagingRefresh::Reuse
+        } else {
+            return Ok(false);
+        };
+        self.reload_with(refresh)?;
+        Ok(true)
+    }
+
+    /// Directories of the journal and staging files, watched for changes.
+    ///
+    /// Rewritten journal files are replaced by renaming, so the directories are watched
+    /// rather than the files themselves.
+    pub fn watched_dirs(&self) -> BTreeSet<PathBuf> {
+        let state = &self.reconcile_state;
+        state
+            .journal_sourceset
+            .iter()
+            .chain(state.staging_sourceset.iter())
+            .map(|path| match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+                _ => PathBuf::from("."),
+            })
+            .collect()
+    }
+
+    fn reload_with(&mut self, refresh: StagingRefresh) -> anyhow::Result<()> {
+        self.reload_sources(|_| refresh)
+    }
+
+    /// Like [`Self::reload_with`], deciding for each staging source by its index.
+    fn reload_sources(&mut self, refresh: impl Fn(usize) -> StagingRefresh) -> anyhow::Result<()> {
+        self.reconcile_state = self.reconcile_config.reread_sources(refresh)?;
         let results = self.reconcile_state.reconcile()?;
 
         if self.auto_commit_staging(&results) > 0 {
             // Re-read so newly-committed transactions show up as journal-matched
             // and are filtered out of the UI list below.
-            self.reconcile_state = self.reconcile_config.read()?;
+            self.reconcile_state = self.reconcile_config.reread(StagingRefresh::Reuse)?;
         }
         let results = self.reconcile_state.reconcile()?;
 
@@ -176,12 +338,38 @@ impl AppStateInner {
         let mut staging_items = BTreeMap::new();
         let mut id_gen = UniqueIdGenerator::new();
 
+        let mut fuzzy_count = 0;
         for item in &results {
-            if let ReconcileItemKind::OnlyInStaging(directive) = item.item {
-                let unique_id = id_gen.generate_id(directive);
-                staging_items.insert(unique_id, (*directive).clone());
+            match item.item {
+                ReconcileItemKind::OnlyInStaging(directive) => {
+                    let unique_id = id_gen.generate_id(directive);
+                    staging_items.insert(unique_id, (*directive).clone());
+                }
+                // Both sides of a transfer are reviewed and committed as one transaction
+                ReconcileItemKind::Transfer { outgoing, incoming } => {
+                    match beancount_staging::reconcile::merge_transfer(outgoing, incoming) {
+                        Ok(merged) => {
+                            staging_items.insert(id_gen.generate_id(&merged), merged);
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                "Failed to merge transfer, reviewing sides separately: {e}"
+                            );
+                            for directive in [outgoing, incoming] {
+                                staging_items
+                                    .insert(id_gen.generate_id(directive), directive.clone());
+                            }
+                        }
+                    }
+                }
+                // Fuzzy matches count as matched, `diff` lists them for confirmation
+                ReconcileItemKind::FuzzyMatch { .. } => fuzzy_count += 1,
+                ReconcileItemKind::OnlyInJournal(_) | ReconcileItemKind::CombinedMatch { .. } => {}
             }
         }
+        if fuzzy_count > 0 {
+            tracing::info!("{} staging item(s) matched fuzzily", fuzzy_count);
+        }
 
         self.staging_items = staging_items;
 
@@ -189,8 +377,8 @@ impl AppStateInner {
         self.available_accounts = self.reconcile_state.accounts();
 
         // Note: We don't retrain the predictor on every reload since it's expensive
-        // and the journal changes frequently (on every commit). The predictor is only
-        // trained once at startup and can use slightly stale data.
+        // and the journal changes frequently (on every commit). Commits learn right away
+        // and request a debounced retraining in the background instead.
 
         Ok(())
     }
@@ -198,14 +386,17 @@ impl AppStateInner {
     /// For each `OnlyInStaging` item, auto-commit it if either
     /// (a) a user-configured rule matches, or
     /// (b) the transaction is non-`!`-flagged and already balanced.
+    /// All decided items are written in a single batch, so either every one of
+    /// them lands in the journal or none does.
     /// Logs a summary and returns the number of successful commits.
     fn auto_commit_staging(
         &self,
         results: &[beancount_staging::reconcile::ReconcileItem<'_>],
     ) -> usize {
-        let Some(journal_path) = self.reconcile_config.journal_paths.first() else {
+        if self.reconcile_config.journal_paths.is_empty() {
             return 0;
-        };
+        }
+        let mut batch = Vec::new();
         let mut committed_lines: Vec<String> = Vec::new();
         for item in results {
             let ReconcileItemKind::OnlyInStaging(directive) = item.item else {
@@ -244,29 +435,39 @@ impl AppStateInner {
                 }
                 _ => continue,
             };
-            if let Err(e) = beancount_staging::commit_transaction(
+            batch.push((
                 directive,
-                decision.target_account(),
-                None,
-                None,
-                beancount_staging::SourceMetaTarget::Transaction,
-                journal_path,
-            ) {
-                tracing::error!("Failed to auto-commit directive ({:?}): {}", label, e);
-            } else {
-                committed_lines.push(format!(
-                    "{} {:?} ({}) -> {}",
-                    directive.date, label, amount, target_desc,
-                ));
-            }
+                beancount_staging::CommitOverrides {
+                    expense_account: decision.target_account(),
+                    ..Default::default()
+                },
+            ));
+            committed_lines.push(format!(
+                "{} {:?} ({}) -> {}",
+                directive.date, label, amount, target_desc,
+            ));
+        }
+        if batch.is_empty() {
+            return 0;
         }
-        if !committed_lines.is_empty() {
-            tracing::info!(
-                "Auto-categorized {} transactions:\n  {}",
-                committed_lines.len(),
-                committed_lines.join("\n  "),
+
+        // All-or-nothing: a single bad entry must not leave the journal half-written.
+        if let Err(e) = self
+            .reconcile_config
+            .commit_transactions(&batch, beancount_staging::SourceMetaTarget::Transaction)
+        {
+            tracing::error!(
+                "Failed to auto-commit {} transactions, journal left unchanged: {}",
+                batch.len(),
+                e
             );
+            return 0;
         }
+        tracing::info!(
+            "Auto-categorized {} transactions:\n  {}",
+            committed_lines.len(),
+            committed_lines.join("\n  "),
+        );
         committed_lines.len()
     }
 
@@ -288,33 +489,181 @@ impl AppStateInner {
         }
     }
 
-    pub fn retrain(&mut self) -> anyhow::Result<()> {
-        self.predictor = train_predictor(&self.reconcile_state);
-        Ok(())
+    /// Number of commits learned from in this session, to tell which ones a retrained
+    /// predictor includes.
+    pub fn learned_count(&self) -> usize {
+        self.learned_count
     }
 
-    pub fn predict(&self, directive: &Directive) -> Option<Account> {
-        let Some(predictor) = &self.predictor else {
-            return None;
+    /// Replace the predictor by one retrained on a journal with the first `learned_count`
+    /// commits of this session.
+    pub fn swap_predictor(
+        &mut self,
+        predictor: Option<DecisionTreePredictor<Alpha>>,
+        split_templates: SplitTemplates,
+        learned_count: usize,
+    ) {
+        self.predictor = predictor;
+        self.split_templates = split_templates;
+        self.learned_commits
+            .retain(|commit| commit.seq >= learned_count);
+        self.relearn();
+    }
+
+    /// Move staging item `id` to the undo history after it was committed to `journal_path`
+    /// with `overrides`. Returns the key to undo the commit with.
+    pub fn record_commit(
+        &mut self,
+        id: &str,
+        overrides: &CommitOverrides<'_>,
+        journal_path: PathBuf,
+    ) -> Option<String> {
+        let directive = self.staging_items.remove(id)?;
+        let key = UniqueIdGenerator::generate_directive_id(&directive);
+        self.learn_commit(&key, &directive, overrides);
+        self.committed_items
+            .entry(key.clone())
+            .or_default()
+            .push(CommittedItem {
+                directive,
+                journal_path,
+            });
+        Some(key)
+    }
+
+    /// The most recent commit recorded under undo `key`.
+    pub fn last_commit(&self, key: &str) -> Option<&CommittedItem> {
+        self.committed_items.get(key)?.last()
+    }
+
+    /// Put the most recent commit recorded under undo `key` back into staging after it was
+    /// undone.
+    pub fn restore_commit(&mut self, key: &str) {
+        let Some(items) = self.committed_items.get_mut(key) else {
+            return;
         };
+        let item = items.pop();
+        if items.is_empty() {
+            self.committed_items.remove(key);
+        }
+        let Some(CommittedItem { directive, .. }) = item else {
+            return;
+        };
+        self.forget_commit(key);
+
+        // Duplicates still in staging keep their ids
+        let mut counter = 1;
+        let mut id = key.to_string();
+        while self.staging_items.contains_key(&id) {
+            counter += 1;
+            id = format!("{key}-{counter}");
+        }
+        self.staging_items.insert(id, directive);
+    }
+
+    /// Learn from committing `directive` with `overrides`, without waiting for the
+    /// predictor to be retrained.
+    fn learn_commit(&mut self, key: &str, directive: &Directive, overrides: &CommitOverrides<'_>) {
+        let Some(example) = commit_example(directive, overrides) else {
+            return;
+        };
+        self.session_predictor.learn(&example);
+        self.learned_commits.push(LearnedCommit {
+            id: key.to_string(),
+            seq: self.learned_count,
+            example,
+        });
+        self.learned_count += 1;
+    }
+
+    /// Forget the most recent commit learned under undo `key` after it was undone.
+    fn forget_commit(&mut self, key: &str) {
+        if let Some(index) = self
+            .learned_commits
+            .iter()
+            .rposition(|commit| commit.id == key)
+        {
+            self.learned_commits.remove(index);
+            self.relearn();
+        }
+    }
+
+    fn relearn(&mut self) {
+        let examples: Vec<TrainingExample> = self
+            .learned_commits
+            .iter()
+            .map(|commit| commit.example.clone())
+            .collect();
+        self.session_predictor = PayeeFrequencyPredictor::train(&examples);
+    }
+
+    /// Up to `limit` target accounts for `directive`, most likely first: the default account
+    /// of its staging source if configured, otherwise learned from the journal.
+    pub fn predict(&self, directive: &Directive, limit: usize) -> Vec<RankedPrediction> {
+        if let Some(account) = self.reconcile_config.default_account(directive) {
+            return account
+                .parse::<Account>()
+                .ok()
+                .map(|account| RankedPrediction {
+                    account,
+                    probability: 1.0,
+                })
+                .into_iter()
+                .collect();
+        }
 
         let DirectiveContent::Transaction(txn) = &directive.content else {
-            return None;
+            return Vec::new();
         };
         // TODO: handle source account in second posting?
-        let source_account = txn
-            .postings
-            .first()
+        let source_posting = txn.postings.first();
+        let source_account = source_posting
             .map(|p| p.account.clone())
             .unwrap_or_else(|| "Assets:Unknown".parse().unwrap());
+        let source_amount = source_posting.and_then(|p| p.amount.as_ref());
 
         let input = PredictionInput {
             source_account,
             payee: txn.payee.clone(),
             narration: txn.narration.clone().unwrap_or_default(),
+            amount: source_amount.map(|amount| amount.value),
+            currency: source_amount.map(|amount| amount.currency.to_string()),
+            date: Some(directive.date),
+        };
+
+        // Commits of this session are newer than what the predictor was trained on
+        if let Some(payee) = &input.payee
+            && self.session_predictor.knows_payee(payee)
+        {
+            return self.session_predictor.predict_ranked(&input, limit);
+        }
+
+        let Some(predictor) = &self.predictor else {
+            return Vec::new();
+        };
+        predictor.predict_ranked(&input, limit)
+    }
+
+    /// The legs for splitting `directive` across several accounts, if its payee is always
+    /// split the same way.
+    pub fn suggest_split(&self, directive: &Directive) -> Vec<SplitLeg> {
+        if self.reconcile_config.default_account(directive).is_some() {
+            return Vec::new();
+        }
+        let DirectiveContent::Transaction(txn) = &directive.content else {
+            return Vec::new();
+        };
+        // The legs balance the single staging posting
+        let ([posting], Some(payee)) = (txn.postings.as_slice(), &txn.payee) else {
+            return Vec::new();
+        };
+        let Some(amount) = &posting.amount else {
+            return Vec::new();
         };
 
-        predictor.predict(&input)
+        self.split_templates
+            .suggest(payee, -amount.value)
+            .unwrap_or_default()
     }
 }
 
@@ -329,28 +678,72 @@ impl AppState {
     }
 
     pub fn new(
-        journal_paths: Vec<PathBuf>,
-        staging_source: StagingSource,
+        reconcile_config: ReconcileConfig,
         auto_rules: Vec<AutoCategorizeRule>,
         file_change_tx: broadcast::Sender<FileChangeEvent>,
     ) -> anyhow::Result<Self> {
-        let mut state = AppStateInner::new(journal_paths, staging_source, auto_rules);
-        state.reload()?;
+        let staging_watch = reconcile_config.staging_watch()?;
+        let mut state = AppStateInner::new(reconcile_config, auto_rules, staging_watch);
+        state.reload(&[])?;
 
         Ok(Self {
             inner: Arc::new(Mutex::new(state)),
             file_change_tx,
-            _watcher: None,
+            retrain_requested: Arc::new(Notify::new()),
         })
     }
 
-    pub fn set_watcher(&mut self, watcher: FileWatcher) {
-        self._watcher = Some(Arc::new(watcher));
+    pub fn reload(&self, changed: &[PathBuf]) -> anyhow::Result<bool> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.reload(changed)
     }
 
-    pub fn reload(&self) -> anyhow::Result<()> {
-        let mut inner = self.inner.lock().unwrap();
-        inner.reload()
+    /// Run the staging command again, even if its inputs didn't change, and notify clients.
+    ///
+    /// Returns the number of staging items left to review.
+    pub fn refresh_staging(&self) -> anyhow::Result<usize> {
+        let remaining_count = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.reload_with(StagingRefresh::Force)?;
+            inner.staging_items.len()
+        };
+        self.notify_clients();
+        Ok(remaining_count)
+    }
+
+    /// Run the command of the staging source at `index` again, reusing the output of the
+    /// others, and notify clients.
+    pub fn refresh_staging_source(&self, index: usize) -> anyhow::Result<()> {
+        self.inner.lock().unwrap().reload_sources(|i| {
+            if i == index {
+                StagingRefresh::Force
+            } else {
+                StagingRefresh::Reuse
+            }
+        })?;
+        self.notify_clients();
+        Ok(())
+    }
+
+    /// Retrain the predictor in the background once no commits happened for a while.
+    pub fn request_retrain(&self) {
+        self.retrain_requested.notify_one();
+    }
+
+    /// Tell connected clients to fetch the staging items again.
+    pub fn notify_clients(&self) {
+        let subscriber_count = self.file_change_tx.receiver_count();
+        match self.file_change_tx.send(FileChangeEvent) {
+            Ok(_) => {
+                tracing::info!(
+                    "Sent file change event to {} SSE clients",
+                    subscriber_count - 1
+                );
+            }
+            Err(e) => {
+                tracing::error!("Failed to send SSE event: {}", e);
+            }
+        }
     }
 }
 
@@ -368,6 +761,62 @@ mod tests {
         parsed.directives.into_iter().next().unwrap()
     }
 
+    #[test]
+    fn commit_example_uses_commit_date() {
+        let txn = make_transaction("2024-01-01", "Store", "Purchase", "-10.00");
+        let example = commit_example(
+            &txn,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                date: Some("2024-01-05"),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            example.date.map(|date| date.to_string()).as_deref(),
+            Some("2024-01-05")
+        );
+    }
+
+    #[test]
+    fn undo_survives_renumbered_duplicates() {
+        let mut inner = AppStateInner::new(
+            ReconcileConfig::new(
+                Vec::new(),
+                beancount_staging::reconcile::StagingSource::Files(Vec::new()),
+            ),
+            Vec::new(),
+            Vec::new(),
+        );
+        let txn = make_transaction("2024-01-01", "Store", "Purchase", "-10.00");
+        let mut id_gen = UniqueIdGenerator::new();
+        let first = id_gen.generate_id(&txn);
+        let second = id_gen.generate_id(&txn);
+        inner.staging_items.insert(first.clone(), txn.clone());
+        inner.staging_items.insert(second.clone(), txn.clone());
+
+        let undo_id = inner
+            .record_commit(
+                &second,
+                &CommitOverrides::default(),
+                PathBuf::from("journal.beancount"),
+            )
+            .unwrap();
+        assert_eq!(undo_id, first);
+
+        // A reload numbers the remaining duplicate without a counter
+        inner.staging_items = BTreeMap::from([(first.clone(), txn.clone())]);
+
+        assert!(inner.last_commit(&undo_id).is_some());
+        inner.restore_commit(&undo_id);
+        assert!(inner.last_commit(&undo_id).is_none());
+        assert_eq!(
+            inner.staging_items.keys().collect::<Vec<_>>(),
+            [&first, &second]
+        );
+    }
+
     #[test]
     fn unique_id_generator_no_collisions() {
         let mut id_gen = UniqueIdGenerator::new();
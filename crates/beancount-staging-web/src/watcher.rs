@@ -1,20 +1,30 @@
 use anyhow::Result;
 use notify::{EventKind, RecursiveMode};
 use notify_debouncer_full::{Debouncer, RecommendedCache, new_debouncer};
-use std::path::Path;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tracing::{error, info};
 
 pub struct FileWatcher {
-    _debouncer: Debouncer<notify::RecommendedWatcher, RecommendedCache>,
+    debouncer: Debouncer<notify::RecommendedWatcher, RecommendedCache>,
+    /// Paths watched non-recursively
+    watched: BTreeSet<PathBuf>,
 }
 
 impl FileWatcher {
-    pub fn new<'a, F>(paths: impl Iterator<Item = &'a Path>, on_change: F) -> Result<Self>
+    /// Watch `paths`, and everything below `recursive_paths`.
+    ///
+    /// `on_change` is called with the changed files.
+    pub fn new<'a, F>(
+        paths: impl Iterator<Item = &'a Path>,
+        recursive_paths: impl Iterator<Item = &'a Path>,
+        on_change: F,
+    ) -> Result<Self>
     where
-        F: Fn() + Send + 'static,
+        F: Fn(&[PathBuf]) + Send + 'static,
     {
-        let mut debouncer = new_debouncer(
+        let debouncer = new_debouncer(
             Duration::from_millis(100),
             None,
             move |res: Result<Vec<notify_debouncer_full::DebouncedEvent>, _>| {
@@ -36,25 +46,50 @@ impl FileWatcher {
                 if !events.is_empty() {
                     info!("File modification detected: {} events", events.len());
 
-                    on_change();
+                    let mut changed: Vec<PathBuf> = events
+                        .iter()
+                        .flat_map(|e| e.event.paths.iter().cloned())
+                        .collect();
+                    changed.sort();
+                    changed.dedup();
+                    on_change(&changed);
                 }
             },
         )?;
 
-        // Watch all provided paths
+        let mut watcher = Self {
+            debouncer,
+            watched: BTreeSet::new(),
+        };
         for path in paths {
-            let path_display = (|| {
-                let cwd = std::env::current_dir().ok()?;
-                let base = path.strip_prefix(&cwd).ok()?;
-                Some(base)
-            })()
-            .unwrap_or(path);
-            info!("Watching path: {}", path_display.display());
-            debouncer.watch(path, RecursiveMode::NonRecursive)?;
+            watcher.watch(path)?;
         }
+        for path in recursive_paths {
+            watcher.watch_path(path, RecursiveMode::Recursive)?;
+        }
+
+        Ok(watcher)
+    }
+
+    /// Also watch `path`, unless it is watched already.
+    pub fn watch(&mut self, path: &Path) -> Result<()> {
+        if self.watched.contains(path) {
+            return Ok(());
+        }
+        self.watch_path(path, RecursiveMode::NonRecursive)?;
+        self.watched.insert(path.to_owned());
+        Ok(())
+    }
 
-        Ok(Self {
-            _debouncer: debouncer,
-        })
+    fn watch_path(&mut self, path: &Path, mode: RecursiveMode) -> Result<()> {
+        let path_display = (|| {
+            let cwd = std::env::current_dir().ok()?;
+            let base = path.strip_prefix(&cwd).ok()?;
+            Some(base)
+        })()
+        .unwrap_or(path);
+        info!("Watching path: {}", path_display.display());
+        self.debouncer.watch(path, mode)?;
+        Ok(())
     }
 }
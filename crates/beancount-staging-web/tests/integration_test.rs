@@ -1,5 +1,5 @@
 use beancount_staging::AutoCategorizeRule;
-use beancount_staging::reconcile::StagingSource;
+use beancount_staging::reconcile::{ReconcileConfig, StagingSource, StagingSourceConfig};
 use beancount_staging_web::ListenerType;
 
 #[tokio::test]
@@ -47,8 +47,7 @@ async fn test_api_workflow() {
     // technically this can race but it seems fast enough for now
     tokio::spawn(async move {
         beancount_staging_web::run(
-            journal,
-            StagingSource::Files(staging),
+            ReconcileConfig::new(journal, StagingSource::Files(staging)),
             Vec::new(),
             ListenerType::Tcp(8081),
         )
@@ -217,8 +216,7 @@ async fn test_file_watching_detects_changes() {
     // Start the server
     tokio::spawn(async move {
         beancount_staging_web::run(
-            journal,
-            StagingSource::Files(staging),
+            ReconcileConfig::new(journal, StagingSource::Files(staging)),
             Vec::new(),
             ListenerType::Tcp(8082),
         )
@@ -368,8 +366,7 @@ async fn test_auto_categorize_hides_matching_transaction() {
 
     tokio::spawn(async move {
         beancount_staging_web::run(
-            journal,
-            StagingSource::Files(staging),
+            ReconcileConfig::new(journal, StagingSource::Files(staging)),
             rules,
             ListenerType::Tcp(8083),
         )
@@ -462,8 +459,7 @@ async fn test_auto_commit_balanced_starred_transaction() {
 
     tokio::spawn(async move {
         beancount_staging_web::run(
-            journal,
-            StagingSource::Files(staging),
+            ReconcileConfig::new(journal, StagingSource::Files(staging)),
             Vec::new(),
             ListenerType::Tcp(8084),
         )
@@ -553,8 +549,7 @@ async fn test_auto_commit_balance_directive() {
 
     tokio::spawn(async move {
         beancount_staging_web::run(
-            journal,
-            StagingSource::Files(staging),
+            ReconcileConfig::new(journal, StagingSource::Files(staging)),
             Vec::new(),
             ListenerType::Tcp(8085),
         )
@@ -587,3 +582,676 @@ async fn test_auto_commit_balance_directive() {
 
     let _ = std::fs::remove_dir_all(&temp_dir);
 }
+
+#[tokio::test]
+async fn test_uncommit_restores_staging_item() {
+    let temp_dir =
+        std::env::temp_dir().join(format!("beancount-uncommit-test-{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    let journal_path = temp_dir.join("journal.beancount");
+    let staging_path = temp_dir.join("staging.beancount");
+
+    let journal_contents = r#"
+2024-01-01 open Assets:Checking
+2024-01-01 open Expenses:Groceries
+"#;
+    std::fs::write(&journal_path, journal_contents).unwrap();
+    std::fs::write(
+        &staging_path,
+        r#"
+2024-01-20 * "Shop" "Groceries"
+    Assets:Checking  -25.00 USD
+"#,
+    )
+    .unwrap();
+
+    let journal = vec![journal_path.clone()];
+    let staging = vec![staging_path];
+
+    tokio::spawn(async move {
+        beancount_staging_web::run(
+            ReconcileConfig::new(journal, StagingSource::Files(staging)),
+            Vec::new(),
+            ListenerType::Tcp(8086),
+        )
+        .await
+        .ok();
+    });
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let base = "http://localhost:8086";
+
+    let init: serde_json::Value = client
+        .get(format!("{}/api/init", base))
+        .send()
+        .await
+        .expect("init request failed")
+        .json()
+        .await
+        .expect("init json parse failed");
+    let id = init["items"][0]["id"].as_str().unwrap().to_string();
+
+    // Uncommitting something that was never committed is not found
+    let not_committed = client
+        .post(format!("{}/api/transaction/{}/uncommit", base, id))
+        .send()
+        .await
+        .expect("uncommit request failed");
+    assert_eq!(not_committed.status().as_u16(), 404);
+
+    let commit_response: serde_json::Value = client
+        .post(format!("{}/api/transaction/{}/commit", base, id))
+        .json(&serde_json::json!({ "account": "Expenses:Groceries" }))
+        .send()
+        .await
+        .expect("commit request failed")
+        .json()
+        .await
+        .expect("commit json parse failed");
+    assert_eq!(commit_response["remaining_count"], 0);
+    assert_ne!(
+        std::fs::read_to_string(&journal_path).unwrap(),
+        journal_contents
+    );
+
+    let uncommit_response: serde_json::Value = client
+        .post(format!("{}/api/transaction/{}/uncommit", base, id))
+        .send()
+        .await
+        .expect("uncommit request failed")
+        .json()
+        .await
+        .expect("uncommit json parse failed");
+    assert_eq!(uncommit_response["ok"], true);
+    assert_eq!(uncommit_response["remaining_count"], 1);
+    assert_eq!(
+        std::fs::read_to_string(&journal_path).unwrap(),
+        journal_contents
+    );
+
+    // Give the watcher time to pick up the journal change and reload
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let init2: serde_json::Value = client
+        .get(format!("{}/api/init", base))
+        .send()
+        .await
+        .expect("init request failed")
+        .json()
+        .await
+        .expect("init json parse failed");
+    let items2 = init2["items"].as_array().unwrap();
+    assert_eq!(
+        items2.len(),
+        1,
+        "uncommitted item should be back in staging"
+    );
+    assert_eq!(items2[0]["id"], id.as_str());
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+#[tokio::test]
+async fn test_other_directive_types_are_served() {
+    let temp_dir =
+        std::env::temp_dir().join(format!("beancount-other-directive-{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    let journal_path = temp_dir.join("journal.beancount");
+    let staging_path = temp_dir.join("staging.beancount");
+
+    std::fs::write(
+        &journal_path,
+        r#"
+2024-01-01 open Assets:Checking
+"#,
+    )
+    .unwrap();
+    // Directive types without a dedicated serialization used to crash the server
+    std::fs::write(
+        &staging_path,
+        r#"
+2024-02-01 event "location" "Berlin"
+"#,
+    )
+    .unwrap();
+
+    let journal = vec![journal_path];
+    let staging = vec![staging_path];
+
+    tokio::spawn(async move {
+        beancount_staging_web::run(
+            ReconcileConfig::new(journal, StagingSource::Files(staging)),
+            Vec::new(),
+            ListenerType::Tcp(8087),
+        )
+        .await
+        .ok();
+    });
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let init: serde_json::Value = client
+        .get("http://localhost:8087/api/init")
+        .send()
+        .await
+        .expect("init request failed")
+        .json()
+        .await
+        .expect("init json parse failed");
+
+    let items = init["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["type"], "other");
+    assert_eq!(items[0]["date"], "2024-02-01");
+    insta::assert_snapshot!(
+        items[0]["text"].as_str().unwrap(),
+        @r#"2024-02-01 event "location" "Berlin""#
+    );
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+#[tokio::test]
+async fn test_ignore_writes_ignored_file_and_hides_item() {
+    let temp_dir =
+        std::env::temp_dir().join(format!("beancount-ignore-test-{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    let journal_path = temp_dir.join("journal.beancount");
+    let staging_path = temp_dir.join("staging.beancount");
+    let ignored_path = temp_dir.join("ignored.beancount");
+    let _ = std::fs::remove_file(&ignored_path);
+
+    let journal_contents = r#"
+2024-01-01 open Assets:Checking
+"#;
+    std::fs::write(&journal_path, journal_contents).unwrap();
+    std::fs::write(
+        &staging_path,
+        r#"
+2024-01-20 ! "Card Hold" "Pre-authorization"
+    Assets:Checking  -80.00 USD
+
+2024-01-21 ! "Shop" "Groceries"
+    Assets:Checking  -25.00 USD
+"#,
+    )
+    .unwrap();
+
+    let mut config = ReconcileConfig::new(
+        vec![journal_path.clone()],
+        StagingSource::Files(vec![staging_path]),
+    );
+    config.ignored_file = Some(ignored_path.clone());
+
+    tokio::spawn(async move {
+        beancount_staging_web::run(config, Vec::new(), ListenerType::Tcp(8088))
+            .await
+            .ok();
+    });
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let base = "http://localhost:8088";
+
+    let init: serde_json::Value = client
+        .get(format!("{}/api/init", base))
+        .send()
+        .await
+        .expect("init request failed")
+        .json()
+        .await
+        .expect("init json parse failed");
+    let items = init["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    let hold = items
+        .iter()
+        .find(|item| item["payee"] == "Card Hold")
+        .expect("card hold is served");
+    let id = hold["id"].as_str().unwrap().to_string();
+
+    let ignore_response: serde_json::Value = client
+        .post(format!("{}/api/transaction/{}/ignore", base, id))
+        .send()
+        .await
+        .expect("ignore request failed")
+        .json()
+        .await
+        .expect("ignore json parse failed");
+    assert_eq!(ignore_response["remaining_count"], 1);
+
+    let ignored = std::fs::read_to_string(&ignored_path).unwrap();
+    assert!(ignored.contains("#ignored"), "{ignored}");
+    assert_eq!(
+        std::fs::read_to_string(&journal_path).unwrap(),
+        journal_contents
+    );
+
+    // A journal change triggers a reload, the ignored entry stays hidden
+    std::fs::write(&journal_path, format!("{journal_contents}\n")).unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let init: serde_json::Value = client
+        .get(format!("{}/api/init", base))
+        .send()
+        .await
+        .expect("init request failed")
+        .json()
+        .await
+        .expect("init json parse failed");
+    let items = init["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["payee"], "Shop");
+}
+
+#[tokio::test]
+async fn test_refresh_reruns_cached_staging_command() {
+    let temp_dir =
+        std::env::temp_dir().join(format!("beancount-refresh-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(temp_dir.join("data")).unwrap();
+
+    let journal_path = temp_dir.join("journal.beancount");
+    let import_path = temp_dir.join("import.beancount");
+    let journal_contents = r#"
+2024-01-01 open Assets:Checking
+"#;
+    std::fs::write(&journal_path, journal_contents).unwrap();
+    let first_import = r#"
+2024-01-20 ! "Shop" "Groceries"
+    Assets:Checking  -25.00 USD
+"#;
+    std::fs::write(&import_path, first_import).unwrap();
+
+    let config = ReconcileConfig::new(
+        vec![journal_path.clone()],
+        StagingSource::Command {
+            command: vec!["cat".to_string(), "import.beancount".to_string()],
+            cwd: temp_dir.clone(),
+            inputs: vec!["data".into()],
+            watch: Vec::new(),
+            env: Default::default(),
+        },
+    );
+
+    tokio::spawn(async move {
+        beancount_staging_web::run(config, Vec::new(), ListenerType::Tcp(8089))
+            .await
+            .ok();
+    });
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let base = "http://localhost:8089";
+    assert_eq!(item_count(&client, base).await, 1);
+    let cached = std::fs::read_dir(temp_dir.join(".beancount-staging"))
+        .unwrap()
+        .count();
+    assert_eq!(cached, 1);
+
+    // The importer would now print another transaction
+    std::fs::write(
+        &import_path,
+        format!(
+            "{first_import}\n2024-01-21 ! \"Bakery\" \"Bread\"\n    Assets:Checking  -3.00 USD\n"
+        ),
+    )
+    .unwrap();
+
+    // Journal changes reuse the last import
+    std::fs::write(&journal_path, format!("{journal_contents}\n")).unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    assert_eq!(item_count(&client, base).await, 1);
+
+    let refresh_response: serde_json::Value = client
+        .post(format!("{}/api/staging/refresh", base))
+        .send()
+        .await
+        .expect("refresh request failed")
+        .json()
+        .await
+        .expect("refresh json parse failed");
+    assert_eq!(refresh_response["remaining_count"], 2);
+    assert_eq!(item_count(&client, base).await, 2);
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+#[tokio::test]
+async fn test_watched_input_reruns_staging_command() {
+    let temp_dir =
+        std::env::temp_dir().join(format!("beancount-watch-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(temp_dir.join("data/bank")).unwrap();
+
+    let journal_path = temp_dir.join("journal.beancount");
+    let import_path = temp_dir.join("import.beancount");
+    std::fs::write(&journal_path, "2024-01-01 open Assets:Checking\n").unwrap();
+    let first_import = r#"
+2024-01-20 ! "Shop" "Groceries"
+    Assets:Checking  -25.00 USD
+"#;
+    std::fs::write(&import_path, first_import).unwrap();
+
+    let config = ReconcileConfig::new(
+        vec![journal_path.clone()],
+        StagingSource::Command {
+            command: vec!["cat".to_string(), "import.beancount".to_string()],
+            cwd: temp_dir.clone(),
+            inputs: Vec::new(),
+            watch: vec!["data/**/*.csv".to_string()],
+            env: Default::default(),
+        },
+    );
+
+    tokio::spawn(async move {
+        beancount_staging_web::run(config, Vec::new(), ListenerType::Tcp(8090))
+            .await
+            .ok();
+    });
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let base = "http://localhost:8090";
+    assert_eq!(item_count(&client, base).await, 1);
+
+    // The importer would now print another transaction
+    std::fs::write(
+        &import_path,
+        format!(
+            "{first_import}\n2024-01-21 ! \"Bakery\" \"Bread\"\n    Assets:Checking  -3.00 USD\n"
+        ),
+    )
+    .unwrap();
+
+    // Files which don't match the pattern are ignored
+    std::fs::write(temp_dir.join("data/bank/statement.pdf"), "pdf").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    assert_eq!(item_count(&client, base).await, 1);
+
+    // A new download reruns the command
+    std::fs::write(temp_dir.join("data/bank/statement.csv"), "csv").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    assert_eq!(item_count(&client, base).await, 2);
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+#[tokio::test]
+async fn test_named_staging_sources() {
+    let temp_dir =
+        std::env::temp_dir().join(format!("beancount-sources-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    let journal_path = temp_dir.join("journal.beancount");
+    let bank_path = temp_dir.join("bank.beancount");
+    let paypal_path = temp_dir.join("paypal.beancount");
+    std::fs::write(
+        &journal_path,
+        "2024-01-01 open Assets:Checking\n2024-01-01 open Assets:PayPal\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &bank_path,
+        "2024-01-20 ! \"Bakery\" \"Bread\"\n    Assets:Checking  -3.00 USD\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &paypal_path,
+        "2024-01-21 ! \"Shop\" \"Order\"\n    Assets:PayPal  -25.00 USD\n",
+    )
+    .unwrap();
+
+    let config = ReconcileConfig::with_sources(
+        vec![journal_path.clone()],
+        vec![
+            StagingSourceConfig {
+                name: Some("bank".to_string()),
+                source: StagingSource::Files(vec![bank_path]),
+                default_account: None,
+                refresh_interval: None,
+            },
+            StagingSourceConfig {
+                name: Some("paypal".to_string()),
+                source: StagingSource::Files(vec![paypal_path]),
+                default_account: Some("Expenses:Shopping".to_string()),
+                refresh_interval: None,
+            },
+        ],
+    );
+
+    tokio::spawn(async move {
+        beancount_staging_web::run(config, Vec::new(), ListenerType::Tcp(8091))
+            .await
+            .ok();
+    });
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let base = "http://localhost:8091";
+
+    let init: serde_json::Value = client
+        .get(format!("{}/api/init", base))
+        .send()
+        .await
+        .expect("init request failed")
+        .json()
+        .await
+        .expect("init json parse failed");
+    assert_eq!(init["sources"], serde_json::json!(["bank", "paypal"]));
+    let items = init["items"].as_array().unwrap();
+    let sources: Vec<_> = items.iter().map(|item| &item["source"]).collect();
+    assert_eq!(sources, ["bank", "paypal"]);
+
+    let id = items[1]["id"].as_str().unwrap();
+    let txn: serde_json::Value = client
+        .get(format!("{}/api/transaction/{}", base, id))
+        .send()
+        .await
+        .expect("transaction request failed")
+        .json()
+        .await
+        .expect("transaction json parse failed");
+    assert_eq!(txn["predicted_account"], "Expenses:Shopping");
+    assert_eq!(
+        txn["predictions"],
+        serde_json::json!([{ "account": "Expenses:Shopping", "probability": 1.0 }])
+    );
+
+    let response = client
+        .post(format!("{}/api/transaction/{}/commit", base, id))
+        .json(&serde_json::json!({ "account": "Expenses:Shopping" }))
+        .send()
+        .await
+        .expect("commit request failed");
+    assert!(response.status().is_success());
+
+    // The source is only known while reviewing
+    let journal = std::fs::read_to_string(&journal_path).unwrap();
+    assert!(journal.contains("Expenses:Shopping"), "{journal}");
+    assert!(!journal.contains("staging_source"), "{journal}");
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+#[tokio::test]
+async fn test_commits_learned_immediately() {
+    let temp_dir =
+        std::env::temp_dir().join(format!("beancount-learn-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    let journal_path = temp_dir.join("journal.beancount");
+    let staging_path = temp_dir.join("staging.beancount");
+    std::fs::write(&journal_path, "2024-01-01 open Assets:Checking\n").unwrap();
+    std::fs::write(
+        &staging_path,
+        r#"
+2024-01-20 ! "Bakery" "Bread"
+    Assets:Checking  -3.00 USD
+
+2024-01-27 ! "Bakery" "Rolls"
+    Assets:Checking  -2.50 USD
+"#,
+    )
+    .unwrap();
+
+    let config = ReconcileConfig::new(vec![journal_path], StagingSource::Files(vec![staging_path]));
+    tokio::spawn(async move {
+        beancount_staging_web::run(config, Vec::new(), ListenerType::Tcp(8092))
+            .await
+            .ok();
+    });
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let base = "http://localhost:8092";
+
+    let init: serde_json::Value = client
+        .get(format!("{}/api/init", base))
+        .send()
+        .await
+        .expect("init request failed")
+        .json()
+        .await
+        .expect("init json parse failed");
+    let items = init["items"].as_array().unwrap();
+    let first = items[0]["id"].as_str().unwrap().to_string();
+    let second = items[1]["id"].as_str().unwrap().to_string();
+
+    let predictions = |id: String| {
+        let client = client.clone();
+        async move {
+            let txn: serde_json::Value = client
+                .get(format!("{}/api/transaction/{}", base, id))
+                .send()
+                .await
+                .expect("transaction request failed")
+                .json()
+                .await
+                .expect("transaction json parse failed");
+            txn["predictions"].clone()
+        }
+    };
+    // Too few journal entries to train a predictor
+    assert_eq!(predictions(second.clone()).await, serde_json::json!([]));
+
+    let response = client
+        .post(format!("{}/api/transaction/{}/commit", base, first))
+        .json(&serde_json::json!({ "account": "Expenses:Food" }))
+        .send()
+        .await
+        .expect("commit request failed");
+    assert!(response.status().is_success());
+    assert_eq!(
+        predictions(second.clone()).await,
+        serde_json::json!([{ "account": "Expenses:Food", "probability": 1.0 }])
+    );
+
+    let response = client
+        .post(format!("{}/api/transaction/{}/uncommit", base, first))
+        .send()
+        .await
+        .expect("uncommit request failed");
+    assert!(response.status().is_success());
+    assert_eq!(predictions(second).await, serde_json::json!([]));
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+#[tokio::test]
+async fn test_split_template_suggested() {
+    let temp_dir =
+        std::env::temp_dir().join(format!("beancount-split-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    let journal_path = temp_dir.join("journal.beancount");
+    let staging_path = temp_dir.join("staging.beancount");
+    std::fs::write(
+        &journal_path,
+        r#"
+2024-01-05 * "Telco" "Phone bill"
+    Assets:Checking  -40.00 EUR
+    Expenses:Phone:Contract  30.00 EUR
+    Expenses:Phone:Device
+
+2024-02-05 * "Telco" "Phone bill"
+    Assets:Checking  -42.00 EUR
+    Expenses:Phone:Contract  31.50 EUR
+    Expenses:Phone:Device  10.50 EUR
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        &staging_path,
+        r#"
+2024-03-05 ! "Telco" "Phone bill"
+    Assets:Checking  -50.00 EUR
+"#,
+    )
+    .unwrap();
+
+    let config = ReconcileConfig::new(vec![journal_path], StagingSource::Files(vec![staging_path]));
+    tokio::spawn(async move {
+        beancount_staging_web::run(config, Vec::new(), ListenerType::Tcp(8093))
+            .await
+            .ok();
+    });
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let base = "http://localhost:8093";
+
+    let init: serde_json::Value = client
+        .get(format!("{}/api/init", base))
+        .send()
+        .await
+        .expect("init request failed")
+        .json()
+        .await
+        .expect("init json parse failed");
+    let id = init["items"][0]["id"].as_str().unwrap();
+
+    let txn: serde_json::Value = client
+        .get(format!("{}/api/transaction/{}", base, id))
+        .send()
+        .await
+        .expect("transaction request failed")
+        .json()
+        .await
+        .expect("transaction json parse failed");
+    assert_eq!(
+        txn["split_template"],
+        serde_json::json!([
+            { "account": "Expenses:Phone:Contract", "amount": "37.50" },
+            { "account": "Expenses:Phone:Device", "amount": "12.50" },
+        ])
+    );
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+}
+
+async fn item_count(client: &reqwest::Client, base: &str) -> usize {
+    let init: serde_json::Value = client
+        .get(format!("{}/api/init", base))
+        .send()
+        .await
+        .expect("init request failed")
+        .json()
+        .await
+        .expect("init json parse failed");
+    init["items"].as_array().unwrap().len()
+}
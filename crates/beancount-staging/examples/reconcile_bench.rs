@@ -0,0 +1,109 @@
+//! Time reconciliation of a synthetic journal against a full re-import of its history.
+//!
+//! Usage: `reconcile_bench [JOURNAL_TRANSACTIONS] [NEW_STAGING_TRANSACTIONS]`
+
+use anyhow::{Context, Result};
+use beancount_staging::reconcile::{ReconcileItemKind, ReconcileState};
+use beancount_staging::{Decimal, Directive, Entry};
+use std::fmt::Write;
+use std::time::Instant;
+
+const YEARS: usize = 15;
+const PAYEES: &[&str] = &[
+    "Supermarket",
+    "Bakery",
+    "Coffee Shop",
+    "Railway",
+    "Pharmacy",
+    "Bookstore",
+    "Streaming",
+    "Gym",
+];
+const ACCOUNTS: &[&str] = &["Assets:Checking", "Assets:Card", "Liabilities:CreditCard"];
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let journal_count: usize = match args.next() {
+        Some(count) => count.parse().context("invalid journal transaction count")?,
+        None => 100_000,
+    };
+    let new_count: usize = match args.next() {
+        Some(count) => count.parse().context("invalid staging transaction count")?,
+        None => 500,
+    };
+
+    let start = Instant::now();
+    let total = journal_count + new_count;
+    let mut journal = String::new();
+    let mut staging = String::new();
+    for i in 0..total {
+        let txn = synthetic_transaction(i, total);
+        // The newest entries are not in the journal yet
+        if i < journal_count {
+            writeln!(journal, "{}  Expenses:Misc\n", txn)?;
+        }
+        writeln!(staging, "{}", txn)?;
+    }
+    let journal = parse(&journal)?;
+    let staging = parse(&staging)?;
+    println!(
+        "generated {} journal and {} staging directives in {:?}",
+        journal.len(),
+        staging.len(),
+        start.elapsed()
+    );
+
+    let state = ReconcileState {
+        journal,
+        staging,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let results = state.reconcile()?;
+    let elapsed = start.elapsed();
+
+    let only_staging = results
+        .iter()
+        .filter(|item| matches!(item.item, ReconcileItemKind::OnlyInStaging(_)))
+        .count();
+    println!(
+        "reconciled in {:?}: {} results, {} only in staging",
+        elapsed,
+        results.len(),
+        only_staging
+    );
+
+    Ok(())
+}
+
+/// The `i`th of `total` transactions, spread evenly over [`YEARS`] years.
+///
+/// Only the primary posting is rendered, like an importer would emit it.
+fn synthetic_transaction(i: usize, total: usize) -> String {
+    // Simple LCG, so runs are reproducible without a rand dependency
+    let random = (i as u64)
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407)
+        >> 33;
+
+    // Months of 28 days keep every date valid
+    let day = i * (YEARS * 12 * 28) / total;
+    let (year, month, day) = (2010 + day / (12 * 28), day / 28 % 12 + 1, day % 28 + 1);
+
+    let payee = PAYEES[random as usize % PAYEES.len()];
+    let account = ACCOUNTS[(random >> 8) as usize % ACCOUNTS.len()];
+    let amount = Decimal::new(-((random % 20_000) as i64 + 1), 2);
+    format!(
+        "{year}-{month:02}-{day:02} * \"{payee}\" \"Purchase {i}\"\n  {account}  {amount} EUR\n"
+    )
+}
+
+fn parse(source: &str) -> Result<Vec<Directive>> {
+    let mut directives = Vec::new();
+    for entry in beancount_parser::parse_iter::<Decimal>(source) {
+        if let Entry::Directive(directive) = entry.context("Failed to parse synthetic journal")? {
+            directives.push(directive);
+        }
+    }
+    Ok(directives)
+}
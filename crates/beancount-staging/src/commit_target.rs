@@ -0,0 +1,466 @@
+//! Choosing the journal file a staging directive is committed to.
+
+use crate::reconcile::SourceSet;
+use crate::utils::journal_file;
+use crate::{CommitMode, CommitOverrides, Directive, DirectiveContent, Result, SourceMetaTarget};
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A rule for routing committed directives into a different journal file.
+///
+/// A rule matches when the account of the directive's first posting (or the account of a
+/// balance directive) matches `match_source_account`, a glob pattern in which `*` matches
+/// any sequence of characters.
+///
+/// The `file` may contain `{year}` and `{month}` placeholders, which are filled in from the
+/// date of the directive.
+#[derive(Debug, Clone)]
+pub struct CommitTargetRule {
+    pub match_source_account: regex::Regex,
+    pub file: PathBuf,
+}
+
+impl CommitTargetRule {
+    pub fn new(match_source_account: &str, file: PathBuf) -> Result<Self> {
+        let pattern = match_source_account
+            .split('*')
+            .map(regex::escape)
+            .collect::<Vec<_>>()
+            .join(".*");
+        let match_source_account = regex::Regex::new(&format!("^{pattern}$"))
+            .with_context(|| format!("Invalid account pattern: {:?}", match_source_account))?;
+
+        Ok(CommitTargetRule {
+            match_source_account,
+            file,
+        })
+    }
+
+    pub fn matches(&self, directive: &Directive) -> bool {
+        let account = match &directive.content {
+            DirectiveContent::Transaction(txn) => txn.postings.first().map(|p| &p.account),
+            DirectiveContent::Balance(bal) => Some(&bal.account),
+            _ => None,
+        };
+        account.is_some_and(|account| self.match_source_account.is_match(account.as_str()))
+    }
+
+    /// The target file for `directive`, with the date placeholders filled in.
+    pub fn file_for(&self, directive: &Directive) -> PathBuf {
+        let date = directive.date.to_string();
+        let file = self
+            .file
+            .to_string_lossy()
+            .replace("{year}", &date[0..4])
+            .replace("{month}", &date[5..7]);
+        PathBuf::from(file)
+    }
+}
+
+/// Resolve the file `directive` should be committed to: the file of the first matching
+/// rule, or `main_journal` if no rule matches.
+pub fn resolve_commit_target(
+    directive: &Directive,
+    rules: &[CommitTargetRule],
+    main_journal: &Path,
+) -> PathBuf {
+    rules
+        .iter()
+        .find(|rule| rule.matches(directive))
+        .map(|rule| rule.file_for(directive))
+        .unwrap_or_else(|| main_journal.to_owned())
+}
+
+/// Resolve the file `directive` is committed to once `overrides` are applied, so that a
+/// changed date picks the file of its new month.
+pub fn resolve_committed_target(
+    directive: &Directive,
+    overrides: &CommitOverrides<'_>,
+    rules: &[CommitTargetRule],
+    main_journal: &Path,
+) -> Result<PathBuf> {
+    let committed =
+        crate::apply_commit_overrides(directive, overrides, SourceMetaTarget::Transaction)?;
+    Ok(resolve_commit_target(&committed, rules, main_journal))
+}
+
+/// `path` with symlinks and `.`/`..` resolved as far as it exists, to tell whether two
+/// paths name the same file even before it is created.
+fn file_identity(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            file_identity(parent).join(name)
+        }
+        _ => std::path::absolute(path).unwrap_or_else(|_| path.to_owned()),
+    }
+}
+
+/// Make sure `target` exists and is part of the journal.
+///
+/// Missing files (and their parent directories) are created, and an `include` line is
+/// appended to `main_journal` unless the target is one of the `journal_files` already.
+pub fn prepare_commit_target(
+    target: &Path,
+    main_journal: &Path,
+    journal_files: &SourceSet,
+) -> Result<()> {
+    if file_identity(target) == file_identity(main_journal) {
+        return Ok(());
+    }
+
+    if !target.exists() {
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        std::fs::File::create(target)
+            .with_context(|| format!("Failed to create journal file: {}", target.display()))?;
+        tracing::info!("Created journal file {}", target.display());
+    }
+
+    let target_canonical = target.canonicalize()?;
+    if journal_files.iter().any(|file| {
+        file.canonicalize()
+            .is_ok_and(|file| file == target_canonical)
+    }) {
+        return Ok(());
+    }
+
+    let include = format!("\ninclude \"{}\"\n", include_path(target, main_journal));
+    journal_file::append_all_or_nothing(&[(main_journal, include.as_bytes())])?;
+    tracing::info!(
+        "Added include for {} to {}",
+        target.display(),
+        main_journal.display()
+    );
+
+    Ok(())
+}
+
+/// Commit several transactions at once, writing each one into the file chosen by `rules`
+/// for the directive with its overrides applied.
+///
+/// Like [`crate::commit_transactions`], either every entry lands in its journal file or
+/// none does. Target files and `include` lines are created before writing and are kept
+/// even if the write fails. `journal_files` are the files the journal currently consists
+/// of, targets among them are not included again.
+pub fn commit_transactions_to_targets(
+    entries: &[(&Directive, CommitOverrides<'_>)],
+    source_meta_target: SourceMetaTarget,
+    mode: CommitMode,
+    main_journal: &Path,
+    journal_files: &SourceSet,
+    rules: &[CommitTargetRule],
+) -> Result<()> {
+    // Every directive is rendered before any file is touched, and paths naming the same
+    // file are grouped so that it is written once.
+    let mut by_target: BTreeMap<PathBuf, (PathBuf, Vec<Directive>)> = BTreeMap::new();
+    for &(directive, overrides) in entries {
+        let committed = crate::apply_commit_overrides(directive, &overrides, source_meta_target)?;
+        let target = resolve_commit_target(&committed, rules, main_journal);
+        by_target
+            .entry(file_identity(&target))
+            .or_insert_with(|| (target, Vec::new()))
+            .1
+            .push(committed);
+    }
+
+    for (target, _) in by_target.values() {
+        prepare_commit_target(target, main_journal, journal_files)?;
+    }
+
+    match mode {
+        CommitMode::Append => {
+            let mut rendered = Vec::with_capacity(by_target.len());
+            for (target, directives) in by_target.values() {
+                let mut contents = Vec::new();
+                for directive in directives {
+                    writeln!(contents, "\n{}", directive)?;
+                }
+                rendered.push((target.as_path(), contents));
+            }
+
+            let writes: Vec<(&Path, &[u8])> = rendered
+                .iter()
+                .map(|(target, contents)| (*target, contents.as_slice()))
+                .collect();
+            journal_file::append_all_or_nothing(&writes)
+        }
+        CommitMode::DateOrdered => {
+            let mut contents = Vec::with_capacity(by_target.len());
+            for (target, directives) in by_target.values() {
+                let original = std::fs::read_to_string(target).with_context(|| {
+                    format!("Failed to read journal file: {}", target.display())
+                })?;
+                let mut updated = original.clone();
+                for directive in directives {
+                    updated = journal_file::insert_in_date_order(
+                        &updated,
+                        directive,
+                        &directive.to_string(),
+                    );
+                }
+                contents.push((target, original, updated));
+            }
+
+            let writes: Vec<(&Path, &[u8], &[u8])> = contents
+                .iter()
+                .map(|(target, original, updated)| {
+                    (target.as_path(), original.as_bytes(), updated.as_bytes())
+                })
+                .collect();
+            journal_file::rewrite_all_or_nothing(&writes)
+        }
+    }
+}
+
+/// The path to write into an `include` line, relative to the directory of `main_journal`
+/// where possible.
+fn include_path(target: &Path, main_journal: &Path) -> String {
+    let base = main_journal.parent().unwrap_or(Path::new(""));
+    let relative = target.strip_prefix(base).unwrap_or(target);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Decimal;
+
+    fn parse_directive(content: &str) -> Directive {
+        beancount_parser::parse::<Decimal>(content)
+            .unwrap()
+            .directives
+            .into_iter()
+            .next()
+            .unwrap()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("beancount-staging-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn rule_matches_glob_and_fills_date() {
+        let rule =
+            CommitTargetRule::new("Assets:Bank:*", "{year}/bank-{month}.beancount".into()).unwrap();
+
+        let bank = parse_directive("2026-03-05 * \"Shop\"\n  Assets:Bank:Checking -1.00 EUR\n");
+        let cash = parse_directive("2026-03-05 * \"Shop\"\n  Assets:Cash -1.00 EUR\n");
+        let balance = parse_directive("2026-03-05 balance Assets:Bank:Savings 10.00 EUR\n");
+
+        assert!(rule.matches(&bank));
+        assert!(!rule.matches(&cash));
+        assert!(rule.matches(&balance));
+        assert_eq!(
+            rule.file_for(&bank),
+            PathBuf::from("2026/bank-03.beancount")
+        );
+        assert_eq!(
+            resolve_commit_target(&cash, &[rule], Path::new("main.beancount")),
+            PathBuf::from("main.beancount")
+        );
+    }
+
+    #[test]
+    fn commit_creates_target_and_include() {
+        let dir = temp_dir("commit-target");
+        let main_journal = dir.join("main.beancount");
+        std::fs::write(&main_journal, "2026-01-01 open Assets:Bank:Checking\n").unwrap();
+
+        let rules =
+            [CommitTargetRule::new("Assets:Bank:*", dir.join("{year}/bank.beancount")).unwrap()];
+        let bank = parse_directive("2026-03-05 ! \"Shop\"\n  Assets:Bank:Checking -1.00 EUR\n");
+        let cash = parse_directive("2026-03-06 ! \"Bakery\"\n  Assets:Cash -2.00 EUR\n");
+        let overrides = CommitOverrides {
+            expense_account: Some("Expenses:Food"),
+            ..Default::default()
+        };
+
+        commit_transactions_to_targets(
+            &[(&bank, overrides), (&cash, overrides)],
+            SourceMetaTarget::Transaction,
+            CommitMode::Append,
+            &main_journal,
+            &SourceSet::from([main_journal.clone()]),
+            &rules,
+        )
+        .unwrap();
+        // the include is only added once
+        commit_transactions_to_targets(
+            &[(&bank, overrides)],
+            SourceMetaTarget::Transaction,
+            CommitMode::Append,
+            &main_journal,
+            &SourceSet::from([main_journal.clone(), dir.join("2026/bank.beancount")]),
+            &rules,
+        )
+        .unwrap();
+
+        let main = std::fs::read_to_string(&main_journal).unwrap();
+        insta::assert_snapshot!(main, @r#"
+        2026-01-01 open Assets:Bank:Checking
+
+        include "2026/bank.beancount"
+
+        2026-03-06 * "Bakery"
+          Assets:Cash -2.00 EUR
+          Expenses:Food
+        "#);
+
+        let bank_file = std::fs::read_to_string(dir.join("2026/bank.beancount")).unwrap();
+        insta::assert_snapshot!(bank_file, @r#"
+
+        2026-03-05 * "Shop"
+          Assets:Bank:Checking -1.00 EUR
+          Expenses:Food
+
+        2026-03-05 * "Shop"
+          Assets:Bank:Checking -1.00 EUR
+          Expenses:Food
+        "#);
+    }
+
+    #[test]
+    fn commit_target_follows_overridden_date() {
+        let dir = temp_dir("commit-target-date");
+        let main_journal = dir.join("main.beancount");
+        std::fs::write(&main_journal, "2026-01-01 open Assets:Bank:Checking\n").unwrap();
+
+        let rules =
+            [
+                CommitTargetRule::new("Assets:Bank:*", dir.join("{year}/bank-{month}.beancount"))
+                    .unwrap(),
+            ];
+        let bank = parse_directive("2026-03-31 ! \"Shop\"\n  Assets:Bank:Checking -1.00 EUR\n");
+        let overrides = CommitOverrides {
+            expense_account: Some("Expenses:Food"),
+            date: Some("2026-04-01"),
+            ..Default::default()
+        };
+
+        let target = resolve_committed_target(&bank, &overrides, &rules, &main_journal).unwrap();
+        assert_eq!(target, dir.join("2026/bank-04.beancount"));
+
+        commit_transactions_to_targets(
+            &[(&bank, overrides)],
+            SourceMetaTarget::Transaction,
+            CommitMode::Append,
+            &main_journal,
+            &SourceSet::from([main_journal.clone()]),
+            &rules,
+        )
+        .unwrap();
+        assert!(!dir.join("2026/bank-03.beancount").exists());
+        assert!(
+            std::fs::read_to_string(&target)
+                .unwrap()
+                .contains("2026-04-01 * \"Shop\"")
+        );
+    }
+
+    #[test]
+    fn commit_writes_the_same_file_once() {
+        let dir = temp_dir("commit-same-file");
+        let main_journal = dir.join("main.beancount");
+        std::fs::write(&main_journal, "2026-01-01 open Assets:Bank:Checking\n").unwrap();
+
+        // Names the main journal through a different path
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let rules =
+            [CommitTargetRule::new("Assets:Bank:*", dir.join("sub/../main.beancount")).unwrap()];
+        let bank = parse_directive("2026-03-05 ! \"Shop\"\n  Assets:Bank:Checking -1.00 EUR\n");
+        let cash = parse_directive("2026-03-06 ! \"Bakery\"\n  Assets:Cash -2.00 EUR\n");
+        let overrides = CommitOverrides {
+            expense_account: Some("Expenses:Food"),
+            ..Default::default()
+        };
+
+        commit_transactions_to_targets(
+            &[(&bank, overrides), (&cash, overrides)],
+            SourceMetaTarget::Transaction,
+            CommitMode::DateOrdered,
+            &main_journal,
+            &SourceSet::from([main_journal.clone()]),
+            &rules,
+        )
+        .unwrap();
+
+        let main = std::fs::read_to_string(&main_journal).unwrap();
+        assert!(!main.contains("include"));
+        assert!(main.contains("2026-03-05 * \"Shop\""));
+        assert!(main.contains("2026-03-06 * \"Bakery\""));
+    }
+
+    #[test]
+    fn commit_date_ordered_inserts_between_entries() {
+        let dir = temp_dir("commit-date-ordered");
+        let main_journal = dir.join("main.beancount");
+        std::fs::write(
+            &main_journal,
+            r#"option "title" "Test"
+
+2026-01-01 open Assets:Cash
+
+; march
+2026-03-01 * "Early"
+  Assets:Cash -1.00 EUR
+  Expenses:Food
+
+2026-03-20 * "Late"
+  Assets:Cash -3.00 EUR
+  Expenses:Food
+"#,
+        )
+        .unwrap();
+
+        let middle = parse_directive("2026-03-10 ! \"Middle\"\n  Assets:Cash -2.00 EUR\n");
+        let first = parse_directive("2025-12-31 ! \"First\"\n  Assets:Cash -4.00 EUR\n");
+        let overrides = CommitOverrides {
+            expense_account: Some("Expenses:Food"),
+            ..Default::default()
+        };
+        commit_transactions_to_targets(
+            &[(&middle, overrides), (&first, overrides)],
+            SourceMetaTarget::Transaction,
+            CommitMode::DateOrdered,
+            &main_journal,
+            &SourceSet::from([main_journal.clone()]),
+            &[],
+        )
+        .unwrap();
+
+        let main = std::fs::read_to_string(&main_journal).unwrap();
+        insta::assert_snapshot!(main, @r#"
+        option "title" "Test"
+
+        2025-12-31 * "First"
+          Assets:Cash -4.00 EUR
+          Expenses:Food
+
+        2026-01-01 open Assets:Cash
+
+        ; march
+        2026-03-01 * "Early"
+          Assets:Cash -1.00 EUR
+          Expenses:Food
+
+        2026-03-10 * "Middle"
+          Assets:Cash -2.00 EUR
+          Expenses:Food
+
+        2026-03-20 * "Late"
+          Assets:Cash -3.00 EUR
+          Expenses:Food
+        "#);
+    }
+}
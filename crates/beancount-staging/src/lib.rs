@@ -1,11 +1,22 @@
+mod commit_target;
 pub mod reconcile;
 mod sorting;
 mod utils;
 
+pub use utils::glob::Glob;
+pub use utils::journal_file::write_atomically;
+pub use utils::stable_hash::StableHasher;
+
+pub use commit_target::{
+    CommitTargetRule, commit_transactions_to_targets, prepare_commit_target, resolve_commit_target,
+    resolve_committed_target,
+};
+
 pub type Directive = beancount_parser::Directive<Decimal>;
 pub type Entry = beancount_parser::Entry<Decimal>;
 pub type DirectiveContent = beancount_parser::DirectiveContent<Decimal>;
 pub type Transaction = beancount_parser::Transaction<Decimal>;
+pub type Posting = beancount_parser::Posting<Decimal>;
 pub type Decimal = rust_decimal::Decimal;
 
 /// Specifies where to store source metadata (source_desc, source_payee)
@@ -17,10 +28,12 @@ pub enum SourceMetaTarget {
     Posting,
 }
 
+use anyhow::Context;
 pub use anyhow::Result;
 use beancount_parser::metadata::Value;
+use beancount_parser::{Date, PostingPrice};
 
-use std::{io::BufWriter, path::Path};
+use std::path::Path;
 
 /// A rule for auto-categorizing staging transactions.
 ///
@@ -77,8 +90,9 @@ pub fn find_matching_rule<'a>(
 ///
 /// A transaction is balanced if:
 /// - Any posting has no amount (beancount will auto-balance it), OR
-/// - All postings have amounts and they sum to zero per currency
-///   (considering cost basis)
+/// - All postings have amounts and their weights sum to zero per currency.
+///   The weight of a posting is its cost if it has one, otherwise its price
+///   (`@`/`@@`) converted amount, otherwise its amount.
 pub fn is_transaction_balanced(txn: &Transaction) -> bool {
     use std::collections::HashMap;
 
@@ -86,28 +100,14 @@ pub fn is_transaction_balanced(txn: &Transaction) -> bool {
         return true;
     }
 
-    let mut totals_by_currency: HashMap<String, Decimal> = HashMap::new();
+    let mut totals_by_currency: HashMap<&str, Decimal> = HashMap::new();
 
     for posting in &txn.postings {
         if let Some(amount) = &posting.amount {
-            let (value, currency) = if let Some(cost) = &posting.cost {
-                // Prefer total_amount (e.g., {# 350 EUR}); fall back to per-unit × units.
-                if let Some(total) = &cost.total_amount {
-                    (total.value, total.currency.to_string())
-                } else if let Some(per_unit) = &cost.amount {
-                    (per_unit.value * amount.value, per_unit.currency.to_string())
-                } else {
-                    (amount.value, amount.currency.to_string())
-                }
-            } else {
-                (amount.value, amount.currency.to_string())
-            };
-
-            let current = totals_by_currency
-                .get(&currency)
-                .copied()
-                .unwrap_or_default();
-            totals_by_currency.insert(currency, current + value);
+            let (value, currency) = cost_total(posting)
+                .or_else(|| price_total(posting))
+                .unwrap_or((amount.value, amount.currency.as_str()));
+            *totals_by_currency.entry(currency).or_default() += value;
         }
     }
 
@@ -117,6 +117,32 @@ pub fn is_transaction_balanced(txn: &Transaction) -> bool {
         .all(|total| total.abs() <= tolerance)
 }
 
+/// The total cost of a posting's lot in the cost currency, signed like the units.
+///
+/// Prefers the total (e.g. `{# 350 EUR}`) and falls back to per-unit × units. Returns `None`
+/// without a cost or for an empty cost spec (`{}`), which leaves the lot to beancount's booking.
+pub(crate) fn cost_total(posting: &Posting) -> Option<(Decimal, &str)> {
+    let cost = posting.cost.as_ref()?;
+    let units = posting.amount.as_ref()?.value;
+    if let Some(total) = &cost.total_amount {
+        Some((total.value * units.signum(), total.currency.as_str()))
+    } else {
+        let per_unit = cost.amount.as_ref()?;
+        Some((per_unit.value * units, per_unit.currency.as_str()))
+    }
+}
+
+/// The total price of a posting in the price currency, signed like the units.
+///
+/// `10 ACME @ 6 EUR` and `10 ACME @@ 60 EUR` both have a total price of `60 EUR`.
+pub(crate) fn price_total(posting: &Posting) -> Option<(Decimal, &str)> {
+    let units = posting.amount.as_ref()?.value;
+    match posting.price.as_ref()? {
+        PostingPrice::Unit(price) => Some((price.value * units, price.currency.as_str())),
+        PostingPrice::Total(price) => Some((price.value * units.signum(), price.currency.as_str())),
+    }
+}
+
 /// Read all directives from the given source.
 pub fn read_directives(file: impl AsRef<Path>) -> Result<Vec<Directive>> {
     let mut directives = Vec::new();
@@ -134,48 +160,114 @@ pub fn read_directives(file: impl AsRef<Path>) -> Result<Vec<Directive>> {
     Ok(directives)
 }
 
-/// Commit a transaction to the journal file with the specified expense account.
+/// How committed directives are placed into a journal file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommitMode {
+    /// Append at the end of the file
+    #[default]
+    Append,
+    /// Insert after the last directive dated on or before the committed one,
+    /// keeping the file in date order
+    DateOrdered,
+}
+
+/// Changes applied to a staging directive when it is committed to the journal.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommitOverrides<'a> {
+    /// Account for the balancing posting (amount is inferred by beancount)
+    pub expense_account: Option<&'a str>,
+    pub payee: Option<&'a str>,
+    pub narration: Option<&'a str>,
+    /// New date in `YYYY-MM-DD` format, e.g. the purchase date of a card transaction
+    pub date: Option<&'a str>,
+    /// Postings with explicit amounts, in the currency of the primary posting.
+    ///
+    /// Together with `expense_account`, the remainder goes to that account. Without it,
+    /// the splits have to balance the transaction on their own.
+    pub splits: &'a [CommitSplit<'a>],
+    /// Tags to add or remove, with or without the leading `#`
+    pub add_tags: &'a [&'a str],
+    pub remove_tags: &'a [&'a str],
+    /// Links to add or remove, with or without the leading `^`
+    pub add_links: &'a [&'a str],
+    pub remove_links: &'a [&'a str],
+    /// Additional transaction metadata, stored as string values
+    pub metadata: &'a [(&'a str, &'a str)],
+}
+
+/// A posting with an explicit amount, added when committing a transaction.
+#[derive(Debug, Clone, Copy)]
+pub struct CommitSplit<'a> {
+    pub account: &'a str,
+    pub amount: Decimal,
+}
+
+/// Commit a transaction to the journal file with the specified overrides.
 ///
 /// This modifies the transaction by:
 /// - Changing the flag from `!` to `*`
-/// - Optionally updating payee and narration if provided
+/// - Optionally updating payee, narration and date if provided
+/// - Adding and removing tags and links, and adding metadata
 /// - Adding a balancing posting with the expense account if provided (amount is inferred by beancount)
 pub fn commit_transaction(
     directive: &Directive,
-    expense_account: Option<&str>,
-    payee: Option<&str>,
-    narration: Option<&str>,
+    overrides: &CommitOverrides<'_>,
     source_meta_target: SourceMetaTarget,
     journal_path: &Path,
 ) -> Result<()> {
-    use std::fs::OpenOptions;
-
-    // Open journal file in append mode
-    let file = BufWriter::new(OpenOptions::new().append(true).open(journal_path)?);
-
-    commit_transaction_to_writer(
-        directive,
-        expense_account,
-        payee,
-        narration,
-        source_meta_target,
-        file,
-    )
+    commit_transactions(&[(directive, *overrides)], source_meta_target, journal_path)
+}
+
+/// Commit several transactions to the journal file at once.
+///
+/// Every directive is rendered before the journal is touched, so an invalid override
+/// (e.g. an unparsable account name) aborts the whole batch without writing anything.
+/// The rendered entries are then appended in a single fsync'd write, and the file is
+/// truncated back to its previous length if that write fails.
+/// Either every entry lands in the journal or none does.
+pub fn commit_transactions(
+    entries: &[(&Directive, CommitOverrides<'_>)],
+    source_meta_target: SourceMetaTarget,
+    journal_path: &Path,
+) -> Result<()> {
+    let rendered = render_commits(entries, source_meta_target)?;
+    utils::journal_file::append_all_or_nothing(&[(journal_path, &rendered)])
+}
+
+/// Render the journal text for a batch of commits without writing it anywhere.
+fn render_commits(
+    entries: &[(&Directive, CommitOverrides<'_>)],
+    source_meta_target: SourceMetaTarget,
+) -> Result<Vec<u8>> {
+    let mut rendered = Vec::new();
+    for (directive, overrides) in entries {
+        commit_transaction_to_writer(directive, overrides, source_meta_target, &mut rendered)?;
+    }
+    Ok(rendered)
 }
 
 /// Internal function that commits to a writer. Used by both the public API and tests.
 fn commit_transaction_to_writer(
     directive: &Directive,
-    expense_account: Option<&str>,
-    payee: Option<&str>,
-    narration: Option<&str>,
+    overrides: &CommitOverrides<'_>,
     source_meta_target: SourceMetaTarget,
     mut writer: impl std::io::Write,
 ) -> Result<()> {
-    use anyhow::Context;
+    let directive = apply_commit_overrides(directive, overrides, source_meta_target)?;
+    writeln!(writer, "\n{}", directive)?;
+
+    Ok(())
+}
 
-    let original = directive;
+/// Build the journal directive for a staging directive with the given overrides applied.
+fn apply_commit_overrides(
+    original: &Directive,
+    overrides: &CommitOverrides<'_>,
+    source_meta_target: SourceMetaTarget,
+) -> Result<Directive> {
     let mut directive = original.clone();
+    // Only needed to tell staging sources apart
+    directive.metadata.remove(reconcile::STAGING_SOURCE_KEY);
 
     if let DirectiveContent::Transaction(ref mut txn) = directive.content {
         // Change flag from ! to *
@@ -188,13 +280,13 @@ fn commit_transaction_to_writer(
                 &mut txn
                     .postings
                     .first_mut()
-                    .expect("TODO: no first account")
+                    .context("Transaction has no posting to store source metadata on")?
                     .metadata
             }
         };
 
         // Update payee if provided, saving original as metadata
-        if let Some(new_payee) = payee {
+        if let Some(new_payee) = overrides.payee {
             if let Some(original_payee) = &txn.payee
                 && original_payee != new_payee
             {
@@ -207,7 +299,7 @@ fn commit_transaction_to_writer(
         }
 
         // Update narration if provided, saving original as metadata
-        if let Some(new_narration) = narration {
+        if let Some(new_narration) = overrides.narration {
             if let Some(original_narration) = &txn.narration
                 && original_narration != new_narration
             {
@@ -219,11 +311,68 @@ fn commit_transaction_to_writer(
             txn.narration = Some(new_narration.to_string());
         }
 
+        // Update date if provided, saving original as metadata so reconciliation still finds it
+        if let Some(new_date) = overrides.date {
+            let new_date = parse_date(new_date)?;
+            if new_date != directive.date {
+                meta.insert(
+                    "source_date".parse().unwrap(),
+                    Value::String(directive.date.to_string()),
+                );
+                directive.date = new_date;
+            }
+        }
+
+        // Update tags and links. The matcher ignores both, so this doesn't affect reconciliation.
+        txn.tags.retain(|tag| {
+            !overrides
+                .remove_tags
+                .iter()
+                .any(|name| name.trim_start_matches('#') == tag.to_string())
+        });
+        txn.links.retain(|link| {
+            !overrides
+                .remove_links
+                .iter()
+                .any(|name| name.trim_start_matches('^') == link.to_string())
+        });
+        if !overrides.add_tags.is_empty() || !overrides.add_links.is_empty() {
+            let parsed = parse_tags_and_links(overrides.add_tags, overrides.add_links)?;
+            txn.tags.extend(parsed.tags);
+            txn.links.extend(parsed.links);
+        }
+
+        // Add split postings before the balancing posting
+        if !overrides.splits.is_empty() {
+            let primary_amount = txn
+                .postings
+                .first()
+                .and_then(|p| p.amount.clone())
+                .context("Splits require a primary posting with an amount")?;
+
+            // Without a remainder account, the splits replace any amountless posting
+            if overrides.expense_account.is_none() {
+                txn.postings.retain(|p| p.amount.is_some());
+            }
+
+            let insert_at = txn
+                .postings
+                .iter()
+                .position(|p| p.amount.is_none())
+                .unwrap_or(txn.postings.len());
+            for (i, split) in overrides.splits.iter().enumerate() {
+                let mut amount = primary_amount.clone();
+                amount.value = split.amount;
+                let account = parse_account(split.account)?;
+                let mut posting = beancount_parser::Posting::from_account(account);
+                posting.amount = Some(amount);
+                txn.postings.insert(insert_at + i, posting);
+            }
+        }
+
         // Add or update balancing posting with expense account if provided (no amount - beancount infers it)
-        if let Some(expense_account) = expense_account {
-            let account: beancount_parser::Account = expense_account
-                .parse()
-                .with_context(|| format!("Failed to parse account name: '{}'", expense_account))?;
+        if let Some(expense_account) = overrides.expense_account {
+            let account = parse_account(expense_account)?;
 
             // If there's already an unbalanced posting (no amount), update its account
             // Otherwise, add a new balancing posting
@@ -236,15 +385,221 @@ fn commit_transaction_to_writer(
         }
     }
 
-    let does_match = reconcile::matching::journal_matches_staging(&directive, original);
-    assert!(
-        does_match.is_ok(),
-        "Internal error: commited transaction does not match original"
+    for (key, value) in overrides.metadata {
+        anyhow::ensure!(
+            !RESERVED_METADATA_KEYS.contains(key),
+            "Metadata key '{}' is reserved for matching the staging entry",
+            key
+        );
+        let meta_key: beancount_parser::metadata::Key = key
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid metadata key: '{}'", key))?;
+        directive
+            .metadata
+            .insert(meta_key, Value::String(value.to_string()));
+    }
+
+    if !overrides.splits.is_empty()
+        && let DirectiveContent::Transaction(txn) = &directive.content
+    {
+        anyhow::ensure!(
+            is_transaction_balanced(txn),
+            "Split amounts don't balance the transaction"
+        );
+    }
+
+    if let Err(reason) = reconcile::matching::journal_matches_staging(&directive, original) {
+        anyhow::bail!("Internal error: committed transaction does not match original: {reason}");
+    }
+
+    Ok(directive)
+}
+
+/// Metadata keys written by [`apply_commit_overrides`] and read when matching, so
+/// overriding them would break the match with the staging entry.
+const RESERVED_METADATA_KEYS: [&str; 4] = [
+    "source_date",
+    "source_payee",
+    "source_desc",
+    reconcile::STAGING_SOURCE_KEY,
+];
+
+fn parse_account(name: &str) -> Result<beancount_parser::Account> {
+    name.parse()
+        .with_context(|| format!("Failed to parse account name: '{}'", name))
+}
+
+/// Parse a `YYYY-MM-DD` date by letting the parser read it from a minimal directive.
+pub fn parse_date(date: &str) -> Result<Date> {
+    let is_valid = date.len() == 10
+        && date.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        });
+    anyhow::ensure!(is_valid, "Invalid date: '{}', expected YYYY-MM-DD", date);
+
+    beancount_parser::parse::<Decimal>(&format!("{date} commodity EUR\n"))
+        .ok()
+        .and_then(|parsed| parsed.directives.into_iter().next())
+        .map(|directive| directive.date)
+        .with_context(|| format!("Invalid date: '{}'", date))
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+pub fn day_number(date: &Date) -> i64 {
+    let date = date.to_string();
+    let part = |range: std::ops::Range<usize>| date.get(range).and_then(|s| s.parse::<i64>().ok());
+    let (Some(year), Some(month), Some(day)) = (part(0..4), part(5..7), part(8..10)) else {
+        return 0;
+    };
+
+    // https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Parse tag and link names by letting the parser read them from a minimal transaction.
+///
+/// The returned transaction carries nothing but the parsed `tags` and `links`.
+fn parse_tags_and_links(tags: &[&str], links: &[&str]) -> Result<Transaction> {
+    let mut line = String::from("1970-01-01 * \"\"");
+    for (prefix, names) in [('#', tags), ('^', links)] {
+        for name in names {
+            let name = name.trim_start_matches(prefix);
+            anyhow::ensure!(
+                !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '/' | '.')),
+                "Invalid tag or link: '{}{}'",
+                prefix,
+                name
+            );
+            line.push(' ');
+            line.push(prefix);
+            line.push_str(name);
+        }
+    }
+    line.push('\n');
+
+    let directive = beancount_parser::parse::<Decimal>(&line)
+        .ok()
+        .and_then(|parsed| parsed.directives.into_iter().next());
+    match directive.map(|directive| directive.content) {
+        Some(DirectiveContent::Transaction(txn)) => Ok(txn),
+        _ => anyhow::bail!("Failed to parse tags and links: {}", line.trim_end()),
+    }
+}
+
+/// Remove the journal entry that was committed for `staging` from the journal file.
+///
+/// The entry is found with the same matching logic used during reconciliation, searching
+/// from the end of the file since commits are appended. Returns the removed journal entry,
+/// so that the staging directive shows up as unreviewed again on the next reload.
+pub fn uncommit_transaction(staging: &Directive, journal_path: &Path) -> Result<Directive> {
+    uncommit_matching(journal_path, |journal| {
+        reconcile::matching::source_date(journal) == staging.date
+            && reconcile::matching::journal_matches_staging(journal, staging).is_ok()
+    })?
+    .with_context(|| {
+        format!(
+            "No committed entry matching the staging directive found in {}:\n{}",
+            journal_path.display(),
+            staging
+        )
+    })
+}
+
+/// Remove the most recently appended journal entry that matches any of the `staging`
+/// directives. Returns the removed journal entry, or `None` if nothing matched.
+///
+/// Only supported for [`CommitMode::Append`]: date ordered commits are inserted in the
+/// middle of the file, so the last match isn't necessarily the latest commit.
+pub fn uncommit_last(
+    staging: &[Directive],
+    journal_path: &Path,
+    mode: CommitMode,
+) -> Result<Option<Directive>> {
+    anyhow::ensure!(
+        mode == CommitMode::Append,
+        "Can't tell the most recent commit apart in date ordered journal files, \
+         remove it from {} by hand",
+        journal_path.display()
     );
+    uncommit_matching(journal_path, |journal| {
+        staging.iter().any(|staging_directive| {
+            reconcile::matching::source_date(journal) == staging_directive.date
+                && reconcile::matching::journal_matches_staging(journal, staging_directive).is_ok()
+        })
+    })
+}
 
-    writeln!(writer, "\n{}", directive)?;
+fn uncommit_matching(
+    journal_path: &Path,
+    is_match: impl Fn(&Directive) -> bool,
+) -> Result<Option<Directive>> {
+    let source = std::fs::read_to_string(journal_path)
+        .with_context(|| format!("Failed to read journal file: {}", journal_path.display()))?;
+
+    for span in utils::journal_file::entry_spans(&source).into_iter().rev() {
+        // Spans which don't hold a directive (options, includes, ...) can't have been committed.
+        let Some(directive) = beancount_parser::parse_iter::<Decimal>(&source[span.clone()])
+            .filter_map(|entry| match entry {
+                Ok(Entry::Directive(directive)) => Some(directive),
+                _ => None,
+            })
+            .next()
+        else {
+            continue;
+        };
 
-    Ok(())
+        if is_match(&directive) {
+            let updated = utils::journal_file::remove_span(&source, span);
+            utils::journal_file::rewrite_all_or_nothing(&[(
+                journal_path,
+                source.as_bytes(),
+                updated.as_bytes(),
+            )])?;
+            return Ok(Some(directive));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Record staging directives as ignored by appending them to `ignored_file`.
+///
+/// Transactions are tagged `#ignored`. The file must not be included from the journal:
+/// reconciliation treats its entries as matched, so they never show up for review again,
+/// but they don't become part of the books. Missing files are created.
+pub fn ignore_staging(directives: &[&Directive], ignored_file: &Path) -> Result<()> {
+    use std::io::Write;
+
+    let mut rendered = Vec::new();
+    for directive in directives {
+        let mut directive = (*directive).clone();
+        directive.metadata.remove(reconcile::STAGING_SOURCE_KEY);
+        if let DirectiveContent::Transaction(txn) = &mut directive.content {
+            txn.tags
+                .extend(parse_tags_and_links(&["ignored"], &[])?.tags);
+        }
+        writeln!(rendered, "\n{}", directive)?;
+    }
+
+    if !ignored_file.exists() {
+        if let Some(parent) = ignored_file.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        std::fs::File::create(ignored_file).with_context(|| {
+            format!("Failed to create ignored file: {}", ignored_file.display())
+        })?;
+    }
+    utils::journal_file::append_all_or_nothing(&[(ignored_file, &rendered)])
 }
 
 #[cfg(test)]
@@ -290,9 +645,10 @@ mod tests {
 
         commit_transaction_to_writer(
             &directive,
-            Some("Expenses:Groceries"),
-            None,
-            None,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Groceries"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output,
         )
@@ -307,6 +663,36 @@ mod tests {
         "#);
     }
 
+    #[test]
+    fn test_commit_transaction_drops_staging_source() {
+        let directive = parse_directive(
+            r#"2024-01-15 ! "Shop" "Order"
+  staging_source: "paypal"
+  Assets:PayPal  -50.00 USD
+"#,
+        );
+        let mut output = Vec::new();
+
+        commit_transaction_to_writer(
+            &directive,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Shopping"),
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            &mut output,
+        )
+        .unwrap();
+
+        let content = String::from_utf8(output).unwrap();
+        insta::assert_snapshot!(content, @r#"
+
+        2024-01-15 * "Shop" "Order"
+          Assets:PayPal -50.00 USD
+          Expenses:Shopping
+        "#);
+    }
+
     #[test]
     fn test_commit_transaction_balanced() {
         let directive = create_balanced_transaction('!', "Transfer", "Internal transfer");
@@ -314,9 +700,7 @@ mod tests {
 
         commit_transaction_to_writer(
             &directive,
-            None,
-            None,
-            None,
+            &CommitOverrides::default(),
             SourceMetaTarget::Transaction,
             &mut output,
         )
@@ -338,9 +722,11 @@ mod tests {
 
         commit_transaction_to_writer(
             &directive,
-            Some("Expenses:Food"),
-            Some("New Payee"),
-            None,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                payee: Some("New Payee"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output,
         )
@@ -363,9 +749,11 @@ mod tests {
 
         commit_transaction_to_writer(
             &directive,
-            Some("Expenses:Food"),
-            None,
-            Some("New Narration"),
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                narration: Some("New Narration"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output,
         )
@@ -388,9 +776,12 @@ mod tests {
 
         commit_transaction_to_writer(
             &directive,
-            Some("Expenses:Food"),
-            Some("New Payee"),
-            Some("New Narration"),
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                payee: Some("New Payee"),
+                narration: Some("New Narration"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output,
         )
@@ -414,9 +805,10 @@ mod tests {
 
         commit_transaction_to_writer(
             &directive,
-            Some("Expenses:Food"),
-            None,
-            None,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output,
         )
@@ -438,9 +830,11 @@ mod tests {
 
         commit_transaction_to_writer(
             &directive,
-            Some("Expenses:Food"),
-            Some("Same Payee"),
-            None,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                payee: Some("Same Payee"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output,
         )
@@ -462,9 +856,10 @@ mod tests {
 
         let result = commit_transaction_to_writer(
             &directive,
-            Some("Invalid Account Name!"),
-            None,
-            None,
+            &CommitOverrides {
+                expense_account: Some("Invalid Account Name!"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output,
         );
@@ -488,9 +883,10 @@ mod tests {
         // User selects "Assets:ZeroSum:Transfers" in the UI (same account as the existing posting)
         commit_transaction_to_writer(
             &directive,
-            Some("Assets:ZeroSum:Transfers"),
-            None,
-            None,
+            &CommitOverrides {
+                expense_account: Some("Assets:ZeroSum:Transfers"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output,
         )
@@ -520,9 +916,10 @@ mod tests {
         // User changes the account to something different
         commit_transaction_to_writer(
             &directive,
-            Some("Expenses:Groceries"),
-            None,
-            None,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Groceries"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output,
         )
@@ -550,27 +947,30 @@ mod tests {
 
         commit_transaction_to_writer(
             &directive_exclaim,
-            Some("Expenses:Food"),
-            None,
-            None,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output1,
         )
         .unwrap();
         commit_transaction_to_writer(
             &directive_asterisk,
-            Some("Expenses:Food"),
-            None,
-            None,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output2,
         )
         .unwrap();
         commit_transaction_to_writer(
             &directive_txn,
-            Some("Expenses:Food"),
-            None,
-            None,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                ..Default::default()
+            },
             SourceMetaTarget::Transaction,
             &mut output3,
         )
@@ -586,6 +986,364 @@ mod tests {
         assert!(content3.contains("2024-01-15 *"));
     }
 
+    #[test]
+    fn test_commit_transaction_splits_with_remainder() {
+        let directive = create_test_transaction('!', "Supermarket", "Receipt");
+        let mut output = Vec::new();
+
+        commit_transaction_to_writer(
+            &directive,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Groceries"),
+                splits: &[CommitSplit {
+                    account: "Expenses:Household",
+                    amount: Decimal::new(1250, 2),
+                }],
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            &mut output,
+        )
+        .unwrap();
+
+        let content = String::from_utf8(output).unwrap();
+        insta::assert_snapshot!(content, @r#"
+
+        2024-01-15 * "Supermarket" "Receipt"
+          Assets:Checking -50.00 USD
+          Expenses:Household 12.50 USD
+          Expenses:Groceries
+        "#);
+    }
+
+    #[test]
+    fn test_commit_transaction_with_date_override() {
+        let directive = create_test_transaction('!', "Card Shop", "Purchase");
+        let mut output = Vec::new();
+
+        commit_transaction_to_writer(
+            &directive,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Shopping"),
+                date: Some("2024-01-12"),
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            &mut output,
+        )
+        .unwrap();
+
+        let content = String::from_utf8(output).unwrap();
+        insta::assert_snapshot!(content, @r#"
+
+        2024-01-12 * "Card Shop" "Purchase"
+          source_date: "2024-01-15"
+          Assets:Checking -50.00 USD
+          Expenses:Shopping
+        "#);
+
+        let committed = parse_directive(&content);
+        assert_eq!(reconcile::matching::source_date(&committed), directive.date);
+        assert!(reconcile::matching::journal_matches_staging(&committed, &directive).is_ok());
+
+        let invalid = commit_transaction_to_writer(
+            &directive,
+            &CommitOverrides {
+                date: Some("12.01.2024"),
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            Vec::new(),
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_commit_transaction_tags_links_and_metadata() {
+        let directive = parse_directive(
+            r#"2024-01-15 ! "Hotel" "Two nights" #import ^booking-1
+    Assets:Checking  -50.00 USD
+"#,
+        );
+        let mut output = Vec::new();
+
+        commit_transaction_to_writer(
+            &directive,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Travel"),
+                add_tags: &["#trip-2026"],
+                remove_tags: &["import"],
+                add_links: &["^invoice-42"],
+                remove_links: &["booking-1"],
+                metadata: &[("receipt", "scan-0815.pdf")],
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            &mut output,
+        )
+        .unwrap();
+
+        let content = String::from_utf8(output).unwrap();
+        insta::assert_snapshot!(content, @r#"
+
+        2024-01-15 * "Hotel" "Two nights" #trip-2026 ^invoice-42
+          receipt: "scan-0815.pdf"
+          Assets:Checking -50.00 USD
+          Expenses:Travel
+        "#);
+
+        let invalid = commit_transaction_to_writer(
+            &directive,
+            &CommitOverrides {
+                add_tags: &["two words"],
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            Vec::new(),
+        );
+        assert!(invalid.is_err());
+
+        let reserved = commit_transaction_to_writer(
+            &directive,
+            &CommitOverrides {
+                metadata: &[("source_date", "2024-01-01")],
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            Vec::new(),
+        );
+        assert!(
+            reserved.is_err(),
+            "Should not allow overriding metadata used for matching"
+        );
+    }
+
+    #[test]
+    fn test_commit_transaction_splits_must_balance() {
+        let directive = create_test_transaction('!', "Supermarket", "Receipt");
+        let splits = [
+            CommitSplit {
+                account: "Expenses:Groceries",
+                amount: Decimal::new(3750, 2),
+            },
+            CommitSplit {
+                account: "Expenses:Household",
+                amount: Decimal::new(1250, 2),
+            },
+        ];
+
+        let mut output = Vec::new();
+        commit_transaction_to_writer(
+            &directive,
+            &CommitOverrides {
+                splits: &splits,
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            &mut output,
+        )
+        .unwrap();
+        let content = String::from_utf8(output).unwrap();
+        insta::assert_snapshot!(content, @r#"
+
+        2024-01-15 * "Supermarket" "Receipt"
+          Assets:Checking -50.00 USD
+          Expenses:Groceries 37.50 USD
+          Expenses:Household 12.50 USD
+        "#);
+
+        let result = commit_transaction_to_writer(
+            &directive,
+            &CommitOverrides {
+                splits: &splits[..1],
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            &mut Vec::new(),
+        );
+        assert!(result.is_err());
+    }
+
+    fn temp_journal(name: &str, contents: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("beancount-staging-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("journal.beancount");
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn commit_transactions_appends_all_entries() {
+        let journal_path = temp_journal("batch-ok", "2024-01-01 open Assets:Checking\n");
+        let first = create_test_transaction('!', "First", "One");
+        let second = create_test_transaction('!', "Second", "Two");
+
+        commit_transactions(
+            &[
+                (
+                    &first,
+                    CommitOverrides {
+                        expense_account: Some("Expenses:Food"),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    &second,
+                    CommitOverrides {
+                        expense_account: Some("Expenses:Rent"),
+                        ..Default::default()
+                    },
+                ),
+            ],
+            SourceMetaTarget::Transaction,
+            &journal_path,
+        )
+        .unwrap();
+
+        let content = std::fs::read_to_string(&journal_path).unwrap();
+        insta::assert_snapshot!(content, @r#"
+        2024-01-01 open Assets:Checking
+
+        2024-01-15 * "First" "One"
+          Assets:Checking -50.00 USD
+          Expenses:Food
+
+        2024-01-15 * "Second" "Two"
+          Assets:Checking -50.00 USD
+          Expenses:Rent
+        "#);
+    }
+
+    #[test]
+    fn ignore_staging_creates_file_and_tags_entries() {
+        let journal_path = temp_journal("ignore", "");
+        let ignored_file = journal_path.with_file_name("ignored/ignored.beancount");
+        let _ = std::fs::remove_file(&ignored_file);
+        let hold = create_test_transaction('!', "Card Hold", "Pre-authorization");
+
+        ignore_staging(&[&hold], &ignored_file).unwrap();
+
+        let content = std::fs::read_to_string(&ignored_file).unwrap();
+        insta::assert_snapshot!(content, @r#"
+
+        2024-01-15 ! "Card Hold" "Pre-authorization" #ignored
+          Assets:Checking -50.00 USD
+        "#);
+    }
+
+    #[test]
+    fn commit_transactions_invalid_entry_writes_nothing() {
+        let original = "2024-01-01 open Assets:Checking\n";
+        let journal_path = temp_journal("batch-rollback", original);
+        let valid = create_test_transaction('!', "Valid", "Entry");
+        let invalid = create_test_transaction('!', "Invalid", "Entry");
+
+        let result = commit_transactions(
+            &[
+                (
+                    &valid,
+                    CommitOverrides {
+                        expense_account: Some("Expenses:Food"),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    &invalid,
+                    CommitOverrides {
+                        expense_account: Some("Invalid Account Name!"),
+                        ..Default::default()
+                    },
+                ),
+            ],
+            SourceMetaTarget::Transaction,
+            &journal_path,
+        );
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&journal_path).unwrap(), original);
+    }
+
+    #[test]
+    fn uncommit_transaction_restores_journal() {
+        let original = r#"2024-01-01 open Assets:Checking
+
+2024-01-15 * "Other" "Entry"
+  Assets:Checking -10.00 USD
+  Expenses:Rent
+"#;
+        let journal_path = temp_journal("uncommit", original);
+        let directive = create_test_transaction('!', "Shop", "Groceries");
+
+        commit_transaction(
+            &directive,
+            &CommitOverrides {
+                expense_account: Some("Expenses:Food"),
+                payee: Some("Supermarket"),
+                ..Default::default()
+            },
+            SourceMetaTarget::Transaction,
+            &journal_path,
+        )
+        .unwrap();
+        assert_ne!(std::fs::read_to_string(&journal_path).unwrap(), original);
+
+        let removed = uncommit_transaction(&directive, &journal_path).unwrap();
+        insta::assert_snapshot!(removed, @r#"
+        2024-01-15 * "Supermarket" "Groceries"
+          source_payee: "Shop"
+          Assets:Checking -50.00 USD
+          Expenses:Food
+        "#);
+        assert_eq!(std::fs::read_to_string(&journal_path).unwrap(), original);
+
+        assert!(uncommit_transaction(&directive, &journal_path).is_err());
+    }
+
+    #[test]
+    fn uncommit_last_removes_latest_match() {
+        let journal_path = temp_journal("uncommit-last", "2024-01-01 open Assets:Checking\n");
+        let first = create_test_transaction('!', "First", "One");
+        let second = create_test_transaction('!', "Second", "Two");
+        let overrides = CommitOverrides {
+            expense_account: Some("Expenses:Food"),
+            ..Default::default()
+        };
+        commit_transactions(
+            &[(&first, overrides), (&second, overrides)],
+            SourceMetaTarget::Transaction,
+            &journal_path,
+        )
+        .unwrap();
+
+        let staging = [first, second];
+        let uncommit = || uncommit_last(&staging, &journal_path, CommitMode::Append).unwrap();
+        let removed = uncommit().unwrap();
+        assert!(reconcile::matching::journal_matches_staging(&removed, &staging[1]).is_ok());
+        let removed = uncommit().unwrap();
+        assert!(reconcile::matching::journal_matches_staging(&removed, &staging[0]).is_ok());
+        assert!(uncommit().is_none());
+        assert_eq!(
+            std::fs::read_to_string(&journal_path).unwrap(),
+            "2024-01-01 open Assets:Checking\n"
+        );
+    }
+
+    #[test]
+    fn uncommit_last_rejects_date_ordered() {
+        let original = r#"2024-01-01 open Assets:Checking
+
+2024-02-01 * "Later" "Entry"
+  Assets:Checking -10.00 USD
+  Expenses:Rent
+"#;
+        let journal_path = temp_journal("uncommit-last-date-ordered", original);
+        let staging = [create_test_transaction('!', "Shop", "Groceries")];
+
+        assert!(uncommit_last(&staging, &journal_path, CommitMode::DateOrdered).is_err());
+        assert_eq!(std::fs::read_to_string(&journal_path).unwrap(), original);
+    }
+
     fn make_rule(payee_pattern: &str, source: &str, target: &str) -> AutoCategorizeRule {
         AutoCategorizeRule {
             match_source_account: source.to_string(),
@@ -793,6 +1551,35 @@ mod tests {
         assert!(is_transaction_balanced(&txn));
     }
 
+    #[test]
+    fn balanced_transaction_with_price_and_gains() {
+        let unit_price = parse_txn(
+            r#"2024-01-15 * "Exchange"
+    Assets:Bank:USD  100.00 USD @ 0.90 EUR
+    Assets:Bank:EUR  -90.00 EUR
+"#,
+        );
+        assert!(is_transaction_balanced(&unit_price));
+
+        let total_price = parse_txn(
+            r#"2024-01-15 * "Exchange"
+    Assets:Bank:USD  -100.00 USD @@ 90.00 EUR
+    Assets:Bank:EUR    90.00 EUR
+"#,
+        );
+        assert!(is_transaction_balanced(&total_price));
+
+        let sale_with_gains = parse_txn(
+            r#"2024-06-01 * "Sell ETF"
+    Assets:Broker:Cash     1190.00 EUR
+    Assets:Broker:ETF     -10 ACME {100.00 EUR} @ 120.00 EUR
+    Expenses:Fees            10.00 EUR
+    Income:Gains           -200.00 EUR
+"#,
+        );
+        assert!(is_transaction_balanced(&sale_with_gains));
+    }
+
     #[test]
     fn balanced_transaction_with_cost() {
         let txn = parse_txn(
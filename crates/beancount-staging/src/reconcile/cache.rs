@@ -0,0 +1,301 @@
+//! Parsed journal and staging files, kept between reads.
+//!
+//! The web UI reads everything again on every file change, most of which are our own appends
+//! to the journal. Files whose modification time, size and contents are unchanged since the
+//! last read are not parsed again.
+//!
+//! The output of a staging command is also cached on disk, as importers can take a while.
+
+use super::{CACHE_DIR, SourceSet};
+use crate::{Decimal, Directive, Entry, Result, StableHasher};
+use anyhow::Context;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Default)]
+pub(crate) struct FileCache {
+    files: HashMap<PathBuf, CachedFile>,
+    /// Directives printed by each staging command on its last run, by source index.
+    pub(crate) command_outputs: HashMap<usize, Vec<Directive>>,
+}
+
+struct CachedFile {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+    directives: Vec<Directive>,
+    /// Files included from this one, resolved against its directory.
+    includes: Vec<PathBuf>,
+}
+
+impl FileCache {
+    /// Read `paths` and all files included from them, like [`beancount_parser::read_files_v2`].
+    pub(crate) fn read_files(&mut self, paths: &[PathBuf]) -> Result<(Vec<Directive>, SourceSet)> {
+        let mut directives = Vec::new();
+        let mut loaded = SourceSet::new();
+        let mut pending = paths.to_vec();
+        while let Some(path) = pending.pop() {
+            if !loaded.insert(path.clone()) {
+                continue;
+            }
+            let file = self.read_file(&path)?;
+            directives.extend(file.directives.iter().cloned());
+            pending.extend(file.includes.iter().cloned());
+        }
+        crate::sorting::sort_dedup_directives(&mut directives);
+
+        Ok((directives, loaded))
+    }
+
+    /// The files `paths` consist of, including included files, without their directives.
+    pub(crate) fn sourceset(&mut self, paths: &[PathBuf]) -> Result<SourceSet> {
+        let mut loaded = SourceSet::new();
+        let mut pending = paths.to_vec();
+        while let Some(path) = pending.pop() {
+            if !loaded.insert(path.clone()) {
+                continue;
+            }
+            pending.extend(self.read_file(&path)?.includes.iter().cloned());
+        }
+        Ok(loaded)
+    }
+
+    /// Forget files which are no longer part of any of `sourcesets`.
+    pub(crate) fn retain(&mut self, sourcesets: &[&SourceSet]) {
+        self.files
+            .retain(|path, _| sourcesets.iter().any(|set| set.contains(path)));
+    }
+
+    fn read_file(&mut self, path: &Path) -> Result<&CachedFile> {
+        let metadata = std::fs::metadata(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+        let unchanged = self.files.get(path).is_some_and(|cached| {
+            cached.modified.is_some() && cached.modified == modified && cached.len == len
+        });
+        if unchanged {
+            return Ok(&self.files[path]);
+        }
+
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // Touched, but not changed
+        if let Some(cached) = self.files.get_mut(path)
+            && cached.hash == hash
+        {
+            cached.modified = modified;
+            cached.len = len;
+            return Ok(&self.files[path]);
+        }
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        let mut directives = Vec::new();
+        let mut includes = Vec::new();
+        for entry in beancount_parser::parse_iter::<Decimal>(&source) {
+            match entry.with_context(|| format!("Failed to parse file: {}", path.display()))? {
+                Entry::Directive(directive) => directives.push(directive),
+                Entry::Include(include) => includes.push(base.join(include)),
+                _ => {}
+            }
+        }
+        tracing::debug!("Parsed {}", path.display());
+
+        let file = CachedFile {
+            modified,
+            len,
+            hash,
+            directives,
+            includes,
+        };
+        self.files.insert(path.to_owned(), file);
+        Ok(&self.files[path])
+    }
+}
+
+/// Key for the cached output of `command`, which changes whenever a file below one of the
+/// `inputs` directories is added, removed or modified.
+///
+/// The key is part of the cache file name, so it's computed with a [`StableHasher`] over
+/// plain bytes and numbers to stay the same across toolchain updates.
+pub(crate) fn command_cache_key(
+    command: &[String],
+    cwd: &Path,
+    env: &BTreeMap<String, String>,
+    inputs: &[PathBuf],
+) -> Result<u64> {
+    let mut hasher = StableHasher::new();
+    hasher.write_u64(command.len() as u64);
+    for arg in command {
+        hasher.write_str(arg);
+    }
+    hasher.write_u64(env.len() as u64);
+    for (key, value) in env {
+        hasher.write_str(key);
+        hasher.write_str(value);
+    }
+    for input in inputs {
+        hash_modification_times(&cwd.join(input), &mut hasher)
+            .with_context(|| format!("Failed to read staging input: {}", input.display()))?;
+    }
+    Ok(hasher.finish())
+}
+
+fn hash_modification_times(path: &Path, hasher: &mut StableHasher) -> std::io::Result<()> {
+    let metadata = std::fs::metadata(path)?;
+    hasher.write_bytes(path.as_os_str().as_encoded_bytes());
+    hasher.write_u128(
+        metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_nanos()),
+    );
+    hasher.write_u64(metadata.len());
+    if metadata.is_dir() {
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort();
+        // Writing the cache must not invalidate it, in case it's inside an input
+        entries.retain(|entry| !entry.ends_with(CACHE_DIR));
+        for entry in entries {
+            hash_modification_times(&entry, hasher)?;
+        }
+    }
+    Ok(())
+}
+
+/// Outputs are named after their staging source, so sources sharing a directory don't
+/// replace each other's outputs.
+fn command_output_path(cwd: &Path, name: &str, key: u64) -> PathBuf {
+    cwd.join(CACHE_DIR)
+        .join(format!("{name}-{key:016x}.beancount"))
+}
+
+fn is_command_output_of(file_name: &str, name: &str) -> bool {
+    file_name
+        .strip_prefix(name)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .and_then(|rest| rest.strip_suffix(".beancount"))
+        .is_some_and(|key| key.len() == 16 && key.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// The cached output of the staging command of source `name` for `key`, if there is one.
+pub(crate) fn read_command_output(cwd: &Path, name: &str, key: u64) -> Result<Option<String>> {
+    let path = command_output_path(cwd, name, key);
+    match std::fs::read_to_string(&path) {
+        Ok(output) => Ok(Some(output)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read file: {}", path.display())),
+    }
+}
+
+/// Cache the output of the staging command of source `name` for `key`, replacing outdated
+/// outputs.
+pub(crate) fn write_command_output(cwd: &Path, name: &str, key: u64, output: &str) -> Result<()> {
+    let path = command_output_path(cwd, name, key);
+    let dir = path.parent().expect("cache file is in a directory");
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if is_command_output_of(&entry.file_name().to_string_lossy(), name) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    std::fs::write(&path, output)
+        .with_context(|| format!("Failed to write file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("beancount-staging-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn payees(directives: &[Directive]) -> Vec<String> {
+        directives
+            .iter()
+            .filter_map(|directive| match &directive.content {
+                crate::DirectiveContent::Transaction(txn) => txn.payee.clone(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rereads_changed_included_file() {
+        let dir = temp_dir("file-cache");
+        let main = dir.join("main.beancount");
+        let included = dir.join("sub/included.beancount");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(
+            &main,
+            "include \"sub/included.beancount\"\n\n2025-01-01 * \"Main\"\n  Assets:Cash -1.00 EUR\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &included,
+            "2025-01-02 * \"Included\"\n  Assets:Cash -2.00 EUR\n",
+        )
+        .unwrap();
+
+        let mut cache = FileCache::default();
+        let (directives, loaded) = cache.read_files(std::slice::from_ref(&main)).unwrap();
+        assert_eq!(payees(&directives), ["Main", "Included"]);
+        assert!(loaded.contains(&included));
+
+        std::fs::write(
+            &included,
+            "2025-01-02 * \"Included\"\n  Assets:Cash -2.00 EUR\n\n2025-01-03 * \"Appended\"\n  Assets:Cash -3.00 EUR\n",
+        )
+        .unwrap();
+        let (directives, _) = cache.read_files(std::slice::from_ref(&main)).unwrap();
+        assert_eq!(payees(&directives), ["Main", "Included", "Appended"]);
+    }
+
+    #[test]
+    fn command_output_cached_until_inputs_change() {
+        let dir = temp_dir("command-cache");
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        let command = ["import".to_string()];
+        let env = BTreeMap::new();
+        let inputs = [PathBuf::from("data")];
+
+        let key = command_cache_key(&command, &dir, &env, &inputs).unwrap();
+        assert_eq!(read_command_output(&dir, "staging", key).unwrap(), None);
+        write_command_output(&dir, "staging", key, "output").unwrap();
+        write_command_output(&dir, "paypal", key, "paypal output").unwrap();
+        assert_eq!(
+            read_command_output(&dir, "staging", key)
+                .unwrap()
+                .as_deref(),
+            Some("output")
+        );
+
+        std::fs::write(dir.join("data/statement.csv"), "new statement").unwrap();
+        let new_key = command_cache_key(&command, &dir, &env, &inputs).unwrap();
+        assert_ne!(key, new_key);
+        write_command_output(&dir, "staging", new_key, "new output").unwrap();
+        // Outdated outputs are removed, but not those of other sources
+        assert_eq!(read_command_output(&dir, "staging", key).unwrap(), None);
+        assert_eq!(
+            read_command_output(&dir, "paypal", key).unwrap().as_deref(),
+            Some("paypal output")
+        );
+    }
+}
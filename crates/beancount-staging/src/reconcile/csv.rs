@@ -0,0 +1,439 @@
+//! Staging entries read directly from a bank's CSV export, for banks where a beangulp
+//! importer would be overkill.
+
+use super::SourceSet;
+use crate::{Decimal, Directive, Result};
+use anyhow::Context;
+use beancount_parser::Date;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// How to turn the rows of a CSV export into staging transactions.
+#[derive(Debug, Clone)]
+pub struct CsvProfile {
+    pub files: Vec<PathBuf>,
+    /// Account of the imported posting, e.g. `Assets:Bank:Checking`.
+    pub account: String,
+    pub date: CsvColumn,
+    /// Format of the date column, using `%Y`, `%y`, `%m` and `%d`, e.g. `%d.%m.%Y`.
+    pub date_format: String,
+    pub amount: CsvAmount,
+    pub amount_sign: AmountSign,
+    pub payee: Option<CsvColumn>,
+    /// Joined with spaces, skipping empty values.
+    pub narration: Vec<CsvColumn>,
+    pub currency: String,
+    /// Overrides `currency` for rows where it is not empty.
+    pub currency_column: Option<CsvColumn>,
+    pub delimiter: char,
+    pub decimal_separator: char,
+    /// Label of the file encoding, like `utf-8` or `windows-1252`.
+    pub encoding: String,
+    /// Lines before the header, like account details in the export.
+    pub skip_rows: usize,
+    /// Whether the first row names the columns.
+    pub has_header: bool,
+}
+
+impl Default for CsvProfile {
+    fn default() -> Self {
+        CsvProfile {
+            files: Vec::new(),
+            account: String::new(),
+            date: CsvColumn::Index(0),
+            date_format: "%Y-%m-%d".to_string(),
+            amount: CsvAmount::Signed(CsvColumn::Index(1)),
+            amount_sign: AmountSign::default(),
+            payee: None,
+            narration: Vec::new(),
+            currency: "EUR".to_string(),
+            currency_column: None,
+            delimiter: ',',
+            decimal_separator: '.',
+            encoding: "utf-8".to_string(),
+            skip_rows: 0,
+            has_header: true,
+        }
+    }
+}
+
+/// A column, by its name in the header or its index starting at 0.
+#[derive(Debug, Clone)]
+pub enum CsvColumn {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum CsvAmount {
+    /// One column with positive and negative amounts.
+    Signed(CsvColumn),
+    /// Separate columns for money going out and coming in, both without sign.
+    DebitCredit { debit: CsvColumn, credit: CsvColumn },
+}
+
+/// What a positive amount in the CSV means for the imported account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AmountSign {
+    /// Positive amounts are deposits, like in most bank exports.
+    #[default]
+    Normal,
+    /// Positive amounts are withdrawals, like in some credit card exports.
+    Inverted,
+}
+
+/// Read the staging transactions of all files of `profile`.
+pub(crate) fn read_csv_files(profile: &CsvProfile) -> Result<(Vec<Directive>, SourceSet)> {
+    anyhow::ensure!(!profile.account.is_empty(), "CSV import needs an account");
+    let encoding = encoding_rs::Encoding::for_label(profile.encoding.as_bytes())
+        .with_context(|| format!("Unknown encoding: {}", profile.encoding))?;
+
+    let mut directives = Vec::new();
+    let mut sourceset = SourceSet::new();
+    for path in &profile.files {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let (text, _, had_errors) = encoding.decode(&bytes);
+        if had_errors {
+            tracing::warn!(
+                "{} is not valid {}, some characters were replaced",
+                path.display(),
+                profile.encoding
+            );
+        }
+        directives.extend(parse_csv(&text, profile, path)?);
+        sourceset.insert(path.clone());
+    }
+    crate::sorting::sort_dedup_directives(&mut directives);
+
+    Ok((directives, sourceset))
+}
+
+fn parse_csv(text: &str, profile: &CsvProfile, path: &Path) -> Result<Vec<Directive>> {
+    let text = skip_lines(text, profile.skip_rows);
+    let mut records = read_records(text, profile.delimiter).into_iter();
+
+    let header: HashMap<String, usize> = if profile.has_header {
+        records
+            .next()
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name.trim().to_string(), i))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let column = |column: &CsvColumn| -> Result<usize> {
+        match column {
+            CsvColumn::Index(index) => Ok(*index),
+            CsvColumn::Name(name) => header
+                .get(name)
+                .copied()
+                .with_context(|| format!("No column '{}' in {}", name, path.display())),
+        }
+    };
+    let date = column(&profile.date)?;
+    let amount = match &profile.amount {
+        CsvAmount::Signed(amount) => Amount::Signed(column(amount)?),
+        CsvAmount::DebitCredit { debit, credit } => Amount::DebitCredit {
+            debit: column(debit)?,
+            credit: column(credit)?,
+        },
+    };
+    let payee = profile.payee.as_ref().map(column).transpose()?;
+    let narration = profile
+        .narration
+        .iter()
+        .map(column)
+        .collect::<Result<Vec<_>>>()?;
+    let currency = profile.currency_column.as_ref().map(column).transpose()?;
+
+    let first_row = profile.skip_rows + usize::from(profile.has_header) + 1;
+    let mut directives = Vec::new();
+    for (i, record) in records.enumerate() {
+        if record.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        let field = |index: usize| record.get(index).map_or("", |value| value.trim());
+        let row = Row {
+            date: field(date),
+            amount: match amount {
+                Amount::Signed(column) => Amount::Signed(field(column)),
+                Amount::DebitCredit { debit, credit } => Amount::DebitCredit {
+                    debit: field(debit),
+                    credit: field(credit),
+                },
+            },
+            payee: payee.map(field).filter(|payee| !payee.is_empty()),
+            narration: narration
+                .iter()
+                .map(|&column| field(column))
+                .filter(|value| !value.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+            currency: currency
+                .map(field)
+                .filter(|currency| !currency.is_empty())
+                .unwrap_or(profile.currency.as_str()),
+        };
+        let directive = row_directive(&row, profile)
+            .with_context(|| format!("Invalid row {} in {}", first_row + i, path.display()))?;
+        directives.push(directive);
+    }
+    Ok(directives)
+}
+
+/// [`CsvAmount`] with resolved column indices, or the values of a row.
+#[derive(Clone, Copy)]
+enum Amount<T> {
+    Signed(T),
+    DebitCredit { debit: T, credit: T },
+}
+
+struct Row<'a> {
+    date: &'a str,
+    amount: Amount<&'a str>,
+    payee: Option<&'a str>,
+    narration: String,
+    currency: &'a str,
+}
+
+/// Render the row as a beancount transaction and let the parser read it.
+fn row_directive(row: &Row<'_>, profile: &CsvProfile) -> Result<Directive> {
+    let date = parse_csv_date(row.date, &profile.date_format)?;
+    let mut amount = match row.amount {
+        Amount::Signed(amount) => parse_amount(amount, profile)?,
+        Amount::DebitCredit { debit, credit: "" } => -parse_amount(debit, profile)?,
+        Amount::DebitCredit { debit: "", credit } => parse_amount(credit, profile)?,
+        Amount::DebitCredit { debit, credit } => {
+            parse_amount(credit, profile)? - parse_amount(debit, profile)?
+        }
+    };
+    if profile.amount_sign == AmountSign::Inverted {
+        amount = -amount;
+    }
+
+    let mut text = format!("{date} !");
+    if let Some(payee) = row.payee {
+        text.push_str(&format!(" {}", quote(payee)));
+    }
+    text.push_str(&format!(
+        " {}\n  {} {} {}\n",
+        quote(&row.narration),
+        profile.account,
+        amount,
+        row.currency
+    ));
+
+    beancount_parser::parse::<Decimal>(&text)
+        .ok()
+        .and_then(|parsed| parsed.directives.into_iter().next())
+        .with_context(|| format!("Failed to parse generated transaction:\n{}", text))
+}
+
+pub(super) fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace(['\r', '\n'], " ");
+    format!("\"{}\"", escaped)
+}
+
+/// Parse an amount like `-1.234,56` with the configured decimal separator.
+fn parse_amount(value: &str, profile: &CsvProfile) -> Result<Decimal> {
+    let normalized: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '+')
+        .filter_map(|c| match c {
+            c if c == profile.decimal_separator => Some('.'),
+            // Thousands separators
+            '.' | ',' | '\'' => None,
+            c => Some(c),
+        })
+        .collect();
+    normalized
+        .parse()
+        .with_context(|| format!("Invalid amount: '{}'", value))
+}
+
+fn parse_csv_date(value: &str, format: &str) -> Result<Date> {
+    let (mut year, mut month, mut day) = (None, None, None);
+    let mut input = value;
+    let mut format_chars = format.chars();
+    while let Some(c) = format_chars.next() {
+        if c != '%' {
+            input = input
+                .strip_prefix(c)
+                .with_context(|| format!("Date '{}' doesn't match format '{}'", value, format))?;
+            continue;
+        }
+        let spec = format_chars.next();
+        let max_digits = if spec == Some('Y') { 4 } else { 2 };
+        let digits = input
+            .chars()
+            .take(max_digits)
+            .take_while(char::is_ascii_digit)
+            .count();
+        anyhow::ensure!(
+            digits > 0,
+            "Date '{}' doesn't match format '{}'",
+            value,
+            format
+        );
+        let number: u32 = input[..digits].parse()?;
+        input = &input[digits..];
+        match spec {
+            Some('Y') => year = Some(number),
+            Some('y') => year = Some(2000 + number),
+            Some('m') => month = Some(number),
+            Some('d') => day = Some(number),
+            _ => anyhow::bail!("Unsupported date format: '{}'", format),
+        }
+    }
+    anyhow::ensure!(
+        input.is_empty(),
+        "Date '{}' doesn't match format '{}'",
+        value,
+        format
+    );
+
+    match (year, month, day) {
+        (Some(year), Some(month), Some(day)) => {
+            crate::parse_date(&format!("{year:04}-{month:02}-{day:02}"))
+        }
+        _ => anyhow::bail!("Date format '{}' needs %Y or %y, %m and %d", format),
+    }
+}
+
+fn skip_lines(text: &str, count: usize) -> &str {
+    let skipped: usize = text.split_inclusive('\n').take(count).map(str::len).sum();
+    &text[skipped..]
+}
+
+/// Split CSV text into records, with `"` quoting fields containing delimiters, quotes
+/// (doubled) or line breaks.
+fn read_records(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            c if in_quotes => field.push(c),
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(directives: &[Directive]) -> String {
+        directives
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    #[test]
+    fn reads_quoted_fields() {
+        let records = read_records("a,\"b,\"\"c\"\"\"\r\n\"multi\nline\",d", ',');
+        assert_eq!(records, [vec!["a", "b,\"c\""], vec!["multi\nline", "d"]]);
+    }
+
+    #[test]
+    fn reads_german_bank_export() {
+        let text = "Konto;DE00 1234\n\nBuchungstag;Empfänger;Verwendungszweck;Betrag;Währung\n\
+            02.01.2025;Bäckerei;\"Brot; Brötchen\";-3,50;EUR\n\
+            31.01.2025;ACME Corp;Gehalt Januar;1.234,00;EUR\n";
+        let profile = CsvProfile {
+            account: "Assets:Bank:Checking".to_string(),
+            date: CsvColumn::Name("Buchungstag".to_string()),
+            date_format: "%d.%m.%Y".to_string(),
+            amount: CsvAmount::Signed(CsvColumn::Name("Betrag".to_string())),
+            payee: Some(CsvColumn::Name("Empfänger".to_string())),
+            narration: vec![CsvColumn::Name("Verwendungszweck".to_string())],
+            currency_column: Some(CsvColumn::Name("Währung".to_string())),
+            delimiter: ';',
+            decimal_separator: ',',
+            skip_rows: 2,
+            ..Default::default()
+        };
+
+        let directives = parse_csv(text, &profile, Path::new("bank.csv")).unwrap();
+        insta::assert_snapshot!(render(&directives), @r#"
+        2025-01-02 ! "Bäckerei" "Brot; Brötchen"
+          Assets:Bank:Checking -3.50 EUR
+
+        2025-01-31 ! "ACME Corp" "Gehalt Januar"
+          Assets:Bank:Checking 1234.00 EUR
+        "#);
+    }
+
+    #[test]
+    fn reads_debit_credit_columns_without_header() {
+        let text = "1/5/25,Card payment,12.00,\n1/6/25,Refund,,2.50\n";
+        let profile = CsvProfile {
+            account: "Liabilities:CreditCard".to_string(),
+            date_format: "%m/%d/%y".to_string(),
+            amount: CsvAmount::DebitCredit {
+                debit: CsvColumn::Index(2),
+                credit: CsvColumn::Index(3),
+            },
+            narration: vec![CsvColumn::Index(1)],
+            currency: "USD".to_string(),
+            has_header: false,
+            ..Default::default()
+        };
+
+        let directives = parse_csv(text, &profile, Path::new("card.csv")).unwrap();
+        insta::assert_snapshot!(render(&directives), @r#"
+        2025-01-05 ! "Card payment"
+          Liabilities:CreditCard -12.00 USD
+
+        2025-01-06 ! "Refund"
+          Liabilities:CreditCard 2.50 USD
+        "#);
+
+        let inverted = CsvProfile {
+            amount_sign: AmountSign::Inverted,
+            ..profile
+        };
+        let directives = parse_csv(text, &inverted, Path::new("card.csv")).unwrap();
+        assert!(render(&directives).contains("Liabilities:CreditCard 12.00 USD"));
+    }
+
+    #[test]
+    fn reports_invalid_row() {
+        let text = "date,amount\n2025-01-02,1.00\n2025/01/03,2.00\n";
+        let profile = CsvProfile {
+            account: "Assets:Bank".to_string(),
+            date: CsvColumn::Name("date".to_string()),
+            amount: CsvAmount::Signed(CsvColumn::Name("amount".to_string())),
+            ..Default::default()
+        };
+
+        let error = parse_csv(text, &profile, Path::new("bank.csv")).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid row 3 in bank.csv");
+    }
+}
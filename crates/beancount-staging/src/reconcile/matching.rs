@@ -1,16 +1,32 @@
-use crate::{Directive, DirectiveContent, Transaction};
+use crate::{Decimal, Directive, DirectiveContent, Posting, Transaction};
+use beancount_parser::Date;
 
 /// Reasons why two transactions didn't match
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MismatchReason {
     DifferentDirectiveType,
-    DifferentPayee { journal: String, staging: String },
-    DifferentNarration { journal: String, staging: String },
-    DifferentAccount { journal: String, staging: String },
+    /// Same directive type, but the contents (e.g. account, amount, text) differ
+    DifferentContent,
+    DifferentPayee {
+        journal: String,
+        staging: String,
+    },
+    DifferentNarration {
+        journal: String,
+        staging: String,
+    },
+    DifferentAccount {
+        journal: String,
+        staging: String,
+    },
     DifferentAmount,
     DifferentCost,
     DifferentPrice,
     NoPrimaryPosting,
+    /// A staging posting with a cost or price (e.g. a bought lot) has no equivalent in the journal
+    MissingLeg {
+        account: String,
+    },
 }
 
 fn journal_matches_staging_transaction(
@@ -22,105 +38,441 @@ fn journal_matches_staging_transaction(
     // tags can be anything
     // links can be anything
 
-    // The primary account (first posting) must match exactly
+    postings_match(journal, staging)?;
+
+    let (journal_payee, journal_narration) = journal_source_texts(journal, journal_directive);
+    texts_match(journal_payee, journal_narration, staging)
+}
+
+/// Compare the imported payee and narration of a journal entry with the staging ones.
+fn texts_match(
+    journal_payee: Option<&str>,
+    journal_narration: Option<&str>,
+    staging: &Transaction,
+) -> Result<(), MismatchReason> {
+    // Normalize empty strings to None for comparison
+    let journal_payee = journal_payee.filter(|s| !s.is_empty());
+    let staging_payee = staging.payee.as_deref().filter(|s| !s.is_empty());
+    let journal_narration = journal_narration.filter(|s| !s.is_empty());
+    let staging_narration = staging.narration.as_deref().filter(|s| !s.is_empty());
+
+    // Normalize newlines for narration comparison
+    let journal_narration = journal_narration.map(normalize_whitespace);
+    let staging_narration = staging_narration.map(normalize_whitespace);
+
+    if journal_payee != staging_payee {
+        return Err(MismatchReason::DifferentPayee {
+            journal: journal_payee.unwrap_or("").to_string(),
+            staging: staging_payee.unwrap_or("").to_string(),
+        });
+    }
+
+    if journal_narration != staging_narration {
+        return Err(MismatchReason::DifferentNarration {
+            journal: journal_narration.unwrap_or_default(),
+            staging: staging_narration.unwrap_or_default(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Check if a posting other than the primary one matches the primary posting of staging.
+///
+/// Such a leg was imported from a separate staging entry (e.g. the receiving side of a
+/// merged transfer) and carries that entry's payee and narration as its own metadata.
+fn journal_leg_matches_staging_transaction(
+    journal_leg: &Posting,
+    staging: &Transaction,
+) -> Result<(), MismatchReason> {
+    let staging_primary = staging
+        .postings
+        .first()
+        .ok_or(MismatchReason::NoPrimaryPosting)?;
+    posting_matches(journal_leg, staging_primary)?;
+
+    let source_meta = |key: &str| journal_leg.metadata.get(key).and_then(|x| x.as_string());
+    texts_match(
+        source_meta("source_payee"),
+        source_meta("source_desc"),
+        staging,
+    )
+}
+
+/// The primary account (first posting) must match exactly, including amount, cost and price.
+///
+/// Of the other postings, only legs with a cost or price (e.g. the lot of a brokerage buy)
+/// have to be in the journal. Everything else, like fee or capital gains postings, may differ.
+fn postings_match(journal: &Transaction, staging: &Transaction) -> Result<(), MismatchReason> {
     let (Some(staging_primary), Some(journal_primary)) =
         (staging.postings.first(), journal.postings.first())
     else {
         return Err(MismatchReason::NoPrimaryPosting);
     };
 
-    if staging_primary.account != journal_primary.account {
+    posting_matches(journal_primary, staging_primary)?;
+
+    // Other postings in staging are allowed to differ or be absent in journal
+    // (user might reorganize/edit expense accounts between staging and journal),
+    // except for legs with a cost or price which describe what was actually traded.
+    for staging_leg in staging.postings.iter().skip(1) {
+        if staging_leg.cost.is_none() && staging_leg.price.is_none() {
+            continue;
+        }
+        let found = journal.postings.iter().skip(1).any(|journal_leg| {
+            journal_leg.account == staging_leg.account
+                && journal_leg.amount == staging_leg.amount
+                && cost_matches(journal_leg, staging_leg)
+                && price_matches(journal_leg, staging_leg)
+        });
+        if !found {
+            return Err(MismatchReason::MissingLeg {
+                account: staging_leg.account.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Account, amount, cost and price of both postings must be the same.
+fn posting_matches(journal: &Posting, staging: &Posting) -> Result<(), MismatchReason> {
+    if staging.account != journal.account {
         return Err(MismatchReason::DifferentAccount {
-            journal: journal_primary.account.to_string(),
-            staging: staging_primary.account.to_string(),
+            journal: journal.account.to_string(),
+            staging: staging.account.to_string(),
         });
     }
 
-    if staging_primary.amount != journal_primary.amount {
+    if staging.amount != journal.amount {
         return Err(MismatchReason::DifferentAmount);
     }
 
-    if staging_primary.cost != journal_primary.cost {
+    if !cost_matches(journal, staging) {
         return Err(MismatchReason::DifferentCost);
     }
 
-    if staging_primary.price != journal_primary.price {
+    if !price_matches(journal, staging) {
         return Err(MismatchReason::DifferentPrice);
     }
 
-    // Other postings in staging are allowed to differ or be absent in journal
-    // (user might reorganize/edit expense accounts between staging and journal)
+    Ok(())
+}
 
-    // Non-primary postings shouldn't have cost or price
-    for posting in staging.postings.iter().skip(1) {
-        if posting.cost.is_some() || posting.price.is_some() {
-            unimplemented!("Non-primary postings with cost or price are not yet supported");
-        }
+/// Whether the journal posting holds the same lot as the staging posting.
+///
+/// Per-unit (`{}`) and total (`{# }`) costs match if they amount to the same total cost.
+/// An empty staging cost spec (`{}`) leaves the lot to beancount's booking and matches any
+/// journal cost, and lot dates are only compared if both sides specify them.
+fn cost_matches(journal: &Posting, staging: &Posting) -> bool {
+    let (Some(journal_cost), Some(staging_cost)) = (&journal.cost, &staging.cost) else {
+        return journal.cost.is_none() && staging.cost.is_none();
+    };
+
+    if let (Some(journal_date), Some(staging_date)) = (&journal_cost.date, &staging_cost.date)
+        && journal_date != staging_date
+    {
+        return false;
+    }
+
+    match crate::cost_total(staging) {
+        None => true,
+        Some(staging_total) => crate::cost_total(journal)
+            .is_some_and(|journal_total| totals_match(journal_total, staging_total)),
+    }
+}
+
+/// Whether the journal posting has the same price as the staging posting.
+///
+/// Per-unit (`@`) and total (`@@`) prices match if they amount to the same total price.
+fn price_matches(journal: &Posting, staging: &Posting) -> bool {
+    match (crate::price_total(journal), crate::price_total(staging)) {
+        (Some(journal_total), Some(staging_total)) => totals_match(journal_total, staging_total),
+        (None, None) => journal.price.is_none() && staging.price.is_none(),
+        _ => false,
+    }
+}
+
+/// Compare two totals, allowing for rounding of per-unit values.
+fn totals_match((a, a_currency): (Decimal, &str), (b, b_currency): (Decimal, &str)) -> bool {
+    a_currency == b_currency && (a - b).abs() <= Decimal::new(5, 3)
+}
+
+/// Payee and narration of a journal transaction as they were imported.
+///
+/// Checks directive metadata first (new location), then posting metadata (old location),
+/// then the transaction fields.
+fn journal_source_texts<'a>(
+    journal: &'a Transaction,
+    journal_directive: &'a Directive,
+) -> (Option<&'a str>, Option<&'a str>) {
+    let source_meta = |key: &str| {
+        journal_directive
+            .metadata
+            .get(key)
+            .and_then(|x| x.as_string())
+            .or_else(|| {
+                journal
+                    .postings
+                    .first()?
+                    .metadata
+                    .get(key)
+                    .and_then(|x| x.as_string())
+            })
+    };
+
+    (
+        source_meta("source_payee").or(journal.payee.as_deref()),
+        source_meta("source_desc").or(journal.narration.as_deref()),
+    )
+}
+
+/// Options for tolerant matching of staging entries which don't match any journal entry exactly.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchingOptions {
+    /// Journal transactions up to this many days before or after the staging date can match.
+    pub date_window_days: u32,
+    /// Minimum similarity (between 0 and 1) of payee and narration.
+    /// `None` requires them to be equal, like exact matching does.
+    pub min_similarity: Option<f64>,
+    /// Pair one-legged staging transactions moving the same amount between two accounts
+    /// within this many days into a single transfer. `None` disables pairing.
+    pub transfer_window_days: Option<u32>,
+}
+
+impl MatchingOptions {
+    /// Whether tolerant matching against the journal is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.date_window_days > 0 || self.min_similarity.is_some()
     }
-    for posting in journal.postings.iter().skip(1) {
-        if posting.cost.is_some() || posting.price.is_some() {
-            unimplemented!("Non-primary postings with cost or price are not yet supported");
+}
+
+/// Check if journal matches staging under the tolerant rules of `options`.
+///
+/// The primary posting still has to match exactly, only the date and the texts may differ.
+/// Returns the similarity of payee and narration (`1.0` if they are equal).
+pub fn journal_fuzzy_matches_staging(
+    journal: &Directive,
+    staging: &Directive,
+    options: &MatchingOptions,
+) -> Option<f64> {
+    let (DirectiveContent::Transaction(journal_txn), DirectiveContent::Transaction(staging_txn)) =
+        (&journal.content, &staging.content)
+    else {
+        return None;
+    };
+
+    if days_between(source_date(journal), staging.date) > i64::from(options.date_window_days) {
+        return None;
+    }
+    postings_match(journal_txn, staging_txn).ok()?;
+
+    let (journal_payee, journal_narration) = journal_source_texts(journal_txn, journal);
+    let journal_text = normalize_whitespace(&format!(
+        "{} {}",
+        journal_payee.unwrap_or(""),
+        journal_narration.unwrap_or("")
+    ));
+    let staging_text = normalize_whitespace(&format!(
+        "{} {}",
+        staging_txn.payee.as_deref().unwrap_or(""),
+        staging_txn.narration.as_deref().unwrap_or("")
+    ));
+
+    let similarity = text_similarity(&journal_text, &staging_text);
+    let min_similarity = options.min_similarity.unwrap_or(1.0);
+    (similarity >= min_similarity).then_some(similarity)
+}
+
+/// Dice coefficient of the character bigrams of both strings, ignoring case.
+fn text_similarity(a: &str, b: &str) -> f64 {
+    let bigrams = |s: &str| {
+        let chars: Vec<char> = s.trim().to_lowercase().chars().collect();
+        let mut bigrams: Vec<(char, char)> = chars.windows(2).map(|w| (w[0], w[1])).collect();
+        bigrams.sort_unstable();
+        bigrams
+    };
+    let (a, b) = (bigrams(a), bigrams(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    // Count common bigrams by merging the sorted lists
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
         }
     }
 
-    let first_posting = journal.postings.first().expect("TODO: no accounts?");
+    2.0 * common as f64 / (a.len() + b.len()) as f64
+}
+
+/// Number of days between two dates, ignoring the order.
+pub(crate) fn days_between(a: Date, b: Date) -> i64 {
+    (crate::day_number(&a) - crate::day_number(&b)).abs()
+}
+
+/// The date under which a journal entry was imported.
+///
+/// Committing with a different date records the original one as `source_date` metadata,
+/// which is checked the same way as `source_payee`/`source_desc`.
+pub fn source_date(journal: &Directive) -> Date {
+    let first_posting = match &journal.content {
+        DirectiveContent::Transaction(txn) => txn.postings.first(),
+        _ => None,
+    };
 
-    // Check directive metadata first (new location), then posting metadata (old location), then transaction field
-    let journal_payee = journal_directive
+    journal
         .metadata
-        .get("source_payee")
+        .get("source_date")
         .and_then(|x| x.as_string())
         .or_else(|| {
-            first_posting
+            first_posting?
                 .metadata
-                .get("source_payee")
+                .get("source_date")
                 .and_then(|x| x.as_string())
         })
-        .or(journal.payee.as_deref());
+        .and_then(|date| crate::parse_date(date).ok())
+        .unwrap_or(journal.date)
+}
 
-    let journal_narration = journal_directive
-        .metadata
-        .get("source_desc")
-        .and_then(|x| x.as_string())
-        .or_else(|| {
-            first_posting
+/// The legs of a journal entry which were imported from a staging entry, with the date each
+/// one was imported under.
+///
+/// Leg `0` is the entry itself, matched by its primary posting. Other postings count as
+/// imported legs if they carry their own `source_desc` metadata, like the receiving side of
+/// a merged transfer. Their `source_date` defaults to the one of the entry.
+pub(crate) fn imported_legs(journal: &Directive) -> Vec<(usize, Date)> {
+    let date = source_date(journal);
+    let mut legs = vec![(0, date)];
+    if let DirectiveContent::Transaction(txn) = &journal.content {
+        for (leg, posting) in txn.postings.iter().enumerate().skip(1) {
+            if posting.metadata.get("source_desc").is_none() {
+                continue;
+            }
+            let leg_date = posting
                 .metadata
-                .get("source_desc")
+                .get("source_date")
                 .and_then(|x| x.as_string())
-        })
-        .or(journal.narration.as_deref());
+                .and_then(|date| crate::parse_date(date).ok())
+                .unwrap_or(date);
+            legs.push((leg, leg_date));
+        }
+    }
+    legs
+}
 
-    // Normalize empty strings to None for comparison
-    let journal_payee = journal_payee.filter(|s| !s.is_empty());
-    let staging_payee = staging.payee.as_deref().filter(|s| !s.is_empty());
-    let journal_narration = journal_narration.filter(|s| !s.is_empty());
-    let staging_narration = staging.narration.as_deref().filter(|s| !s.is_empty());
+/// Check if one imported leg (see [`imported_legs`]) of journal matches staging.
+pub(crate) fn journal_leg_matches_staging(
+    journal: &Directive,
+    leg: usize,
+    staging: &Directive,
+) -> Result<(), MismatchReason> {
+    if leg == 0 {
+        return journal_primary_matches_staging(journal, staging);
+    }
 
-    // Normalize newlines for narration comparison
-    let journal_narration = journal_narration.map(normalize_whitespace);
-    let staging_narration = staging_narration.map(normalize_whitespace);
+    let (DirectiveContent::Transaction(journal_txn), DirectiveContent::Transaction(staging_txn)) =
+        (&journal.content, &staging.content)
+    else {
+        return Err(MismatchReason::DifferentDirectiveType);
+    };
+    let journal_leg = journal_txn
+        .postings
+        .get(leg)
+        .ok_or(MismatchReason::NoPrimaryPosting)?;
+    journal_leg_matches_staging_transaction(journal_leg, staging_txn)
+}
 
-    if journal_payee != staging_payee {
-        return Err(MismatchReason::DifferentPayee {
-            journal: journal_payee.unwrap_or("").to_string(),
-            staging: staging_payee.unwrap_or("").to_string(),
-        });
-    }
+/// Check if several journal entries together match one staging entry.
+///
+/// This is how an imported payment split into several journal transactions is recognized:
+/// each of them keeps the imported payee and narration and has its primary posting in the
+/// staging account, and their amounts add up to the staging amount.
+pub(crate) fn journal_group_matches_staging(
+    journal: &[&Directive],
+    staging: &Directive,
+) -> Result<(), MismatchReason> {
+    let DirectiveContent::Transaction(staging_txn) = &staging.content else {
+        return Err(MismatchReason::DifferentDirectiveType);
+    };
+    let staging_primary = staging_txn
+        .postings
+        .first()
+        .ok_or(MismatchReason::NoPrimaryPosting)?;
+    let staging_amount = staging_primary
+        .amount
+        .as_ref()
+        .ok_or(MismatchReason::DifferentAmount)?;
+
+    let mut total = Decimal::ZERO;
+    for directive in journal {
+        let DirectiveContent::Transaction(txn) = &directive.content else {
+            return Err(MismatchReason::DifferentDirectiveType);
+        };
+        let primary = txn
+            .postings
+            .first()
+            .ok_or(MismatchReason::NoPrimaryPosting)?;
+        if primary.account != staging_primary.account {
+            return Err(MismatchReason::DifferentAccount {
+                journal: primary.account.to_string(),
+                staging: staging_primary.account.to_string(),
+            });
+        }
+        let amount = primary
+            .amount
+            .as_ref()
+            .filter(|amount| amount.currency.as_str() == staging_amount.currency.as_str())
+            .ok_or(MismatchReason::DifferentAmount)?;
+        if !cost_matches(primary, staging_primary) {
+            return Err(MismatchReason::DifferentCost);
+        }
+        if !price_matches(primary, staging_primary) {
+            return Err(MismatchReason::DifferentPrice);
+        }
+        total += amount.value;
 
-    if journal_narration != staging_narration {
-        return Err(MismatchReason::DifferentNarration {
-            journal: journal_narration.unwrap_or_default(),
-            staging: staging_narration.unwrap_or_default(),
-        });
+        let (payee, narration) = journal_source_texts(txn, directive);
+        texts_match(payee, narration, staging_txn)?;
     }
 
+    if total != staging_amount.value {
+        return Err(MismatchReason::DifferentAmount);
+    }
     Ok(())
 }
 
 /// Check if journal matches staging, returning a mismatch reason if they don't match
+///
+/// Staging matches if any imported leg of journal matches it. The reason reported is the
+/// one of the primary posting.
 pub fn journal_matches_staging(
     journal: &Directive,
     staging: &Directive,
+) -> Result<(), MismatchReason> {
+    let primary = journal_primary_matches_staging(journal, staging);
+    if primary.is_err()
+        && imported_legs(journal)
+            .into_iter()
+            .skip(1)
+            .any(|(leg, _)| journal_leg_matches_staging(journal, leg, staging).is_ok())
+    {
+        return Ok(());
+    }
+    primary
+}
+
+fn journal_primary_matches_staging(
+    journal: &Directive,
+    staging: &Directive,
 ) -> Result<(), MismatchReason> {
     if std::mem::discriminant(&journal.content) != std::mem::discriminant(&staging.content) {
         return Err(MismatchReason::DifferentDirectiveType);
@@ -137,15 +489,14 @@ pub fn journal_matches_staging(
         (DirectiveContent::Transaction(j), DirectiveContent::Transaction(s)) => {
             return journal_matches_staging_transaction(j, s, journal);
         }
-        _ => {
-            todo!("Journal: {}\nStaging: {}", journal, staging)
-        }
+        // Notes, documents, queries, custom directives, ...
+        (j, s) => j == s,
     };
 
     if matches {
         Ok(())
     } else {
-        Err(MismatchReason::DifferentDirectiveType)
+        Err(MismatchReason::DifferentContent)
     }
 }
 
@@ -176,6 +527,7 @@ impl std::fmt::Display for MismatchReason {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             MismatchReason::DifferentDirectiveType => write!(f, "Different directive"),
+            MismatchReason::DifferentContent => write!(f, "Different content"),
             MismatchReason::DifferentPayee { journal, staging } => {
                 write!(
                     f,
@@ -201,13 +553,19 @@ impl std::fmt::Display for MismatchReason {
             MismatchReason::DifferentCost => write!(f, "Different cost"),
             MismatchReason::DifferentPrice => write!(f, "Different price"),
             MismatchReason::NoPrimaryPosting => write!(f, "No primary posting"),
+            MismatchReason::MissingLeg { account } => {
+                write!(f, "Missing posting with cost or price: {}", account)
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Directive, Entry, Result, reconcile::matching::journal_matches_staging};
+    use crate::{
+        Directive, Entry, Result,
+        reconcile::matching::{MismatchReason, journal_matches_staging},
+    };
 
     #[track_caller]
     fn parse_single_entry(source: &str) -> Entry {
@@ -464,6 +822,78 @@ continued here"
         assert!(journal_matches_staging(&directive, &staging).is_err());
     }
 
+    #[test]
+    fn mismatch_reasons_instead_of_panics() {
+        let journal = parse_single_directive("2025-12-01 balance Assets:Checking  100.00 EUR\n");
+        let staging = parse_single_directive("2025-12-01 balance Assets:Checking  200.00 EUR\n");
+        assert_eq!(
+            journal_matches_staging(&journal, &staging),
+            Err(MismatchReason::DifferentContent)
+        );
+    }
+
+    #[test]
+    fn match_investment_legs() {
+        let staging = r#"
+2025-12-01 ! "Broker" "Buy ACME"
+    Assets:Broker:Cash  -1010.00 EUR
+    Assets:Broker:ACME  10 ACME {100.00 EUR}
+    Expenses:Fees  10.00 EUR
+"#;
+        // total cost instead of per-unit cost, fee booked to a different account
+        let journal = r#"
+2025-12-01 * "Broker" "Buy ACME"
+    Assets:Broker:Cash  -1010.00 EUR
+    Assets:Broker:ACME  10 ACME {# 1000.00 EUR}
+    Expenses:Broker:Fees  10.00 EUR
+"#;
+        let staging = parse_single_directive(staging);
+        let directive = parse_single_directive(journal);
+        assert!(journal_matches_staging(&directive, &staging).is_ok());
+
+        // a different lot doesn't match
+        let journal = r#"
+2025-12-01 * "Broker" "Buy ACME"
+    Assets:Broker:Cash  -1010.00 EUR
+    Assets:Broker:ACME  10 ACME {90.00 EUR}
+    Expenses:Fees  10.00 EUR
+"#;
+        let directive = parse_single_directive(journal);
+        assert_eq!(
+            journal_matches_staging(&directive, &staging),
+            Err(MismatchReason::MissingLeg {
+                account: "Assets:Broker:ACME".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn match_sale_with_price_and_gains() {
+        let staging = r#"
+2025-12-01 ! "Broker" "Sell ACME"
+    Assets:Broker:Cash  1200.00 EUR
+    Assets:Broker:ACME  -10 ACME {} @ 120.00 EUR
+"#;
+        // the journal books a specific lot, total price and a capital gains posting
+        let journal = r#"
+2025-12-01 * "Broker" "Sell ACME"
+    Assets:Broker:Cash  1200.00 EUR
+    Assets:Broker:ACME  -10 ACME {100.00 EUR} @@ 1200.00 EUR
+    Income:CapitalGains
+"#;
+        let staging = parse_single_directive(staging);
+        let directive = parse_single_directive(journal);
+        assert!(journal_matches_staging(&directive, &staging).is_ok());
+
+        // a journal entry with cost or price on the legs can still match a simple import
+        let simple_staging = r#"
+2025-12-01 ! "Broker" "Sell ACME"
+    Assets:Broker:Cash  1200.00 EUR
+"#;
+        let simple_staging = parse_single_directive(simple_staging);
+        assert!(journal_matches_staging(&directive, &simple_staging).is_ok());
+    }
+
     #[test]
     fn dont_match_empty_payee() {
         let journal = r#"
@@ -1,27 +1,89 @@
 //! Reconciling differences between existing journal entries and a full automatic import.
 
+mod cache;
+mod csv;
 pub(crate) mod matching;
+mod ofx;
+mod transfer;
 
-pub use matching::MismatchReason;
+pub use csv::{AmountSign, CsvAmount, CsvColumn, CsvProfile};
+pub use matching::{MatchingOptions, MismatchReason};
+pub use ofx::{FITID_KEY, OfxProfile};
+pub use transfer::merge_transfer;
 
 use crate::Result;
 use crate::utils::sort_merge_diff::{JoinResult, SortMergeDiff};
-use crate::{Decimal, Directive};
+use crate::{
+    CommitMode, CommitOverrides, CommitTargetRule, Decimal, Directive, Glob, SourceMetaTarget,
+};
+use beancount_parser::metadata::Value;
 use beancount_parser::{Date, DirectiveContent, Entry};
-use std::collections::{BTreeMap, BTreeSet, HashSet};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct ReconcileItem<'a> {
     pub item: ReconcileItemKind<'a>,
-    /// For `OnlyInStaging` items, contains reasons why the item didn't match journal items on the same date.
-    pub mismatch_reasons: Vec<(usize, &'a Directive, matching::MismatchReason)>,
+    /// For `OnlyInStaging` items, the journal legs on the same date which were still unmatched,
+    /// shared by the items of that date.
+    candidates: Option<Arc<[(&'a Directive, usize)]>>,
+}
+
+impl<'a> ReconcileItem<'a> {
+    fn new(item: ReconcileItemKind<'a>) -> Self {
+        ReconcileItem {
+            item,
+            candidates: None,
+        }
+    }
+
+    /// For `OnlyInStaging` items, reasons why the item didn't match journal items on the
+    /// same date.
+    ///
+    /// Computed on request, since checking every candidate is only needed for debugging.
+    pub fn mismatch_reasons(&self) -> Vec<(usize, &'a Directive, MismatchReason)> {
+        let ReconcileItemKind::OnlyInStaging(staging) = self.item else {
+            return Vec::new();
+        };
+        self.candidates
+            .iter()
+            .flat_map(|candidates| candidates.iter())
+            .enumerate()
+            .filter_map(|(idx, &(journal, leg))| {
+                let reason = matching::journal_leg_matches_staging(journal, leg, staging).err()?;
+                Some((idx, journal, reason))
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug)]
 pub enum ReconcileItemKind<'a> {
     OnlyInJournal(&'a Directive),
     OnlyInStaging(&'a Directive),
+    /// A staging entry matched a journal entry only with the tolerant rules of
+    /// [`MatchingOptions`], so the user should confirm it.
+    FuzzyMatch {
+        journal: &'a Directive,
+        staging: &'a Directive,
+        /// Similarity of payee and narration, between 0 and 1
+        similarity: f64,
+    },
+    /// Two one-legged staging entries which look like both sides of one transfer.
+    /// [`merge_transfer`] turns them into a single transaction to commit.
+    Transfer {
+        outgoing: &'a Directive,
+        incoming: &'a Directive,
+    },
+    /// Matched entries which are not one-to-one: several staging entries covered by one
+    /// journal entry (e.g. both sides of a merged transfer), or one staging entry split into
+    /// several journal entries sharing a link.
+    CombinedMatch {
+        journal: Vec<&'a Directive>,
+        staging: Vec<&'a Directive>,
+    },
 }
 
 pub type SourceSet = HashSet<PathBuf>;
@@ -29,32 +91,239 @@ pub type SourceSet = HashSet<PathBuf>;
 #[derive(Debug, Clone)]
 pub enum StagingSource {
     Files(Vec<PathBuf>),
-    Command { command: Vec<String>, cwd: PathBuf },
+    Command {
+        command: Vec<String>,
+        cwd: PathBuf,
+        /// Directories the command imports from, relative to `cwd`. If set, the output is
+        /// cached in [`CACHE_DIR`] until a file in them changes.
+        inputs: Vec<PathBuf>,
+        /// Glob patterns relative to `cwd` for files which should rerun the command when
+        /// they are added or changed, see [`ReconcileConfig::staging_watch`].
+        watch: Vec<String>,
+        /// Environment variables set for the command.
+        env: BTreeMap<String, String>,
+    },
+    /// Bank exports read without an importer.
+    Csv(CsvProfile),
+    /// OFX/QFX statement downloads.
+    Ofx(OfxProfile),
+}
+
+/// One of several staging sources, merged by [`ReconcileConfig::reread`].
+#[derive(Debug, Clone)]
+pub struct StagingSourceConfig {
+    /// If set, every directive of this source is tagged with it, see [`staging_origin`].
+    pub name: Option<String>,
+    pub source: StagingSource,
+    /// Target account suggested for entries of this source.
+    pub default_account: Option<String>,
+    /// Run the staging command of this source again in this interval while serving the
+    /// web UI.
+    pub refresh_interval: Option<Duration>,
+}
+
+impl From<StagingSource> for StagingSourceConfig {
+    fn from(source: StagingSource) -> Self {
+        StagingSourceConfig {
+            name: None,
+            source,
+            default_account: None,
+            refresh_interval: None,
+        }
+    }
+}
+
+/// Metadata key naming the staging source a directive came from.
+pub const STAGING_SOURCE_KEY: &str = "staging_source";
+
+/// The name of the staging source `directive` came from, if it has one.
+pub fn staging_origin(directive: &Directive) -> Option<&str> {
+    directive.metadata.get(STAGING_SOURCE_KEY)?.as_string()
+}
+
+/// Directory for cached data, relative to the config file.
+pub const CACHE_DIR: &str = ".beancount-staging";
+
+/// When [`ReconcileConfig::reread`] runs the staging command again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StagingRefresh {
+    /// Reuse the output of the last run, e.g. when only the journal changed.
+    Reuse,
+    /// Reuse the output cached on disk, unless the inputs of the command changed.
+    IfChanged,
+    /// Always run the command.
+    Force,
 }
 
 pub struct ReconcileConfig {
     pub journal_paths: Vec<PathBuf>,
-    pub staging_source: StagingSource,
+    pub staging_sources: Vec<StagingSourceConfig>,
+    /// Rules for committing into files other than the first journal file.
+    pub commit_targets: Vec<CommitTargetRule>,
+    pub commit_mode: CommitMode,
+    /// Opt-in tolerant matching for entries without an exact match.
+    pub matching: MatchingOptions,
+    /// File with staging entries which should never be imported, see
+    /// [`crate::ignore_staging`]. It is not part of the journal.
+    pub ignored_file: Option<PathBuf>,
+    cache: Mutex<cache::FileCache>,
 }
 impl ReconcileConfig {
     pub fn new(journal_paths: Vec<PathBuf>, staging_source: StagingSource) -> Self {
+        Self::with_sources(journal_paths, vec![staging_source.into()])
+    }
+
+    /// Merge the entries of several staging sources.
+    pub fn with_sources(
+        journal_paths: Vec<PathBuf>,
+        staging_sources: Vec<StagingSourceConfig>,
+    ) -> Self {
         ReconcileConfig {
             journal_paths,
-            staging_source,
+            staging_sources,
+            commit_targets: Vec::new(),
+            commit_mode: CommitMode::default(),
+            matching: MatchingOptions::default(),
+            ignored_file: None,
+            cache: Mutex::default(),
         }
     }
 
+    /// The journal file `directive` is committed to with `overrides`.
+    pub fn commit_target(
+        &self,
+        directive: &Directive,
+        overrides: &CommitOverrides<'_>,
+    ) -> Result<PathBuf> {
+        crate::resolve_committed_target(
+            directive,
+            overrides,
+            &self.commit_targets,
+            &self.journal_paths[0],
+        )
+    }
+
+    /// Commit directives into their target journal files, as configured.
+    pub fn commit_transactions(
+        &self,
+        entries: &[(&Directive, CommitOverrides<'_>)],
+        source_meta_target: SourceMetaTarget,
+    ) -> Result<()> {
+        let journal_files = self.cache.lock().unwrap().sourceset(&self.journal_paths)?;
+        crate::commit_transactions_to_targets(
+            entries,
+            source_meta_target,
+            self.commit_mode,
+            &self.journal_paths[0],
+            &journal_files,
+            &self.commit_targets,
+        )
+    }
+
+    /// Files to watch in addition to the staging source set, because they are inputs of
+    /// the staging command.
+    pub fn staging_watch(&self) -> Result<Vec<Glob>> {
+        let mut globs = Vec::new();
+        for source in &self.staging_sources {
+            if let StagingSource::Command { cwd, watch, .. } = &source.source {
+                for glob in watch {
+                    globs.push(Glob::new(glob, cwd)?);
+                }
+            }
+        }
+        Ok(globs)
+    }
+
+    /// The default target account of the staging source `directive` came from.
+    pub fn default_account(&self, directive: &Directive) -> Option<&str> {
+        let origin = staging_origin(directive);
+        self.staging_sources
+            .iter()
+            .find(|source| source.name.as_deref() == origin)?
+            .default_account
+            .as_deref()
+    }
+
+    /// Names of the staging sources, in configuration order.
+    pub fn staging_source_names(&self) -> Vec<&str> {
+        self.staging_sources
+            .iter()
+            .filter_map(|source| source.name.as_deref())
+            .collect()
+    }
+
+    /// Never import these staging directives again.
+    pub fn ignore(&self, directives: &[&Directive]) -> Result<()> {
+        use anyhow::Context;
+
+        let ignored_file = self
+            .ignored_file
+            .as_deref()
+            .context("No ignored_file configured")?;
+        crate::ignore_staging(directives, ignored_file)
+    }
+
     pub fn read(&self) -> Result<ReconcileState> {
-        let (journal, journal_sourceset) = read_directives_from_files(&self.journal_paths)?;
-        let (staging, staging_sourceset) = match &self.staging_source {
-            StagingSource::Files(paths) => read_directives_from_files(paths)?,
-            StagingSource::Command { command, cwd } => read_directives_from_command(command, cwd)?,
+        self.reread(StagingRefresh::IfChanged)
+    }
+
+    /// Read journal and staging again, e.g. after a file changed.
+    ///
+    /// Files are only parsed again if they changed since the last read. Whether the staging
+    /// command runs again is up to `refresh`.
+    pub fn reread(&self, refresh: StagingRefresh) -> Result<ReconcileState> {
+        self.reread_sources(|_| refresh)
+    }
+
+    /// Like [`Self::reread`], but decides for each staging source, by its index in
+    /// `staging_sources`, whether its command runs again.
+    pub fn reread_sources(
+        &self,
+        refresh: impl Fn(usize) -> StagingRefresh,
+    ) -> Result<ReconcileState> {
+        use anyhow::Context;
+
+        let mut cache = self.cache.lock().unwrap();
+        let (journal, mut journal_sourceset) = cache.read_files(&self.journal_paths)?;
+        let (ignored, ignored_sourceset) = match &self.ignored_file {
+            Some(path) if path.exists() => cache.read_files(std::slice::from_ref(path))?,
+            _ => Default::default(),
         };
+        // Changes to the ignored file are watched like journal changes
+        journal_sourceset.extend(ignored_sourceset);
+        let mut staging = Vec::new();
+        let mut staging_sourceset = SourceSet::new();
+        for (index, source) in self.staging_sources.iter().enumerate() {
+            let (mut directives, sourceset) =
+                read_staging_source(&mut cache, index, source, refresh(index)).with_context(
+                    || match &source.name {
+                        Some(name) => format!("Failed to read staging source '{}'", name),
+                        None => "Failed to read staging".to_string(),
+                    },
+                )?;
+            if let Some(name) = &source.name {
+                for directive in &mut directives {
+                    directive.metadata.insert(
+                        STAGING_SOURCE_KEY.parse().unwrap(),
+                        Value::String(name.clone()),
+                    );
+                }
+            }
+            staging.extend(directives);
+            staging_sourceset.extend(sourceset);
+        }
+        if self.staging_sources.len() > 1 {
+            crate::sorting::sort_dedup_directives(&mut staging);
+        }
+        cache.retain(&[&journal_sourceset, &staging_sourceset]);
+
         Ok(ReconcileState {
             journal_sourceset,
             staging_sourceset,
             journal,
             staging,
+            ignored,
+            matching: self.matching,
         })
     }
 }
@@ -66,16 +335,43 @@ pub struct ReconcileState {
 
     pub journal: Vec<Directive>,
     pub staging: Vec<Directive>,
+    /// Entries of the ignored file, they count as matched but aren't part of the journal.
+    pub ignored: Vec<Directive>,
+
+    pub matching: MatchingOptions,
 }
 impl ReconcileState {
     /// Try to associate all journal and staging items, returning a list of differences.
     ///
-    /// For items that don't match, also includes debug info about why they didn't match
-    /// journal items on the same date.
+    /// For `OnlyInStaging` items, [`ReconcileItem::mismatch_reasons`] explains why they didn't
+    /// match journal items on the same date.
     pub fn reconcile(&self) -> Result<Vec<ReconcileItem<'_>>> {
-        let journal = group_by_date(&self.journal);
-        let staging = group_by_date(&self.staging);
-        let results = reconcile(journal, staging);
+        // Ignored staging entries are matched against the ignored file first, and are then
+        // left out completely
+        let staging: Vec<&Directive> = if self.ignored.is_empty() {
+            self.staging.iter().collect()
+        } else {
+            reconcile(
+                group_journal_by_date(&self.ignored),
+                group_by_date(&self.staging),
+            )
+            .into_iter()
+            .filter_map(|item| match item.item {
+                ReconcileItemKind::OnlyInStaging(directive) => Some(directive),
+                _ => None,
+            })
+            .collect()
+        };
+
+        let journal = group_journal_by_date(&self.journal);
+        let staging = group_by_date(staging);
+        let mut results = reconcile(journal, staging);
+        if self.matching.is_enabled() {
+            results = match_fuzzy(results, &self.matching);
+        }
+        if let Some(window_days) = self.matching.transfer_window_days {
+            results = transfer::pair_transfers(results, window_days);
+        }
         Ok(results)
     }
 
@@ -87,36 +383,93 @@ impl ReconcileState {
     }
 }
 
-fn read_directives_from_files(paths: &[PathBuf]) -> Result<(Vec<Directive>, HashSet<PathBuf>)> {
+fn read_staging_source(
+    cache: &mut cache::FileCache,
+    index: usize,
+    source: &StagingSourceConfig,
+    refresh: StagingRefresh,
+) -> Result<(Vec<Directive>, SourceSet)> {
+    match &source.source {
+        StagingSource::Files(paths) => cache.read_files(paths),
+        StagingSource::Command {
+            command,
+            cwd,
+            inputs,
+            watch: _,
+            env,
+        } => {
+            let staging = match cache.command_outputs.remove(&index) {
+                Some(output) if refresh == StagingRefresh::Reuse => output,
+                _ => {
+                    let name = source.name.as_deref().unwrap_or("staging");
+                    read_directives_from_command(name, command, cwd, env, inputs, refresh)?
+                }
+            };
+            cache.command_outputs.insert(index, staging.clone());
+            // For command-based staging, we don't have file paths, so return empty set
+            Ok((staging, SourceSet::new()))
+        }
+        StagingSource::Csv(profile) => csv::read_csv_files(profile),
+        StagingSource::Ofx(profile) => ofx::read_ofx_files(profile),
+    }
+}
+
+fn read_directives_from_command(
+    name: &str,
+    command: &[String],
+    cwd: &Path,
+    env: &BTreeMap<String, String>,
+    inputs: &[PathBuf],
+    refresh: StagingRefresh,
+) -> Result<Vec<Directive>> {
+    if command.is_empty() {
+        anyhow::bail!("Command cannot be empty");
+    }
+    let command_str = command.join(" ");
+
+    // Without inputs there is no telling when the cached output is outdated
+    let cache_key = match inputs {
+        [] => None,
+        _ => Some(cache::command_cache_key(command, cwd, env, inputs)?),
+    };
+    if refresh != StagingRefresh::Force
+        && let Some(key) = cache_key
+        && let Some(output) = cache::read_command_output(cwd, name, key)?
+    {
+        tracing::info!("Using cached output of staging command: {}", command_str);
+        return parse_command_output(&output, &command_str);
+    }
+
+    let output = run_command(command, cwd, env)?;
+    let directives = parse_command_output(&output, &command_str)?;
+    if let Some(key) = cache_key
+        && let Err(e) = cache::write_command_output(cwd, name, key, &output)
+    {
+        tracing::warn!("Failed to cache output of staging command: {:#}", e);
+    }
+    Ok(directives)
+}
+
+fn parse_command_output(output: &str, command_str: &str) -> Result<Vec<Directive>> {
+    use anyhow::Context;
+
     let mut directives = Vec::new();
-    let files = paths.iter().map(Clone::clone);
-    // Start with the main files, then add any included files
-    let mut loaded: HashSet<PathBuf> = paths.iter().cloned().collect();
-    beancount_parser::read_files_v2::<Decimal, _>(files, |entry| match entry {
-        Entry::Directive(directive) => {
+    for entry in beancount_parser::parse_iter::<Decimal>(output) {
+        if let Entry::Directive(directive) = entry
+            .with_context(|| format!("Failed to parse staging command output: {}", command_str))?
+        {
             directives.push(directive);
         }
-        Entry::Include(path_buf) => {
-            loaded.insert(path_buf);
-        }
-        _ => {}
-    })?;
+    }
     crate::sorting::sort_dedup_directives(&mut directives);
 
-    Ok((directives, loaded))
+    Ok(directives)
 }
 
-fn read_directives_from_command(
-    command: &[String],
-    cwd: &PathBuf,
-) -> Result<(Vec<Directive>, HashSet<PathBuf>)> {
+fn run_command(command: &[String], cwd: &Path, env: &BTreeMap<String, String>) -> Result<String> {
     use anyhow::Context;
     use std::process::{Command, Stdio};
-    use std::time::{Duration, Instant};
-
-    if command.is_empty() {
-        anyhow::bail!("Command cannot be empty");
-    }
+    use std::time::Instant;
 
     let command_str = command.join(" ");
 
@@ -124,6 +477,7 @@ fn read_directives_from_command(
     let child = Command::new(&command[0])
         .args(&command[1..])
         .current_dir(cwd)
+        .envs(env)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
@@ -171,24 +525,13 @@ fn read_directives_from_command(
         );
     }
 
-    let stdout = String::from_utf8(output.stdout)
-        .with_context(|| format!("Staging command output is not valid UTF-8: {}", command_str))?;
-
-    let mut directives = Vec::new();
-    for entry in beancount_parser::parse_iter::<Decimal>(&stdout) {
-        if let Entry::Directive(directive) = entry
-            .with_context(|| format!("Failed to parse staging command output: {}", command_str))?
-        {
-            directives.push(directive);
-        }
-    }
-    crate::sorting::sort_dedup_directives(&mut directives);
-
-    // For command-based staging, we don't have file paths, so return empty set
-    Ok((directives, HashSet::new()))
+    String::from_utf8(output.stdout)
+        .with_context(|| format!("Staging command output is not valid UTF-8: {}", command_str))
 }
 
-fn group_by_date(all: &[Directive]) -> BTreeMap<Date, Vec<&Directive>> {
+fn group_by_date<'a>(
+    all: impl IntoIterator<Item = &'a Directive>,
+) -> BTreeMap<Date, Vec<&'a Directive>> {
     let mut directives: BTreeMap<_, Vec<_>> = BTreeMap::new();
     for directive in all {
         directives
@@ -200,11 +543,33 @@ fn group_by_date(all: &[Directive]) -> BTreeMap<Date, Vec<&Directive>> {
     directives
 }
 
+/// Like [`group_by_date`], but by the dates the journal entries were imported under.
+///
+/// Entries committed with a different date are found under their original date, and entries
+/// with several imported legs (e.g. a merged transfer) are in the bucket of every leg.
+fn group_journal_by_date(all: &[Directive]) -> BTreeMap<Date, Vec<&Directive>> {
+    let mut directives: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for directive in all {
+        let mut dates: Vec<Date> = matching::imported_legs(directive)
+            .into_iter()
+            .map(|(_, date)| date)
+            .collect();
+        dates.sort();
+        dates.dedup();
+        for date in dates {
+            directives.entry(date).or_default().push(directive);
+        }
+    }
+
+    directives
+}
+
 fn reconcile<'a>(
     journal: BTreeMap<Date, Vec<&'a Directive>>,
     staging: BTreeMap<Date, Vec<&'a Directive>>,
 ) -> Vec<ReconcileItem<'a>> {
     let mut results = Vec::new();
+    let mut matches = Vec::new();
 
     for bucket in SortMergeDiff::new(
         journal.into_iter(),
@@ -212,33 +577,99 @@ fn reconcile<'a>(
         |(date_a, _), (date_b, _)| date_a.cmp(date_b),
     ) {
         match bucket {
-            JoinResult::OnlyInFirst((_, items)) => {
-                results.extend(items.into_iter().map(|directive| ReconcileItem {
-                    item: ReconcileItemKind::OnlyInJournal(directive),
-                    mismatch_reasons: Vec::new(),
-                }));
+            JoinResult::OnlyInFirst((date, items)) => {
+                // Entries are reported once, in the bucket of their primary leg
+                results.extend(
+                    items
+                        .into_iter()
+                        .filter(|directive| matching::source_date(directive) == date)
+                        .map(|directive| {
+                            ReconcileItem::new(ReconcileItemKind::OnlyInJournal(directive))
+                        }),
+                );
             }
             JoinResult::OnlyInSecond((_, items)) => {
-                results.extend(items.into_iter().map(|directive| ReconcileItem {
-                    item: ReconcileItemKind::OnlyInStaging(directive),
-                    mismatch_reasons: Vec::new(),
+                results.extend(items.into_iter().map(|directive| {
+                    ReconcileItem::new(ReconcileItemKind::OnlyInStaging(directive))
                 }));
             }
-            JoinResult::InBoth((_, bucket_journal), (_, bucket_staging)) => {
-                reconcile_bucket(&mut results, bucket_journal, bucket_staging);
+            JoinResult::InBoth((date, bucket_journal), (_, bucket_staging)) => {
+                reconcile_bucket(
+                    &mut results,
+                    &mut matches,
+                    date,
+                    bucket_journal,
+                    bucket_staging,
+                );
             }
         }
     }
 
+    results.extend(combined_matches(&matches));
     results
 }
 
-// PERF: O(journal*staging) per bucket
+/// Account and amount of the posting a journal leg or staging entry is matched by.
+///
+/// Entries can only match if their keys are equal, so buckets are indexed by this key instead
+/// of comparing every staging entry against every journal entry. Directives other than
+/// transactions all share the `None` key.
+type MatchKey<'a> = Option<(&'a str, Option<(Decimal, &'a str)>)>;
+
+fn match_key(directive: &Directive, leg: usize) -> MatchKey<'_> {
+    let DirectiveContent::Transaction(txn) = &directive.content else {
+        return None;
+    };
+    let posting = txn.postings.get(leg)?;
+    let amount = posting
+        .amount
+        .as_ref()
+        .map(|amount| (amount.value, amount.currency.as_str()));
+    Some((posting.account.as_str(), amount))
+}
+
 fn reconcile_bucket<'a>(
     results: &mut Vec<ReconcileItem<'a>>,
-    mut journal: Vec<&'a Directive>,
+    matches: &mut Vec<(&'a Directive, &'a Directive)>,
+    date: Date,
+    journal: Vec<&'a Directive>,
     mut staging: Vec<&'a Directive>,
 ) {
+    // Each imported leg of a journal entry is matched by its own staging entry.
+    // Matched legs are taken out of their slot, so the indices in `index` stay valid.
+    let mut journal: Vec<Option<(&'a Directive, usize)>> = journal
+        .into_iter()
+        .flat_map(|directive| {
+            matching::imported_legs(directive)
+                .into_iter()
+                .filter(move |&(_, leg_date)| leg_date == date)
+                .map(move |(leg, _)| Some((directive, leg)))
+        })
+        .collect();
+    let mut index: HashMap<MatchKey<'a>, Vec<usize>> = HashMap::new();
+    let mut by_link: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (slot, item) in journal.iter().enumerate() {
+        if let Some((directive, leg)) = *item {
+            index
+                .entry(match_key(directive, leg))
+                .or_default()
+                .push(slot);
+            if let DirectiveContent::Transaction(txn) = &directive.content
+                && leg == 0
+            {
+                for link in &txn.links {
+                    by_link.entry(link.to_string()).or_default().push(slot);
+                }
+            }
+        }
+    }
+    let link_groups: Vec<Vec<usize>> = by_link
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+    // Mismatch reasons against the legs of the date are computed on request
+    let mut candidates: Option<Arc<[(&'a Directive, usize)]>> = None;
+
     while let Some(staging_item) = staging.pop() {
         if let DirectiveContent::Transaction(staging_item) = &staging_item.content {
             // not supported yet
@@ -248,29 +679,171 @@ fn reconcile_bucket<'a>(
             }
         }
 
-        let match_at = journal.iter().position(|journal_item| {
-            matching::journal_matches_staging(journal_item, staging_item).is_ok()
+        let match_at = index.get(&match_key(staging_item, 0)).and_then(|slots| {
+            slots.iter().copied().find(|&slot| {
+                journal[slot].is_some_and(|(journal_item, leg)| {
+                    matching::journal_leg_matches_staging(journal_item, leg, staging_item).is_ok()
+                })
+            })
         });
-        if let Some(match_at) = match_at {
-            journal.remove(match_at);
-        } else {
-            // Collect reasons why this staging item didn't match any journal items
-            let mut mismatch_reasons = Vec::new();
-            for (idx, journal_item) in journal.iter().enumerate() {
-                if let Err(reason) = matching::journal_matches_staging(journal_item, staging_item) {
-                    mismatch_reasons.push((idx, *journal_item, reason));
-                }
+        if let Some(slot) = match_at {
+            let (journal_item, _) = journal[slot].take().expect("slot is unmatched");
+            matches.push((journal_item, staging_item));
+        } else if let Some(group) = find_linked_group(&journal, &link_groups, staging_item) {
+            for slot in group {
+                let (journal_item, _) = journal[slot].take().expect("slot is unmatched");
+                matches.push((journal_item, staging_item));
             }
+        } else {
+            let candidates = candidates
+                .get_or_insert_with(|| journal.iter().flatten().copied().collect())
+                .clone();
             results.push(ReconcileItem {
                 item: ReconcileItemKind::OnlyInStaging(staging_item),
-                mismatch_reasons,
+                candidates: Some(candidates),
             });
         }
     }
-    results.extend(journal.into_iter().map(|directive| ReconcileItem {
-        item: ReconcileItemKind::OnlyInJournal(directive),
-        mismatch_reasons: Vec::new(),
-    }));
+    // Unmatched secondary legs are not reported, the entry itself is already in the journal
+    results.extend(
+        journal
+            .into_iter()
+            .flatten()
+            .filter(|&(_, leg)| leg == 0)
+            .map(|(directive, _)| ReconcileItem::new(ReconcileItemKind::OnlyInJournal(directive))),
+    );
+}
+
+/// Slots of unmatched journal entries in the bucket which share a link and together match
+/// `staging`, see [`matching::journal_group_matches_staging`].
+///
+/// `link_groups` are the slots of the entries sharing each link, built once per bucket.
+fn find_linked_group(
+    journal: &[Option<(&Directive, usize)>],
+    link_groups: &[Vec<usize>],
+    staging: &Directive,
+) -> Option<Vec<usize>> {
+    link_groups.iter().find_map(|group| {
+        let (slots, directives): (Vec<usize>, Vec<&Directive>) = group
+            .iter()
+            .filter_map(|&slot| Some((slot, journal[slot]?.0)))
+            .unzip();
+        (slots.len() > 1 && matching::journal_group_matches_staging(&directives, staging).is_ok())
+            .then_some(slots)
+    })
+}
+
+/// `CombinedMatch` items for journal entries matched by several staging entries and for
+/// staging entries matched by several journal entries.
+fn combined_matches<'a>(matches: &[(&'a Directive, &'a Directive)]) -> Vec<ReconcileItem<'a>> {
+    let mut by_journal: Vec<(&'a Directive, Vec<&'a Directive>)> = Vec::new();
+    let mut by_staging: Vec<(&'a Directive, Vec<&'a Directive>)> = Vec::new();
+    let mut journal_index: HashMap<*const Directive, usize> = HashMap::new();
+    let mut staging_index: HashMap<*const Directive, usize> = HashMap::new();
+    for &(journal, staging) in matches {
+        let idx = *journal_index
+            .entry(std::ptr::from_ref(journal))
+            .or_insert_with(|| {
+                by_journal.push((journal, Vec::new()));
+                by_journal.len() - 1
+            });
+        by_journal[idx].1.push(staging);
+
+        let idx = *staging_index
+            .entry(std::ptr::from_ref(staging))
+            .or_insert_with(|| {
+                by_staging.push((staging, Vec::new()));
+                by_staging.len() - 1
+            });
+        by_staging[idx].1.push(journal);
+    }
+
+    let one_journal = by_journal
+        .into_iter()
+        .filter(|(_, staging)| staging.len() > 1)
+        .map(|(journal, staging)| (vec![journal], staging));
+    let one_staging = by_staging
+        .into_iter()
+        .filter(|(_, journal)| journal.len() > 1)
+        .map(|(staging, journal)| (journal, vec![staging]));
+    one_journal
+        .chain(one_staging)
+        .map(|(journal, staging)| {
+            ReconcileItem::new(ReconcileItemKind::CombinedMatch { journal, staging })
+        })
+        .collect()
+}
+
+/// Pair up leftover staging and journal transactions using the tolerant rules of `options`.
+///
+/// Every staging entry takes the unpaired journal entry with the highest similarity, and
+/// both are replaced by a single `FuzzyMatch` item. Only journal entries with the same
+/// [`MatchKey`] inside the date window are compared.
+fn match_fuzzy<'a>(
+    results: Vec<ReconcileItem<'a>>,
+    options: &MatchingOptions,
+) -> Vec<ReconcileItem<'a>> {
+    let mut journal_only: Vec<Option<&'a Directive>> = results
+        .iter()
+        .map(|item| match item.item {
+            ReconcileItemKind::OnlyInJournal(directive) => Some(directive),
+            _ => None,
+        })
+        .collect();
+    // Candidates by key, sorted by the day they were imported on
+    let mut index: HashMap<MatchKey<'a>, Vec<(i64, usize)>> = HashMap::new();
+    for (j, journal) in journal_only.iter().enumerate() {
+        if let Some(journal) = *journal
+            && let key @ Some(_) = match_key(journal, 0)
+        {
+            let day = crate::day_number(&matching::source_date(journal));
+            index.entry(key).or_default().push((day, j));
+        }
+    }
+    for candidates in index.values_mut() {
+        candidates.sort_unstable();
+    }
+    let window = i64::from(options.date_window_days);
+
+    let mut paired = vec![false; results.len()];
+    let mut fuzzy_matches = Vec::new();
+    for (i, item) in results.iter().enumerate() {
+        let ReconcileItemKind::OnlyInStaging(staging) = item.item else {
+            continue;
+        };
+        let Some(candidates) = index.get(&match_key(staging, 0)) else {
+            continue;
+        };
+
+        let day = crate::day_number(&staging.date);
+        let first = candidates.partition_point(|&(candidate_day, _)| candidate_day < day - window);
+        let best = candidates[first..]
+            .iter()
+            .take_while(|&&(candidate_day, _)| candidate_day <= day + window)
+            .filter_map(|&(_, j)| {
+                let similarity =
+                    matching::journal_fuzzy_matches_staging(journal_only[j]?, staging, options)?;
+                Some((j, similarity))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((j, similarity)) = best {
+            let journal = journal_only[j].take().expect("candidate is unpaired");
+            paired[i] = true;
+            paired[j] = true;
+            fuzzy_matches.push(ReconcileItem::new(ReconcileItemKind::FuzzyMatch {
+                journal,
+                staging,
+                similarity,
+            }));
+        }
+    }
+
+    results
+        .into_iter()
+        .zip(paired)
+        .filter_map(|(item, paired)| (!paired).then_some(item))
+        .chain(fuzzy_matches)
+        .collect()
 }
 
 #[cfg(test)]
@@ -326,6 +899,29 @@ mod tests {
                     output.push_str("; OnlyInStaging\n");
                     output.push_str(&directive.to_string());
                 }
+                ReconcileItemKind::FuzzyMatch {
+                    journal,
+                    staging,
+                    similarity,
+                } => {
+                    output.push_str(&format!("; FuzzyMatch ({:.2})\n", similarity));
+                    output.push_str(&journal.to_string());
+                    output.push('\n');
+                    output.push_str(&staging.to_string());
+                }
+                ReconcileItemKind::Transfer { outgoing, incoming } => {
+                    output.push_str("; Transfer\n");
+                    output.push_str(&outgoing.to_string());
+                    output.push('\n');
+                    output.push_str(&incoming.to_string());
+                }
+                ReconcileItemKind::CombinedMatch { journal, staging } => {
+                    output.push_str(&format!(
+                        "; CombinedMatch ({} journal, {} staging)\n",
+                        journal.len(),
+                        staging.len()
+                    ));
+                }
             }
             output.push('\n');
         }
@@ -510,6 +1106,58 @@ mod tests {
         "#);
     }
 
+    #[test]
+    fn reconcile_matches_amounts_with_different_scale() {
+        let journal = r#"
+2025-01-01 * "Payee1" "Transaction 1"
+    Assets:Checking  -100.0 EUR
+    Expenses:Food
+"#;
+        let staging = r#"
+2025-01-01 * "Payee1" "Transaction 1"
+    Assets:Checking  -100.00 EUR
+"#;
+        let journal_directives = build_directives(journal);
+        let staging_directives = build_directives(staging);
+        let journal_map = build_date_map(&journal_directives);
+        let staging_map = build_date_map(&staging_directives);
+        let results = reconcile(journal_map, staging_map);
+
+        assert_eq!(count_results(&results), (0, 0));
+    }
+
+    #[test]
+    fn reconcile_mismatch_reasons_on_request() {
+        let journal = r#"
+2025-01-01 * "Payee1" "Transaction 1"
+    Assets:Checking  -100.00 EUR
+    Expenses:Food    100.00 EUR
+
+2025-01-01 * "Payee2" "Transaction 2"
+    Assets:Checking  -50.00 EUR
+    Expenses:Transport  50.00 EUR
+"#;
+        let staging = r#"
+2025-01-01 * "Payee3" "Transaction 2"
+    Assets:Checking  -50.00 EUR
+"#;
+        let journal_directives = build_directives(journal);
+        let staging_directives = build_directives(staging);
+        let journal_map = build_date_map(&journal_directives);
+        let staging_map = build_date_map(&staging_directives);
+        let results = reconcile(journal_map, staging_map);
+
+        let reasons: Vec<String> = results
+            .iter()
+            .flat_map(|item| item.mismatch_reasons())
+            .map(|(idx, journal, reason)| format!("[{idx}] {reason} - {}", journal.date))
+            .collect();
+        insta::assert_snapshot!(reasons.join("\n"), @r#"
+        [0] Different payee: journal="Payee1" staging="Payee3" - 2025-01-01
+        [1] Different payee: journal="Payee2" staging="Payee3" - 2025-01-01
+        "#);
+    }
+
     // Date bucket handling tests
 
     #[test]
@@ -776,4 +1424,257 @@ mod tests {
         // (1 staging matched with 1 journal, leaving 3 staging unmatched)
         assert_eq!(count_results(&results), (0, 3));
     }
+
+    #[test]
+    fn reconcile_fuzzy_date_window_and_narration() {
+        let journal = r#"
+2025-01-02 * "ACME Corp" "Invoice 2025-001"
+    Assets:Checking  -80.00 EUR
+    Expenses:Office
+
+2025-01-10 * "Bakery" "Bread"
+    Assets:Checking  -3.00 EUR
+    Expenses:Food
+"#;
+        let staging = r#"
+2025-01-03 ! "ACME Corp." "Invoice 2025-001"
+    Assets:Checking  -80.00 EUR
+
+2025-01-15 ! "Bakery" "Bread"
+    Assets:Checking  -3.00 EUR
+"#;
+        let mut state = ReconcileState {
+            journal: build_directives(journal),
+            staging: build_directives(staging),
+            ..Default::default()
+        };
+        assert_eq!(count_results(&state.reconcile().unwrap()), (2, 2));
+
+        state.matching = MatchingOptions {
+            date_window_days: 2,
+            min_similarity: Some(0.8),
+            ..Default::default()
+        };
+        let results = state.reconcile().unwrap();
+        assert_eq!(count_results(&results), (1, 1));
+        insta::assert_snapshot!(format_results(&results), @r#"
+        ; OnlyInJournal
+        2025-01-10 * "Bakery" "Bread"
+          Assets:Checking -3.00 EUR
+          Expenses:Food
+        ; OnlyInStaging
+        2025-01-15 ! "Bakery" "Bread"
+          Assets:Checking -3.00 EUR
+        ; FuzzyMatch (0.94)
+        2025-01-02 * "ACME Corp" "Invoice 2025-001"
+          Assets:Checking -80.00 EUR
+          Expenses:Office
+        2025-01-03 ! "ACME Corp." "Invoice 2025-001"
+          Assets:Checking -80.00 EUR
+        "#);
+    }
+
+    #[test]
+    fn reconcile_uses_source_date() {
+        let journal = r#"
+2025-01-03 * "Card Shop" "Purchase"
+    source_date: "2025-01-05"
+    Assets:Card  -20.00 EUR
+    Expenses:Shopping
+"#;
+        let staging = r#"
+2025-01-05 ! "Card Shop" "Purchase"
+    Assets:Card  -20.00 EUR
+"#;
+        let state = ReconcileState {
+            journal: build_directives(journal),
+            staging: build_directives(staging),
+            ..Default::default()
+        };
+        let results = state.reconcile().unwrap();
+
+        assert_eq!(count_results(&results), (0, 0));
+    }
+    #[test]
+    fn reconcile_merged_transfer_matches_both_sides() {
+        let journal = r#"
+2025-01-02 * "Bank" "Transfer to savings"
+    Assets:Checking  -500.00 EUR
+    Assets:Savings  500.00 EUR
+        source_desc: "Transfer from checking"
+        source_date: "2025-01-03"
+"#;
+        let staging = r#"
+2025-01-02 ! "Bank" "Transfer to savings"
+    Assets:Checking  -500.00 EUR
+
+2025-01-03 ! "Transfer from checking"
+    Assets:Savings  500.00 EUR
+"#;
+        let state = ReconcileState {
+            journal: build_directives(journal),
+            staging: build_directives(staging),
+            ..Default::default()
+        };
+        let results = state.reconcile().unwrap();
+
+        assert_eq!(count_results(&results), (0, 0));
+        insta::assert_snapshot!(format_results(&results), @"; CombinedMatch (1 journal, 2 staging)");
+    }
+
+    #[test]
+    fn reconcile_linked_split_matches_one_staging_entry() {
+        let journal = r#"
+2025-01-05 * "Online Shop" "Order 1234" ^order-1234
+    Assets:Card  -60.00 EUR
+    Expenses:Books
+
+2025-01-05 * "Online Shop" "Order 1234" ^order-1234
+    Assets:Card  -40.00 EUR
+    Expenses:Household
+"#;
+        let staging = r#"
+2025-01-05 ! "Online Shop" "Order 1234"
+    Assets:Card  -100.00 EUR
+
+2025-01-05 ! "Online Shop" "Order 1234"
+    Assets:Card  -100.00 EUR
+"#;
+        let journal_directives = build_directives(journal);
+        let staging_directives = build_directives(staging);
+        let results = reconcile(
+            build_date_map(&journal_directives),
+            build_date_map(&staging_directives),
+        );
+
+        // The split covers one of the identical staging entries
+        assert_eq!(count_results(&results), (0, 1));
+        let combined = results
+            .iter()
+            .find_map(|item| match &item.item {
+                ReconcileItemKind::CombinedMatch { journal, staging } => Some((journal, staging)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!((combined.0.len(), combined.1.len()), (2, 1));
+    }
+
+    #[test]
+    fn reconcile_leaves_out_ignored_entries() {
+        let staging = r#"
+2025-01-03 ! "Card Hold" "Pre-authorization"
+    Assets:Card  -50.00 EUR
+
+2025-01-03 ! "Card Shop" "Purchase"
+    Assets:Card  -20.00 EUR
+"#;
+        let ignored = r#"
+2025-01-03 ! "Card Hold" "Pre-authorization" #ignored
+    Assets:Card  -50.00 EUR
+
+2024-12-01 ! "Card Hold" "Old pre-authorization" #ignored
+    Assets:Card  -10.00 EUR
+"#;
+        let state = ReconcileState {
+            staging: build_directives(staging),
+            ignored: build_directives(ignored),
+            ..Default::default()
+        };
+        let results = state.reconcile().unwrap();
+
+        // Ignored entries no longer in staging aren't reported either
+        insta::assert_snapshot!(format_results(&results), @r#"
+        ; OnlyInStaging
+        2025-01-03 ! "Card Shop" "Purchase"
+          Assets:Card -20.00 EUR
+        "#);
+    }
+
+    #[test]
+    fn reread_merges_named_staging_sources() {
+        let dir =
+            std::env::temp_dir().join(format!("beancount-staging-sources-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let journal = dir.join("journal.beancount");
+        let bank = dir.join("bank.beancount");
+        std::fs::write(&journal, "").unwrap();
+        std::fs::write(&bank, "2025-01-02 ! \"Bakery\"\n  Assets:Bank -3.00 EUR\n").unwrap();
+        std::fs::write(
+            dir.join("paypal.beancount"),
+            "2025-01-01 ! \"Shop\"\n  Assets:PayPal -9.00 EUR\n",
+        )
+        .unwrap();
+
+        let config = ReconcileConfig::with_sources(
+            vec![journal],
+            vec![
+                StagingSourceConfig {
+                    name: Some("bank".to_string()),
+                    source: StagingSource::Files(vec![bank]),
+                    default_account: None,
+                    refresh_interval: None,
+                },
+                StagingSourceConfig {
+                    name: Some("paypal".to_string()),
+                    source: StagingSource::Command {
+                        command: vec!["sh".to_string(), "-c".to_string(), "cat $FILE".to_string()],
+                        cwd: dir.clone(),
+                        inputs: Vec::new(),
+                        watch: Vec::new(),
+                        env: BTreeMap::from([("FILE".to_string(), "paypal.beancount".to_string())]),
+                    },
+                    default_account: Some("Expenses:Shopping".to_string()),
+                    refresh_interval: None,
+                },
+            ],
+        );
+        let state = config.read().unwrap();
+        let origins: Vec<_> = state.staging.iter().map(staging_origin).collect();
+        assert_eq!(origins, [Some("paypal"), Some("bank")]);
+        assert_eq!(
+            config.default_account(&state.staging[0]),
+            Some("Expenses:Shopping")
+        );
+        assert_eq!(config.default_account(&state.staging[1]), None);
+        assert_eq!(config.staging_source_names(), ["bank", "paypal"]);
+    }
+
+    #[test]
+    fn reread_reuses_staging_command_output() {
+        let dir =
+            std::env::temp_dir().join(format!("beancount-staging-reread-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let journal = dir.join("journal.beancount");
+        let staging = dir.join("staging.beancount");
+        std::fs::write(&journal, "").unwrap();
+        std::fs::write(&staging, "2025-01-01 ! \"Shop\"\n  Assets:Cash -1.00 EUR\n").unwrap();
+
+        let config = ReconcileConfig::new(
+            vec![journal],
+            StagingSource::Command {
+                command: vec!["cat".to_string(), "staging.beancount".to_string()],
+                cwd: dir.clone(),
+                inputs: Vec::new(),
+                watch: Vec::new(),
+                env: BTreeMap::new(),
+            },
+        );
+        assert_eq!(config.read().unwrap().staging.len(), 1);
+
+        std::fs::write(&staging, "").unwrap();
+        assert_eq!(
+            config.reread(StagingRefresh::Reuse).unwrap().staging.len(),
+            1
+        );
+        assert_eq!(
+            config
+                .reread(StagingRefresh::IfChanged)
+                .unwrap()
+                .staging
+                .len(),
+            0
+        );
+    }
 }
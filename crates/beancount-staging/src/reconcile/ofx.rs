@@ -0,0 +1,299 @@
+//! Staging entries read directly from OFX/QFX statement downloads.
+//!
+//! Both variants are supported: OFX 1.x is SGML, where elements holding a value usually
+//! have no closing tag, and OFX 2.x is XML.
+
+use super::SourceSet;
+use super::csv::quote;
+use crate::{Decimal, Directive, Result};
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Metadata key for the bank's unique id of a transaction.
+pub const FITID_KEY: &str = "fitid";
+
+/// Which OFX statements to import, and into which accounts.
+#[derive(Debug, Clone, Default)]
+pub struct OfxProfile {
+    pub files: Vec<PathBuf>,
+    /// Account for statements not listed in `accounts`.
+    pub account: Option<String>,
+    /// Accounts by the `ACCTID` of the statement, for files with several accounts.
+    pub accounts: BTreeMap<String, String>,
+}
+
+/// Read the staging transactions and balances of all files of `profile`.
+pub(crate) fn read_ofx_files(profile: &OfxProfile) -> Result<(Vec<Directive>, SourceSet)> {
+    let mut directives = Vec::new();
+    let mut sourceset = SourceSet::new();
+    for path in &profile.files {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        // SGML files are usually in a legacy encoding, given in the `CHARSET` header
+        let text = match String::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(e) => encoding_rs::WINDOWS_1252
+                .decode(e.as_bytes())
+                .0
+                .into_owned(),
+        };
+        let file_directives = parse_ofx(&text, profile)
+            .with_context(|| format!("Failed to read OFX file: {}", path.display()))?;
+        directives.extend(file_directives);
+        sourceset.insert(path.clone());
+    }
+    crate::sorting::sort_dedup_directives(&mut directives);
+
+    Ok((directives, sourceset))
+}
+
+#[derive(Default)]
+struct Statement {
+    account_id: Option<String>,
+    currency: Option<String>,
+    transactions: Vec<BTreeMap<String, String>>,
+    /// Amount and date
+    ledger_balance: Option<(String, String)>,
+}
+
+fn parse_ofx(text: &str, profile: &OfxProfile) -> Result<Vec<Directive>> {
+    let body = text
+        .find("<OFX>")
+        .map(|start| &text[start..])
+        .context("No <OFX> element")?;
+
+    let mut statements = Vec::new();
+    let mut statement: Option<Statement> = None;
+    // Values of the innermost `STMTTRN` or `LEDGERBAL`
+    let mut values: Option<BTreeMap<String, String>> = None;
+    for (tag, value) in elements(body) {
+        match tag {
+            "STMTRS" | "CCSTMTRS" => statement = Some(Statement::default()),
+            "/STMTRS" | "/CCSTMTRS" => statements.extend(statement.take()),
+            "STMTTRN" | "LEDGERBAL" => values = Some(BTreeMap::new()),
+            "/STMTTRN" => {
+                if let (Some(statement), Some(values)) = (&mut statement, values.take()) {
+                    statement.transactions.push(values);
+                }
+            }
+            "/LEDGERBAL" => {
+                if let (Some(statement), Some(mut values)) = (&mut statement, values.take()) {
+                    statement.ledger_balance = values.remove("BALAMT").zip(values.remove("DTASOF"));
+                }
+            }
+            _ if value.is_empty() => {}
+            _ => {
+                if let Some(values) = &mut values {
+                    values.insert(tag.to_string(), value);
+                } else if let Some(statement) = &mut statement {
+                    match tag {
+                        "ACCTID" => statement.account_id = Some(value),
+                        "CURDEF" => statement.currency = Some(value),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    let mut directives = Vec::new();
+    for statement in statements {
+        let account_id = statement.account_id.as_deref().unwrap_or_default();
+        let account = profile
+            .accounts
+            .get(account_id)
+            .or(profile.account.as_ref())
+            .with_context(|| format!("No account configured for OFX account '{}'", account_id))?;
+        let currency = statement
+            .currency
+            .as_deref()
+            .context("Statement has no currency (CURDEF)")?;
+
+        for transaction in &statement.transactions {
+            let directive =
+                transaction_directive(transaction, account, currency).with_context(|| {
+                    let fitid = transaction.get("FITID").map_or("", String::as_str);
+                    format!("Invalid transaction '{}'", fitid)
+                })?;
+            directives.push(directive);
+        }
+        if let Some((amount, date)) = &statement.ledger_balance {
+            // The balance is as of the end of the day, beancount checks it at the start
+            let date = format_date(next_day(parse_ofx_date(date)?))?;
+            let amount = parse_amount(amount)?;
+            directives.push(parse_generated(&format!(
+                "{date} balance {account} {amount} {currency}\n"
+            ))?);
+        }
+    }
+    Ok(directives)
+}
+
+fn transaction_directive(
+    values: &BTreeMap<String, String>,
+    account: &str,
+    currency: &str,
+) -> Result<Directive> {
+    let field = |name: &str| values.get(name).map(String::as_str);
+    let date = format_date(parse_ofx_date(
+        field("DTPOSTED").context("Transaction has no DTPOSTED")?,
+    )?)?;
+    let amount = parse_amount(field("TRNAMT").context("Transaction has no TRNAMT")?)?;
+
+    let mut text = format!("{date} !");
+    if let Some(name) = field("NAME") {
+        text.push_str(&format!(" {}", quote(name)));
+    }
+    text.push_str(&format!(" {}\n", quote(field("MEMO").unwrap_or_default())));
+    if let Some(fitid) = field("FITID") {
+        text.push_str(&format!("  {FITID_KEY}: {}\n", quote(fitid)));
+    }
+    text.push_str(&format!("  {account} {amount} {currency}\n"));
+    parse_generated(&text)
+}
+
+fn parse_generated(text: &str) -> Result<Directive> {
+    beancount_parser::parse::<Decimal>(text)
+        .ok()
+        .and_then(|parsed| parsed.directives.into_iter().next())
+        .with_context(|| format!("Failed to parse generated directive:\n{}", text))
+}
+
+fn parse_amount(value: &str) -> Result<Decimal> {
+    value
+        .trim_start_matches('+')
+        .replace(',', ".")
+        .parse()
+        .with_context(|| format!("Invalid amount: '{}'", value))
+}
+
+/// Dates are `YYYYMMDD`, optionally followed by the time and timezone.
+fn parse_ofx_date(value: &str) -> Result<(u32, u32, u32)> {
+    let digits = value
+        .get(..8)
+        .filter(|digits| digits.chars().all(|c| c.is_ascii_digit()))
+        .with_context(|| format!("Invalid date: '{}'", value))?;
+    Ok((
+        digits[..4].parse()?,
+        digits[4..6].parse()?,
+        digits[6..].parse()?,
+    ))
+}
+
+fn next_day((year, month, day): (u32, u32, u32)) -> (u32, u32, u32) {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    match (month, day) {
+        (12, 31) => (year + 1, 1, 1),
+        (_, day) if day >= days_in_month => (year, month + 1, 1),
+        _ => (year, month, day + 1),
+    }
+}
+
+/// Format as `YYYY-MM-DD`, checking that the date exists.
+fn format_date((year, month, day): (u32, u32, u32)) -> Result<String> {
+    let date = format!("{year:04}-{month:02}-{day:02}");
+    crate::parse_date(&date)?;
+    Ok(date)
+}
+
+/// Tags with the text following them, e.g. `("TRNAMT", "-1.00")` or `("/STMTTRN", "")`.
+fn elements(body: &str) -> impl Iterator<Item = (&str, String)> {
+    body.split('<').skip(1).filter_map(|element| {
+        let (tag, value) = element.split_once('>')?;
+        Some((tag.trim(), decode_entities(value.trim())))
+    })
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    fn render(directives: &[Directive]) -> String {
+        directives
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    #[test]
+    fn reads_sgml_statement() {
+        let profile = OfxProfile {
+            files: vec![fixture("checking.ofx")],
+            account: Some("Assets:Bank:Checking".to_string()),
+            ..Default::default()
+        };
+        let (directives, sourceset) = read_ofx_files(&profile).unwrap();
+        assert!(sourceset.contains(&fixture("checking.ofx")));
+        insta::assert_snapshot!(render(&directives), @r#"
+        2025-01-03 ! "GROCERY OUTLET" "POS PURCHASE"
+          fitid: "2025010301"
+          Assets:Bank:Checking -42.50 USD
+
+        2025-01-15 ! "ACME PAYROLL" "SALARY & BONUS"
+          fitid: "2025011501"
+          Assets:Bank:Checking 1500.00 USD
+
+        2025-02-01 balance Assets:Bank:Checking 2457.50 USD
+        "#);
+    }
+
+    #[test]
+    fn reads_xml_statement_by_account_id() {
+        let profile = OfxProfile {
+            files: vec![fixture("creditcard.qfx")],
+            account: None,
+            accounts: BTreeMap::from([(
+                "4111********1111".to_string(),
+                "Liabilities:CreditCard".to_string(),
+            )]),
+        };
+        let (directives, _) = read_ofx_files(&profile).unwrap();
+        insta::assert_snapshot!(render(&directives), @r#"
+        2025-02-10 ! "Streaming Service" "Monthly subscription"
+          fitid: "CC-0001"
+          Liabilities:CreditCard -19.99 EUR
+
+        2025-03-01 balance Liabilities:CreditCard -19.99 EUR
+        "#);
+    }
+
+    #[test]
+    fn requires_account_for_statement() {
+        let profile = OfxProfile {
+            files: vec![fixture("creditcard.qfx")],
+            ..Default::default()
+        };
+        let error = read_ofx_files(&profile).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            format!(
+                "Failed to read OFX file: {}: No account configured for OFX account '4111********1111'",
+                fixture("creditcard.qfx").display()
+            )
+        );
+    }
+}
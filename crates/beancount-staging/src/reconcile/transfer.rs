@@ -0,0 +1,248 @@
+//! Pairing the two one-legged staging entries of a transfer between imported accounts.
+//!
+//! When money moves from checking to savings, the importer of each account only knows its
+//! own side. Both entries are merged into one transaction with two postings. The receiving
+//! posting keeps the payee, narration and date of its staging entry as posting metadata, so
+//! reconciliation matches both staging entries against the single journal entry.
+
+use super::matching::days_between;
+use super::{ReconcileItem, ReconcileItemKind};
+use crate::{Decimal, Directive, DirectiveContent, Posting, Result};
+use anyhow::Context;
+use beancount_parser::metadata::Value;
+
+/// Replace pairs of `OnlyInStaging` items which look like both sides of one transfer by a
+/// single `Transfer` item.
+///
+/// Every outgoing entry takes the unpaired incoming entry with the closest date, as long
+/// as it is at most `window_days` away.
+pub(crate) fn pair_transfers<'a>(
+    results: Vec<ReconcileItem<'a>>,
+    window_days: u32,
+) -> Vec<ReconcileItem<'a>> {
+    let mut incoming_only: Vec<Option<&'a Directive>> = results
+        .iter()
+        .map(|item| match item.item {
+            ReconcileItemKind::OnlyInStaging(directive) => {
+                single_leg(directive).is_some().then_some(directive)
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut paired = vec![false; results.len()];
+    let mut transfers = Vec::new();
+    for (i, item) in results.iter().enumerate() {
+        let ReconcileItemKind::OnlyInStaging(outgoing) = item.item else {
+            continue;
+        };
+        let Some(outgoing_leg) = single_leg(outgoing) else {
+            continue;
+        };
+        if paired[i] {
+            continue;
+        }
+
+        let best = incoming_only
+            .iter()
+            .enumerate()
+            .filter_map(|(j, incoming)| {
+                let incoming = (*incoming)?;
+                let incoming_leg = single_leg(incoming)?;
+                let days = days_between(outgoing.date, incoming.date);
+                (is_transfer(outgoing_leg, incoming_leg) && days <= i64::from(window_days))
+                    .then_some((j, days))
+            })
+            .min_by_key(|&(_, days)| days);
+        if let Some((j, _)) = best {
+            let incoming = incoming_only[j].take().expect("candidate is unpaired");
+            incoming_only[i] = None;
+            paired[i] = true;
+            paired[j] = true;
+            transfers.push(ReconcileItem::new(ReconcileItemKind::Transfer {
+                outgoing,
+                incoming,
+            }));
+        }
+    }
+
+    results
+        .into_iter()
+        .zip(paired)
+        .filter_map(|(item, paired)| (!paired).then_some(item))
+        .chain(transfers)
+        .collect()
+}
+
+/// The primary posting of a staging transaction which only knows its own side of the money
+/// movement, i.e. all other postings are placeholders without an amount.
+fn single_leg(directive: &Directive) -> Option<&Posting> {
+    let DirectiveContent::Transaction(txn) = &directive.content else {
+        return None;
+    };
+    let (primary, others) = txn.postings.split_first()?;
+    let simple = primary.cost.is_none() && primary.price.is_none();
+    (simple && primary.amount.is_some() && others.iter().all(|p| p.amount.is_none()))
+        .then_some(primary)
+}
+
+/// Whether money leaves the account of `outgoing` and the same amount arrives in the
+/// account of `incoming`.
+fn is_transfer(outgoing: &Posting, incoming: &Posting) -> bool {
+    let (Some(outgoing_amount), Some(incoming_amount)) = (&outgoing.amount, &incoming.amount)
+    else {
+        return false;
+    };
+    outgoing.account != incoming.account
+        && outgoing_amount.value < Decimal::ZERO
+        && outgoing_amount.value == -incoming_amount.value
+        && outgoing_amount.currency.as_str() == incoming_amount.currency.as_str()
+}
+
+/// Merge both sides of a transfer into a single staging transaction.
+///
+/// The result is the outgoing entry with its placeholder postings replaced by the primary
+/// posting of the incoming entry. That posting records the incoming payee, narration and
+/// date as `source_payee`, `source_desc` and `source_date` metadata, which reconciliation
+/// uses to match the incoming entry.
+pub fn merge_transfer(outgoing: &Directive, incoming: &Directive) -> Result<Directive> {
+    let mut merged = outgoing.clone();
+    let (DirectiveContent::Transaction(txn), DirectiveContent::Transaction(incoming_txn)) =
+        (&mut merged.content, &incoming.content)
+    else {
+        anyhow::bail!("Only transactions can be merged into a transfer");
+    };
+
+    let mut leg = incoming_txn
+        .postings
+        .first()
+        .context("Incoming side of the transfer has no posting")?
+        .clone();
+    // `source_desc` marks the posting as imported, so it is always written
+    leg.metadata.insert(
+        "source_desc".parse().unwrap(),
+        Value::String(incoming_txn.narration.clone().unwrap_or_default()),
+    );
+    if let Some(payee) = &incoming_txn.payee {
+        leg.metadata.insert(
+            "source_payee".parse().unwrap(),
+            Value::String(payee.clone()),
+        );
+    }
+    if incoming.date != outgoing.date {
+        leg.metadata.insert(
+            "source_date".parse().unwrap(),
+            Value::String(incoming.date.to_string()),
+        );
+    }
+
+    txn.postings.truncate(1);
+    txn.postings.push(leg);
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reconcile::{MatchingOptions, ReconcileState};
+    use beancount_parser::Entry;
+
+    fn build_directives(source: &str) -> Vec<Directive> {
+        let mut directives: Vec<Directive> = beancount_parser::parse_iter(source)
+            .filter_map(|entry| match entry.unwrap() {
+                Entry::Directive(directive) => Some(directive),
+                _ => None,
+            })
+            .collect();
+        crate::sorting::sort_dedup_directives(&mut directives);
+        directives
+    }
+
+    fn only_transfer<'a>(results: &[ReconcileItem<'a>]) -> (&'a Directive, &'a Directive) {
+        let transfers: Vec<_> = results
+            .iter()
+            .filter_map(|item| match item.item {
+                ReconcileItemKind::Transfer { outgoing, incoming } => Some((outgoing, incoming)),
+                _ => None,
+            })
+            .collect();
+        let [transfer] = transfers[..] else {
+            panic!("expected a single transfer, got {:?}", transfers);
+        };
+        transfer
+    }
+
+    const STAGING: &str = r#"
+2025-01-02 ! "Bank" "Transfer to savings"
+    Assets:Checking  -500.00 EUR
+
+2025-01-03 ! "Transfer from checking"
+    Assets:Savings  500.00 EUR
+
+2025-01-03 ! "Coffee Shop" "Coffee"
+    Assets:Checking  -5.00 EUR
+
+2025-01-20 ! "Transfer from checking"
+    Assets:Savings  5.00 EUR
+"#;
+
+    #[test]
+    fn pair_opposite_amounts_on_nearby_dates() {
+        let mut state = ReconcileState {
+            staging: build_directives(STAGING),
+            ..Default::default()
+        };
+        assert_eq!(state.reconcile().unwrap().len(), 4);
+
+        state.matching = MatchingOptions {
+            transfer_window_days: Some(3),
+            ..Default::default()
+        };
+        let results = state.reconcile().unwrap();
+        assert_eq!(results.len(), 3);
+
+        let (outgoing, incoming) = only_transfer(&results);
+        let merged = merge_transfer(outgoing, incoming).unwrap();
+        let DirectiveContent::Transaction(txn) = &merged.content else {
+            panic!("merged transfer is not a transaction");
+        };
+        assert_eq!(merged.date, outgoing.date);
+        assert_eq!(txn.postings.len(), 2);
+        assert_eq!(txn.postings[1].account.as_str(), "Assets:Savings");
+        assert!(crate::is_transaction_balanced(txn));
+    }
+
+    #[test]
+    fn committed_transfer_matches_both_sides() {
+        let mut state = ReconcileState {
+            staging: build_directives(STAGING),
+            matching: MatchingOptions {
+                transfer_window_days: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (outgoing, incoming) = only_transfer(&state.reconcile().unwrap());
+        let merged = merge_transfer(outgoing, incoming).unwrap();
+
+        // What ends up in the journal after committing the merged transfer
+        state.journal = build_directives(&merged.to_string().replacen(" ! ", " * ", 1));
+        state.matching = MatchingOptions::default();
+        let results = state.reconcile().unwrap();
+
+        let unmatched: Vec<String> = results
+            .iter()
+            .map(|item| match item.item {
+                ReconcileItemKind::OnlyInStaging(directive) => directive.to_string(),
+                ReconcileItemKind::OnlyInJournal(directive) => format!("journal: {directive}"),
+                _ => "other".to_string(),
+            })
+            .collect();
+        insta::assert_snapshot!(unmatched.join("\n"), @r#"
+        2025-01-03 ! "Coffee Shop" "Coffee"
+          Assets:Checking -5.00 EUR
+        2025-01-20 ! "Transfer from checking"
+          Assets:Savings 5.00 EUR
+        "#);
+    }
+}
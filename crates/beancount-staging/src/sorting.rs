@@ -1,15 +1,19 @@
 use crate::{Directive, DirectiveContent};
+use std::cmp::Ordering;
 
 pub fn sort_dedup_directives(directives: &mut Vec<Directive>) {
-    directives.sort_by(|a, b| {
-        a.date
-            .cmp(&b.date)
-            .then_with(|| directive_order(&a.content).cmp(&directive_order(&b.content)))
-            .then_with(|| directive_content_key(&a.content).cmp(directive_content_key(&b.content)))
-    });
+    directives.sort_by(directive_cmp);
     directives.dedup_by(|a, b| is_identical(a, b));
 }
 
+/// The canonical order of directives: by date, then directive type, then account/payee.
+pub fn directive_cmp(a: &Directive, b: &Directive) -> Ordering {
+    a.date
+        .cmp(&b.date)
+        .then_with(|| directive_order(&a.content).cmp(&directive_order(&b.content)))
+        .then_with(|| directive_content_key(&a.content).cmp(directive_content_key(&b.content)))
+}
+
 fn directive_content_key(content: &DirectiveContent) -> &str {
     match content {
         DirectiveContent::Balance(b) => b.account.as_str(),
@@ -18,6 +22,7 @@ fn directive_content_key(content: &DirectiveContent) -> &str {
         DirectiveContent::Close(c) => c.account.as_str(),
         DirectiveContent::Pad(p) => p.account.as_str(),
         DirectiveContent::Commodity(c) => c.as_str(),
+        DirectiveContent::Price(p) => p.currency.as_str(),
         DirectiveContent::Event(e) => e.name.as_str(),
         _ => "",
     }
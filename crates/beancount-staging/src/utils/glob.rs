@@ -0,0 +1,92 @@
+//! Glob patterns for files to watch, like `data/**/*.csv`.
+
+use crate::Result;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// A glob pattern, relative to a base directory.
+///
+/// `*` and `?` match any characters and a single character within a path segment, `**`
+/// matches any number of directories.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    root: PathBuf,
+    pattern: regex::Regex,
+}
+
+impl Glob {
+    pub fn new(glob: &str, base: &Path) -> Result<Self> {
+        let segments: Vec<&str> = glob.split('/').collect();
+        // The last segment names files, even if it has no wildcards
+        let literal = segments
+            .iter()
+            .take_while(|segment| !segment.contains(['*', '?']))
+            .count()
+            .min(segments.len() - 1);
+        let root = base.join(segments[..literal].join("/"));
+        let root = std::path::absolute(&root)
+            .with_context(|| format!("Invalid watch path: {}", root.display()))?;
+
+        let rest = &segments[literal..];
+        let mut pattern = String::from("^");
+        for (i, segment) in rest.iter().enumerate() {
+            let last = i == rest.len() - 1;
+            match *segment {
+                "**" if last => pattern.push_str(".*"),
+                "**" => pattern.push_str("(?:[^/]*/)*"),
+                _ => {
+                    for c in segment.chars() {
+                        match c {
+                            '*' => pattern.push_str("[^/]*"),
+                            '?' => pattern.push_str("[^/]"),
+                            c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                        }
+                    }
+                    if !last {
+                        pattern.push('/');
+                    }
+                }
+            }
+        }
+        pattern.push('$');
+        let pattern = regex::Regex::new(&pattern)
+            .with_context(|| format!("Invalid watch pattern: {:?}", glob))?;
+
+        Ok(Glob { root, pattern })
+    }
+
+    /// The directory containing all matching files, which has to be watched recursively.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        self.pattern.is_match(&relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_nested_files() {
+        let base = Path::new("/finances");
+        let glob = Glob::new("data/**/*.csv", base).unwrap();
+        assert_eq!(glob.root(), Path::new("/finances/data"));
+
+        assert!(glob.matches(Path::new("/finances/data/statement.csv")));
+        assert!(glob.matches(Path::new("/finances/data/bank/2025/01.csv")));
+        assert!(!glob.matches(Path::new("/finances/data/bank/01.pdf")));
+        assert!(!glob.matches(Path::new("/finances/other/01.csv")));
+
+        let glob = Glob::new("data/bank-?.csv", base).unwrap();
+        assert!(glob.matches(Path::new("/finances/data/bank-1.csv")));
+        assert!(!glob.matches(Path::new("/finances/data/sub/bank-1.csv")));
+        assert!(!glob.matches(Path::new("/finances/data/bank-12.csv")));
+    }
+}
@@ -0,0 +1,428 @@
+use crate::{Decimal, Directive, Entry, Result};
+use anyhow::Context;
+use std::cmp::Ordering;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Append contents to one or more files, either completely or not at all.
+///
+/// The current length of every file is recorded before writing. If any write or the
+/// following fsync fails, all files are truncated back to their recorded length so a
+/// journal never ends up with half of a batch.
+pub fn append_all_or_nothing(writes: &[(&Path, &[u8])]) -> Result<()> {
+    let mut files = Vec::with_capacity(writes.len());
+    for &(path, contents) in writes {
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open journal file: {}", path.display()))?;
+        let offset = file.metadata()?.len();
+        files.push((path, file, offset, contents));
+    }
+
+    for i in 0..files.len() {
+        let result = {
+            let (_, file, _, contents) = &mut files[i];
+            file.write_all(contents).and_then(|()| file.sync_data())
+        };
+
+        if let Err(error) = result {
+            for (path, file, offset, _) in &files[..=i] {
+                if let Err(truncate_error) = file.set_len(*offset).and_then(|()| file.sync_data()) {
+                    tracing::error!(
+                        "Failed to roll back partial write to {}: {}",
+                        path.display(),
+                        truncate_error
+                    );
+                }
+            }
+            return Err(anyhow::Error::from(error).context(format!(
+                "Failed to write to journal file: {}",
+                files[i].0.display()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the contents of one or more files, either completely or not at all.
+///
+/// Each entry is `(path, original, updated)`, where `original` is the current content of the
+/// file. The updated contents are written to temporary files next to the originals, which
+/// are then renamed over them, so a crash or a full disk never leaves a journal half written.
+/// If renaming fails part way, the files already replaced are restored to `original`.
+pub fn rewrite_all_or_nothing(writes: &[(&Path, &[u8], &[u8])]) -> Result<()> {
+    let mut temps = Vec::with_capacity(writes.len());
+    for &(path, _, updated) in writes {
+        match write_temp(path, updated) {
+            Ok(temp) => temps.push(temp),
+            Err(error) => {
+                for temp in &temps {
+                    let _ = std::fs::remove_file(temp);
+                }
+                return Err(error);
+            }
+        }
+    }
+
+    for (i, (&(path, _, _), temp)) in writes.iter().zip(&temps).enumerate() {
+        if let Err(error) = replace(temp, path) {
+            for temp in &temps[i..] {
+                let _ = std::fs::remove_file(temp);
+            }
+            for &(path, original, _) in &writes[..i] {
+                if let Err(restore_error) =
+                    write_temp(path, original).and_then(|temp| replace(&temp, path))
+                {
+                    tracing::error!(
+                        "Failed to restore {} after failed write: {:#}",
+                        path.display(),
+                        restore_error
+                    );
+                }
+            }
+            return Err(error);
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the contents of `path`, which doesn't have to exist yet, so that readers see
+/// either the old or the new contents, even after a crash.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let temp = write_temp(path, contents)?;
+    replace(&temp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp);
+    })
+}
+
+/// Write `contents` to a temporary file next to `path`, with the permissions of `path` if
+/// it exists.
+fn write_temp(path: &Path, contents: &[u8]) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Not a file: {}", path.display()))?;
+    let temp = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    let write = || -> std::io::Result<()> {
+        let permissions = match std::fs::metadata(path) {
+            Ok(metadata) => Some(metadata.permissions()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error),
+        };
+        let mut file = File::create(&temp)?;
+        file.write_all(contents)?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.sync_all()
+    };
+    if let Err(error) = write() {
+        let _ = std::fs::remove_file(&temp);
+        return Err(anyhow::Error::from(error)
+            .context(format!("Failed to write to file: {}", path.display())));
+    }
+    Ok(temp)
+}
+
+/// Rename `temp` over `path`, and sync the directory so the rename survives a crash.
+fn replace(temp: &Path, path: &Path) -> Result<()> {
+    std::fs::rename(temp, path)
+        .with_context(|| format!("Failed to write to file: {}", path.display()))?;
+
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .with_context(|| format!("Failed to sync directory: {}", dir.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Insert `text` into `source` at the position of `directive` in date order.
+///
+/// The text is placed after the last directive that sorts on or before `directive`
+/// (by [`crate::sorting::directive_cmp`]), or before the first directive if there is none.
+/// Everything else in `source`, including comments and formatting, is left untouched.
+pub fn insert_in_date_order(source: &str, directive: &Directive, text: &str) -> String {
+    let new_date = directive.date.to_string();
+    let mut first_directive = None;
+    let mut insert_after = None;
+
+    for span in entry_spans(source) {
+        // Only dated entries are directives; options, includes etc. are skipped.
+        let Some(date) = source[span.clone()]
+            .get(..10)
+            .map(|date| date.replace('/', "-"))
+            .filter(|date| date.starts_with(|c: char| c.is_ascii_digit()))
+        else {
+            continue;
+        };
+        first_directive.get_or_insert(span.start);
+
+        let is_before = match date.as_str().cmp(new_date.as_str()) {
+            Ordering::Less => true,
+            Ordering::Greater => false,
+            // Same date: fall back to the full ordering, which needs the parsed directive.
+            Ordering::Equal => parse_directive(&source[span.clone()]).is_none_or(|existing| {
+                crate::sorting::directive_cmp(&existing, directive) != Ordering::Greater
+            }),
+        };
+        if is_before {
+            insert_after = Some(span.end);
+        }
+    }
+
+    let position = insert_after.or(first_directive).unwrap_or(source.len());
+    let (before, after) = source.split_at(position);
+    let text = text.trim_matches('\n');
+
+    // Keep the entry separated from its neighbours by a blank line, like appended commits.
+    let mut result = String::with_capacity(source.len() + text.len() + 3);
+    result.push_str(before);
+    if !result.is_empty() {
+        if !result.ends_with('\n') {
+            result.push('\n');
+        }
+        if !result.ends_with("\n\n") {
+            result.push('\n');
+        }
+    }
+    result.push_str(text);
+    result.push('\n');
+    if !after.is_empty() && !after.starts_with('\n') {
+        result.push('\n');
+    }
+    result.push_str(after);
+    result
+}
+
+fn parse_directive(source: &str) -> Option<Directive> {
+    beancount_parser::parse_iter::<Decimal>(source).find_map(|entry| match entry {
+        Ok(Entry::Directive(directive)) => Some(directive),
+        _ => None,
+    })
+}
+
+/// Split beancount source into the byte ranges of its top-level entries.
+///
+/// An entry starts at a non-indented line and extends over all following indented
+/// lines (postings, metadata). Blank lines and top-level comments are not part of any
+/// entry. Lines inside a multiline string never start a new entry.
+pub fn entry_spans(source: &str) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    let mut in_string = false;
+    let mut offset = 0;
+
+    for line in source.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        let starts_entry = !in_string
+            && line
+                .chars()
+                .next()
+                .is_some_and(|c| !c.is_whitespace() && c != ';');
+        if starts_entry {
+            spans.push(line_start..offset);
+        } else if let Some(span) = spans.last_mut()
+            && (in_string || line.starts_with(char::is_whitespace) && !line.trim().is_empty())
+        {
+            span.end = offset;
+        }
+
+        in_string = scan_string_state(line, in_string);
+    }
+
+    spans
+}
+
+/// Whether a string literal is still open at the end of `line`.
+fn scan_string_state(line: &str, mut in_string: bool) -> bool {
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            ';' if !in_string => break,
+            _ => {}
+        }
+    }
+    in_string
+}
+
+/// Remove the entry at `span` from `source`, together with the blank lines separating it
+/// from the previous entry.
+pub fn remove_span(source: &str, span: Range<usize>) -> String {
+    let before = source[..span.start].trim_end();
+    let after = &source[span.end..];
+
+    let mut result = String::with_capacity(source.len());
+    if !before.is_empty() {
+        result.push_str(before);
+        result.push('\n');
+    }
+    result.push_str(after);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans_text(source: &str) -> Vec<&str> {
+        entry_spans(source)
+            .into_iter()
+            .map(|span| &source[span])
+            .collect()
+    }
+
+    #[test]
+    fn entry_spans_split_directives() {
+        let source = r#"option "title" "Test"
+
+2024-01-01 open Assets:Checking
+; comment
+2024-01-15 * "Payee" "Narration"
+  source_desc: "original"
+  Assets:Checking  -50.00 USD
+
+  Expenses:Food
+
+
+2024-01-16 balance Assets:Checking 0 USD
+"#;
+        insta::assert_debug_snapshot!(spans_text(source), @r#"
+        [
+            "option \"title\" \"Test\"\n",
+            "2024-01-01 open Assets:Checking\n",
+            "2024-01-15 * \"Payee\" \"Narration\"\n  source_desc: \"original\"\n  Assets:Checking  -50.00 USD\n\n  Expenses:Food\n",
+            "2024-01-16 balance Assets:Checking 0 USD\n",
+        ]
+        "#);
+    }
+
+    #[test]
+    fn entry_spans_multiline_string() {
+        let source = r#"2024-01-15 * "Payee" "first line
+2024-01-16 is not a directive; nor a comment \" quote
+last line"
+  Assets:Checking  -50.00 USD
+2024-01-17 * "Next"
+  Assets:Checking  -1.00 USD
+"#;
+        let spans = spans_text(source);
+        assert_eq!(spans.len(), 2);
+        assert!(spans[0].ends_with("-50.00 USD\n"));
+        assert!(spans[1].starts_with("2024-01-17"));
+    }
+
+    #[test]
+    fn remove_span_restores_previous_layout() {
+        let original = "2024-01-01 open Assets:Checking\n";
+        let appended = format!("{original}\n2024-01-15 * \"Payee\"\n  Assets:Checking -1 USD\n");
+        let span = entry_spans(&appended).pop().unwrap();
+        assert_eq!(remove_span(&appended, span), original);
+
+        let source = "2024-01-01 open A\n\n2024-01-02 open B\n\n2024-01-03 open C\n";
+        let span = entry_spans(source)[1].clone();
+        assert_eq!(
+            remove_span(source, span),
+            "2024-01-01 open A\n\n2024-01-03 open C\n"
+        );
+    }
+
+    #[test]
+    fn rewrite_all_or_nothing_replaces_files() {
+        let dir =
+            std::env::temp_dir().join(format!("beancount-staging-rewrite-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let journal = dir.join("journal.beancount");
+        let other = dir.join("other.beancount");
+        std::fs::write(&journal, "old journal\n").unwrap();
+        std::fs::write(&other, "old other\n").unwrap();
+
+        rewrite_all_or_nothing(&[
+            (&journal, b"old journal\n", b"new journal\n"),
+            (&other, b"old other\n", b"new other\n"),
+        ])
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&journal).unwrap(), "new journal\n");
+        assert_eq!(std::fs::read_to_string(&other).unwrap(), "new other\n");
+
+        // Nothing is replaced if one of the files can't be written
+        let missing = dir.join("missing/journal.beancount");
+        let result = rewrite_all_or_nothing(&[
+            (&journal, b"new journal\n", b"newer journal\n"),
+            (&missing, b"", b"newer missing\n"),
+        ]);
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&journal).unwrap(), "new journal\n");
+
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["journal.beancount", "other.beancount"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn write_atomically_creates_and_replaces() {
+        let dir =
+            std::env::temp_dir().join(format!("beancount-staging-atomic-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("model.bin");
+
+        write_atomically(&path, b"first").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"first");
+        write_atomically(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(files, ["model.bin"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn insert_in_date_order_same_date_uses_directive_order() {
+        let source = r#"2024-01-15 open Assets:Checking
+2024-01-15 balance Assets:Checking 0 USD
+"#;
+        let directive = beancount_parser::parse::<Decimal>(
+            "2024-01-15 * \"Payee\"\n  Assets:Checking -1.00 USD\n",
+        )
+        .unwrap()
+        .directives
+        .remove(0);
+
+        let updated = insert_in_date_order(source, &directive, &directive.to_string());
+        insta::assert_snapshot!(updated, @r#"
+        2024-01-15 open Assets:Checking
+
+        2024-01-15 * "Payee"
+          Assets:Checking -1.00 USD
+
+        2024-01-15 balance Assets:Checking 0 USD
+        "#);
+    }
+}
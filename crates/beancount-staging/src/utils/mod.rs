@@ -1 +1,4 @@
+pub mod glob;
+pub mod journal_file;
 pub mod sort_merge_diff;
+pub mod stable_hash;
@@ -0,0 +1,93 @@
+//! A hasher for keys persisted to disk.
+//!
+//! `DefaultHasher` may change between Rust releases, which would invalidate every cache
+//! after a toolchain update, or worse, make an old cache entry match a new key. The same
+//! goes for the bytes std's `Hash` impls feed into a hasher, so keys are built from
+//! explicitly written values instead.
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a over explicitly written values: integers as little endian, byte strings
+/// prefixed with their length.
+///
+/// It doesn't implement `Hasher` on purpose, the layout of std's `Hash` impls is
+/// unspecified.
+#[derive(Debug, Clone)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn write_u64(&mut self, i: u64) {
+        self.update(&i.to_le_bytes());
+    }
+
+    pub fn write_u128(&mut self, i: u128) {
+        self.update(&i.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u64(bytes.len() as u64);
+        self.update(bytes);
+    }
+
+    pub fn write_str(&mut self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+
+    pub fn write_option_str(&mut self, s: Option<&str>) {
+        match s {
+            None => self.update(&[0]),
+            Some(s) => {
+                self.update(&[1]);
+                self.write_str(s);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_fnv1a_reference() {
+        let mut hasher = StableHasher::new();
+        hasher.update(b"foobar");
+        assert_eq!(hasher.finish(), 0x85944171f73967e8);
+
+        let mut a = StableHasher::new();
+        let mut b = StableHasher::new();
+        a.write_str("ab");
+        a.write_str("c");
+        b.write_str("a");
+        b.write_str("bc");
+        assert_ne!(a.finish(), b.finish());
+
+        let mut none = StableHasher::new();
+        let mut empty = StableHasher::new();
+        none.write_option_str(None);
+        empty.write_option_str(Some(""));
+        assert_ne!(none.finish(), empty.finish());
+    }
+}